@@ -0,0 +1,142 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::path::Path;
+
+use crate::template::{missing_variables, ComposedTemplate, TemplateVariable};
+
+/// One row of a batch dataset: its fields, or why they could not be read.
+pub type BatchRow = std::result::Result<tera::Context, String>;
+
+/// The outcome of rendering a prompt for one row of a batch.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct BatchResult {
+    /// The position of the row in the dataset, starting at 0.
+    pub index: usize,
+    /// The hash of the rendered prompt.
+    pub hash: String,
+    /// The rendered prompt, if the row rendered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Why the row failed to render.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Reads the rows of a CSV or JSONL dataset, chosen by the file's extension.
+///
+/// CSV files need a header row naming the fields. Each non-blank JSONL line
+/// must hold an object. A row that cannot be parsed is returned as an error
+/// so the rest of the batch can still render.
+pub fn read_rows(path: &Path) -> Result<Vec<BatchRow>> {
+    let extension = path.extension().and_then(|e| e.to_str()).map(str::to_lowercase);
+    match extension.as_deref() {
+        Some("csv") => read_csv(path),
+        Some("jsonl" | "ndjson") => read_jsonl(path),
+        _ => Err(anyhow::anyhow!(
+            "Unsupported batch file `{}`, expected a .csv, .jsonl or .ndjson extension",
+            path.display()
+        )),
+    }
+}
+
+fn read_csv(path: &Path) -> Result<Vec<BatchRow>> {
+    let mut reader = csv::Reader::from_path(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read batch file `{}`: {}", path.display(), e))?;
+    let headers = reader.headers()?.clone();
+    Ok(reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|e| e.to_string())?;
+            let mut context = tera::Context::new();
+            for (header, value) in headers.iter().zip(record.iter()) {
+                context.insert(header, value);
+            }
+            Ok(context)
+        })
+        .collect())
+}
+
+fn read_jsonl(path: &Path) -> Result<Vec<BatchRow>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read batch file `{}`: {}", path.display(), e))?;
+    Ok(text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            let value: serde_json::Value =
+                serde_json::from_str(line).map_err(|e| format!("Invalid JSON on line {}: {}", number + 1, e))?;
+            if !value.is_object() {
+                return Err(format!("Line {} is not a JSON object", number + 1));
+            }
+            tera::Context::from_value(value).map_err(|e| e.to_string())
+        })
+        .collect())
+}
+
+/// Renders `template` once per row, spreading the rows over `jobs` threads.
+///
+/// Each row's fields are layered over `base`, so shared variables can come
+/// from the command line while rows override them. Results keep the order
+/// of the rows, and a failing row does not stop the others.
+pub fn render_batch(
+    template: &ComposedTemplate,
+    hash: &str,
+    base: &tera::Context,
+    rows: Vec<BatchRow>,
+    jobs: usize,
+) -> Result<Vec<BatchResult>> {
+    let variables = template.variables()?;
+    let rows: Vec<(usize, BatchRow)> = rows.into_iter().enumerate().collect();
+    let chunk_size = rows.len().div_ceil(jobs.max(1)).max(1);
+
+    let results = std::thread::scope(|scope| {
+        let handles: Vec<_> = rows
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let variables = &variables;
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|(index, row)| render_row(template, variables, hash, base, *index, row))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("batch rendering thread panicked"))
+            .collect()
+    });
+    Ok(results)
+}
+
+fn render_row(
+    template: &ComposedTemplate,
+    variables: &[TemplateVariable],
+    hash: &str,
+    base: &tera::Context,
+    index: usize,
+    row: &BatchRow,
+) -> BatchResult {
+    let rendered = row.clone().and_then(|fields| {
+        let mut context = base.clone();
+        context.extend(fields);
+        let missing = missing_variables(variables, &context);
+        if !missing.is_empty() {
+            return Err(format!("Missing template variables: {}", missing.join(", ")));
+        }
+        template.render(&context).map_err(|e| e.to_string())
+    });
+
+    let (output, error) = match rendered {
+        Ok(output) => (Some(output), None),
+        Err(error) => (None, Some(error)),
+    };
+    BatchResult {
+        index,
+        hash: hash.to_string(),
+        output,
+        error,
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::provider::Message;
+use crate::storage::hash_content;
+use crate::template::read_data_file;
+
+/// The roles a message in a chat prompt may have.
+pub const ROLES: [&str; 3] = ["system", "user", "assistant"];
+
+/// Writes messages as a transcript, each under a `[role]` header line.
+///
+/// This is the text form of a chat prompt, used for display, search and editing.
+pub fn transcript(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .map(|message| format!("[{}]\n{}", message.role, message.content))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Parses a transcript written by [`transcript`] and possibly edited by hand.
+///
+/// Blank lines around each message are dropped, as are messages left empty.
+pub fn parse_transcript(text: &str) -> Result<Vec<Message>> {
+    let mut messages: Vec<Message> = Vec::new();
+    for line in text.lines() {
+        let header = line.trim().strip_prefix('[').and_then(|l| l.strip_suffix(']'));
+        match header {
+            Some(role) if ROLES.contains(&role) => messages.push(Message {
+                role: role.to_string(),
+                content: String::new(),
+            }),
+            _ => match messages.last_mut() {
+                Some(message) => {
+                    message.content.push_str(line);
+                    message.content.push('\n');
+                }
+                None if line.trim().is_empty() => {}
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "A chat prompt must start with a role header such as `[system]` or `[user]`"
+                    ))
+                }
+            },
+        }
+    }
+    for message in &mut messages {
+        message.content = message.content.trim().to_string();
+    }
+    messages.retain(|message| !message.content.is_empty());
+    check_messages(&messages)?;
+    Ok(messages)
+}
+
+/// Reads a list of messages from a JSON, TOML or YAML file.
+///
+/// The list may be wrapped in a table under `messages`, as in an OpenAI
+/// request body and as TOML requires.
+pub fn read_messages(path: &Path) -> Result<Vec<Message>> {
+    let value = match read_data_file(path, "message")? {
+        serde_json::Value::Object(mut table) if table.contains_key("messages") => {
+            table.remove("messages").unwrap_or_default()
+        }
+        value => value,
+    };
+    let messages: Vec<Message> = serde_json::from_value(value)
+        .map_err(|e| anyhow::anyhow!("Invalid message file `{}`: {}", path.display(), e))?;
+    check_messages(&messages)?;
+    Ok(messages)
+}
+
+/// Computes the hash of a chat prompt from its messages as canonical JSON.
+pub fn hash_messages(messages: &[Message]) -> String {
+    let json = serde_json::to_string(messages).unwrap_or_default();
+    hash_content(&json)
+}
+
+fn check_messages(messages: &[Message]) -> Result<()> {
+    if messages.is_empty() {
+        return Err(anyhow::anyhow!("A chat prompt needs at least one message"));
+    }
+    if let Some(message) = messages.iter().find(|m| !ROLES.contains(&m.role.as_str())) {
+        return Err(anyhow::anyhow!(
+            "Unknown message role `{}`, expected one of {}",
+            message.role,
+            ROLES.join(", ")
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::batch::BatchRow;
+use crate::core::Prompts;
+use crate::eval::Assertion;
+use crate::provider::{CompletionRequest, Provider, Usage};
+use crate::storage::Prompt;
+use crate::template::missing_variables;
+
+/// The result of running one prompt variant on one input.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Comparison {
+    /// The position of the input in the dataset, starting at 0.
+    pub row: usize,
+    /// The hash of the prompt variant.
+    pub prompt_hash: String,
+    /// The id of the recorded run, if the provider answered.
+    pub run_id: Option<String>,
+    /// The provider's output.
+    pub output: Option<String>,
+    /// The length of the output in characters.
+    pub length: usize,
+    /// How long the provider took to answer, in milliseconds.
+    pub latency_ms: u64,
+    /// Token counts, if the provider reported them.
+    pub usage: Option<Usage>,
+    /// How many of the assertions passed.
+    pub passed: usize,
+    /// The share of assertions that passed, from 0 to 1, or `None` when
+    /// there were no assertions.
+    pub score: Option<f64>,
+    /// Why assertions failed, or why the variant produced no output.
+    pub failures: Vec<String>,
+}
+
+/// How to run and score the prompt variants being compared.
+pub struct CompareOptions<'a> {
+    /// The name the runs are recorded under.
+    pub provider_name: &'a str,
+    pub provider: &'a (dyn Provider + Send + Sync),
+    /// The model to use instead of the provider's configured one.
+    pub model: Option<String>,
+    /// The checks each output is scored by.
+    pub assertions: &'a [Assertion],
+}
+
+/// Runs every prompt on every row through the provider, recording each run.
+///
+/// Rows are layered over `base` as in batch generation. Results are grouped
+/// by row, with the prompts in the order given.
+pub async fn compare_prompts(
+    prompts_api: &Prompts,
+    prompts: &[Prompt],
+    base: &tera::Context,
+    rows: Vec<BatchRow>,
+    options: &CompareOptions<'_>,
+) -> Result<Vec<Comparison>> {
+    let mut templates = Vec::new();
+    for prompt in prompts {
+        let template = prompts_api.compose(prompt).await?;
+        let variables = template.variables()?;
+        templates.push((prompt, template, variables));
+    }
+
+    let mut comparisons = Vec::new();
+    for (row, fields) in rows.into_iter().enumerate() {
+        for (prompt, template, variables) in &templates {
+            let mut comparison = Comparison {
+                row,
+                prompt_hash: prompt.hash.clone(),
+                run_id: None,
+                output: None,
+                length: 0,
+                latency_ms: 0,
+                usage: None,
+                passed: 0,
+                score: None,
+                failures: Vec::new(),
+            };
+
+            let rendered = fields.clone().and_then(|fields| {
+                let mut context = base.clone();
+                context.extend(fields);
+                let missing = missing_variables(variables, &context);
+                if !missing.is_empty() {
+                    return Err(format!("Missing template variables: {}", missing.join(", ")));
+                }
+                let messages = template.render_messages(&context).map_err(|e| e.to_string())?;
+                Ok((messages, context.into_json()))
+            });
+            let run = match rendered {
+                Ok((messages, variables)) => {
+                    let request = CompletionRequest {
+                        messages,
+                        model: options.model.clone(),
+                    };
+                    prompts_api
+                        .execute(prompt, options.provider_name, options.provider, &request, variables, None)
+                        .await
+                        .map_err(|e| e.to_string())
+                }
+                Err(error) => Err(error),
+            };
+
+            match run {
+                Ok(run) => {
+                    for assertion in options.assertions {
+                        match assertion.check(&run.output) {
+                            Ok(()) => comparison.passed += 1,
+                            Err(failure) => comparison.failures.push(failure),
+                        }
+                    }
+                    comparison.length = run.output.chars().count();
+                    comparison.latency_ms = run.latency_ms;
+                    comparison.usage = run.usage;
+                    comparison.run_id = Some(run.id);
+                    comparison.output = Some(run.output);
+                }
+                Err(error) => comparison.failures.push(error),
+            }
+            let total = options.assertions.len();
+            comparison.score = (total > 0).then(|| comparison.passed as f64 / total as f64);
+            comparisons.push(comparison);
+        }
+    }
+    Ok(comparisons)
+}
@@ -1,7 +1,13 @@
 use anyhow::Result;
-use fuzzy_matcher::skim::SkimMatcherV2;
-use fuzzy_matcher::FuzzyMatcher;
-use sha2::{Digest, Sha256};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::str::FromStr;
+use crate::chat;
+use crate::eval::{run_test_cases, TestCase, TestResult};
+use crate::provider::{CompletionRequest, Message, Provider};
+use crate::query::Query;
+use crate::storage::{check_name, hash_content, name_taken, Prompt, Revision, Run, StorageQuery, SyncReport};
+pub use crate::storage::SearchResult;
+use crate::template::ComposedTemplate;
 
 pub struct Prompts {
     storage: Box<dyn crate::storage::Storage + Send + Sync>,
@@ -17,23 +23,91 @@ impl Prompts {
         if prompts.iter().any(|p| p.hash == prompt.hash) {
             return Ok(false);
         }
+        if prompt.id.is_empty() {
+            prompt.id = prompt.hash.clone();
+        }
+        let created_at = *prompt.created_at.get_or_insert_with(Utc::now);
+        prompt.updated_at.get_or_insert(created_at);
+        // A prompt added again after it was deleted keeps its earlier history.
+        // The prompt itself is saved last, as for edits, so that it completes the change.
+        if self.storage.load_revisions(&prompt.id).await?.is_empty() {
+            self.storage.save_revision(&prompt.id, &Revision::new(1, prompt)).await?;
+        }
         self.storage.save_prompt(prompt).await?;
         Ok(true)
     }
 
     pub async fn list_prompts(&self, tags: Option<Vec<String>>) -> Result<Vec<crate::storage::Prompt>> {
-        let prompts = self.storage.load_prompts().await?;
         if let Some(tags) = tags {
-            let search_results = search_prompts(&prompts, "", &tags, &[]);
-            Ok(search_results)
+            let search_results = self.search("", Some(tags), None).await?;
+            Ok(search_results.into_iter().map(|r| r.prompt).collect())
         } else {
-            Ok(prompts)
+            self.storage.load_prompts().await
         }
     }
 
+    /// Finds the prompts a query refers to.
+    ///
+    /// A query of the form `@prefix`, where the prefix is hexadecimal, selects
+    /// the prompt with that hash or id prefix, and one that is exactly the name or an
+    /// alias of a prompt with the given tags selects that prompt alone. Any
+    /// other query is searched for.
     pub async fn show_prompt(&self, query: &str, tags: Option<Vec<String>>) -> Result<Vec<crate::storage::Prompt>> {
+        let hash_prefix = query.strip_prefix('@').filter(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_hexdigit()));
+        if let Some(prefix) = hash_prefix {
+            return Ok(vec![self.find_by_hash(prefix).await?]);
+        }
+        if let Some(prompt) = self.find_by_name(query).await? {
+            let prompt_tags = prompt.tags.as_deref().unwrap_or_default();
+            if tags.as_deref().unwrap_or_default().iter().all(|tag| prompt_tags.contains(tag)) {
+                return Ok(vec![prompt]);
+            }
+        }
+        let search_results = self.search(query, tags, None).await?;
+        Ok(search_results.into_iter().map(|r| r.prompt).collect())
+    }
+
+    /// Searches for prompts, best match first, returning at most `limit` results.
+    ///
+    /// The query uses the syntax described in [`crate::query`]. Text that does
+    /// not parse as a query, such as `Note: summarize`, is searched for as is;
+    /// the parse error is only returned if that finds nothing. The search runs
+    /// inside the storage backend when it supports it. Full-text indexes match
+    /// words by prefix, so if the backend finds nothing for a query with fuzzy
+    /// terms, the in-memory fuzzy search is used so that abbreviations and
+    /// typos still match.
+    pub async fn search(&self, query: &str, tags: Option<Vec<String>>, limit: Option<usize>) -> Result<Vec<SearchResult>> {
+        let (filter, parse_error) = match Query::parse(query) {
+            Ok(filter) => (filter, None),
+            Err(err) => (Query::Fuzzy(query.to_string()), Some(err)),
+        };
+        let storage_query = StorageQuery { filter: filter.with_filters(&tags.unwrap_or_default(), &[]), limit };
+        let search_results = self.run_search(&storage_query).await?;
+        match parse_error {
+            Some(err) if search_results.is_empty() => Err(err.into()),
+            _ => Ok(search_results),
+        }
+    }
+
+    async fn run_search(&self, storage_query: &StorageQuery) -> Result<Vec<SearchResult>> {
+        // Bare words match fuzzily, which no index does, so queries holding
+        // them are always run in memory to find the same prompts on every backend.
+        if !storage_query.filter.has_fuzzy_terms() {
+            if let Some(mut search_results) = self.storage.query_prompts(storage_query).await? {
+                for result in &mut search_results {
+                    if let Some(query_match) = storage_query.filter.evaluate(&result.prompt) {
+                        result.indices = query_match.indices;
+                    }
+                }
+                return Ok(search_results);
+            }
+        }
+
         let prompts = self.storage.load_prompts().await?;
-        let search_results = search_prompts(&prompts, query, &tags.unwrap_or_default(), &[]);
+        let mut search_results = rank_prompts(&prompts, &storage_query.filter);
+        if let Some(limit) = storage_query.limit {
+            search_results.truncate(limit);
+        }
         Ok(search_results)
     }
 
@@ -51,9 +125,9 @@ impl Prompts {
 
         if let Some(prompt) = prompt_to_edit {
             if let Some(text) = new_text {
-                prompt.content = text;
-                let hash = Sha256::digest(prompt.content.as_bytes());
-                prompt.hash = format!("{:x}", hash);
+                let previous = prompt.clone();
+                prompt.set_content(&text)?;
+                self.record_revision(prompt, &previous).await?;
             }
 
             let mut tags = prompt.tags.clone().unwrap_or_default();
@@ -77,29 +151,407 @@ impl Prompts {
                 categories.retain(|c| !categories_to_remove.contains(c));
             }
             prompt.categories = Some(categories);
+            prompt.updated_at = Some(Utc::now());
 
-            self.storage.delete_prompt(hash).await?;
-            self.storage.save_prompt(prompt).await?;
+            self.storage.replace_prompt(hash, prompt).await?;
         }
 
         Ok(())
     }
 
+    /// Replaces the messages of the chat prompt with the given hash.
+    pub async fn set_messages(&self, hash: &str, messages: Vec<Message>) -> Result<()> {
+        let prompts = self.storage.load_prompts().await?;
+        let mut prompt = prompts
+            .into_iter()
+            .find(|p| p.hash == hash)
+            .ok_or_else(|| anyhow::anyhow!("No prompt found with hash {}", hash))?;
+        let previous = prompt.clone();
+        prompt.set_messages(messages)?;
+        self.record_revision(&prompt, &previous).await?;
+        prompt.updated_at = Some(Utc::now());
+        self.storage.replace_prompt(hash, &mut prompt).await
+    }
+
+    /// Appends a revision for the prompt's content if its hash changed from the `previous` version.
+    ///
+    /// Prompts saved before histories were kept get their previous content as revision 1.
+    async fn record_revision(&self, prompt: &Prompt, previous: &Prompt) -> Result<()> {
+        if prompt.hash == previous.hash {
+            return Ok(());
+        }
+        let mut revisions = self.storage.load_revisions(&prompt.id).await?;
+        if revisions.is_empty() {
+            let initial = Revision::new(1, previous);
+            self.storage.save_revision(&prompt.id, &initial).await?;
+            revisions.push(initial);
+        }
+        self.storage.save_revision(&prompt.id, &Revision::new(revisions.len() + 1, prompt)).await
+    }
+
+    /// Finds the prompt whose hash or id starts with `prefix`. The id is the
+    /// hash a prompt was added with, so it keeps selecting the prompt after edits.
+    ///
+    /// Fails if no prompt matches, or if several do, listing them so that a
+    /// longer prefix can be chosen.
+    pub async fn find_by_hash(&self, prefix: &str) -> Result<Prompt> {
+        let prefix = check_hash_prefix(prefix)?;
+        let prompts = self.storage.find_by_hash_prefix(&prefix).await?;
+        only_match(&prefix, prompts, "No prompt found")
+    }
+
+    /// Finds the prompt with the given name or alias.
+    pub async fn find_by_name(&self, name: &str) -> Result<Option<Prompt>> {
+        if check_name(name).is_err() {
+            return Ok(None);
+        }
+        self.storage.find_by_name(name).await
+    }
+
+    /// Replaces the name and aliases of the prompt with the given hash.
+    ///
+    /// Fails without changing anything if another prompt already uses one of
+    /// the names.
+    pub async fn set_names(&self, hash: &str, name: Option<String>, aliases: Vec<String>) -> Result<()> {
+        let prompts = self.storage.load_prompts().await?;
+        let mut prompt = prompts
+            .into_iter()
+            .find(|p| p.hash == hash)
+            .ok_or_else(|| anyhow::anyhow!("Prompt {} not found", hash))?;
+        prompt.name = name;
+        prompt.aliases = aliases;
+        prompt.updated_at = Some(Utc::now());
+        for name in prompt.names() {
+            check_name(name)?;
+            if let Some(other) = self.storage.find_by_name(name).await?.filter(|p| p.hash != hash) {
+                return Err(name_taken(name, &other.hash));
+            }
+        }
+        self.storage.replace_prompt(hash, &mut prompt).await
+    }
+
+    /// Records that the prompt with the given hash was just used, bumping
+    /// its use count.
+    pub async fn record_use(&self, hash: &str) -> Result<()> {
+        self.storage.record_use(hash, Utc::now()).await
+    }
+
+    /// Moves a prompt to the trash, from where `restore_prompt` can bring it back.
     pub async fn delete_prompt(&self, hash: &str) -> Result<()> {
-        self.storage.delete_prompt(hash).await
+        self.storage.trash_prompt(hash, Utc::now()).await
+    }
+
+    /// Lists the prompts in the trash, most recently deleted first.
+    pub async fn list_trash(&self) -> Result<Vec<Prompt>> {
+        let mut prompts = self.storage.load_trash().await?;
+        prompts.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then(a.hash.cmp(&b.hash)));
+        Ok(prompts)
+    }
+
+    /// Moves the trashed prompt whose hash starts with `prefix` back among the
+    /// live prompts and returns it.
+    ///
+    /// Fails, leaving the prompt in the trash, if another prompt has taken one
+    /// of its names since it was deleted.
+    pub async fn restore_prompt(&self, prefix: &str) -> Result<Prompt> {
+        let prefix = check_hash_prefix(prefix)?;
+        let trashed: Vec<Prompt> = self
+            .storage
+            .load_trash()
+            .await?
+            .into_iter()
+            .filter(|p| p.hash.starts_with(&prefix))
+            .collect();
+        let mut prompt = only_match(&prefix, trashed, "No prompt in the trash")?;
+        self.storage.restore_prompt(&prompt.hash).await?;
+        prompt.deleted_at = None;
+        Ok(prompt)
+    }
+
+    /// Deletes the trashed prompts for good, only those deleted before
+    /// `deleted_before` if it is given, and returns them.
+    pub async fn purge_trash(&self, deleted_before: Option<DateTime<Utc>>) -> Result<Vec<Prompt>> {
+        let mut purged = self.list_trash().await?;
+        purged.retain(|p| deleted_before.is_none_or(|before| p.deleted_at.is_some_and(|at| at < before)));
+        for prompt in &purged {
+            self.storage.purge_prompt(&prompt.hash).await?;
+        }
+        Ok(purged)
+    }
+
+    /// Resolves the prompts `prompt` includes, imports or extends from the store.
+    pub async fn compose(&self, prompt: &Prompt) -> Result<ComposedTemplate> {
+        let prompts = self.storage.load_prompts().await?;
+        ComposedTemplate::new(prompt, &prompts)
+    }
+
+    /// Replaces the test cases of the prompt with the given hash.
+    pub async fn set_tests(&self, hash: &str, tests: Vec<TestCase>) -> Result<()> {
+        let prompts = self.storage.load_prompts().await?;
+        let mut prompt = prompts
+            .into_iter()
+            .find(|p| p.hash == hash)
+            .ok_or_else(|| anyhow::anyhow!("Prompt {} not found", hash))?;
+        prompt.tests = tests;
+        prompt.updated_at = Some(Utc::now());
+        self.storage.replace_prompt(hash, &mut prompt).await
+    }
+
+    /// Runs the test cases attached to `prompt`, optionally through `provider`.
+    pub async fn test_prompt(
+        &self,
+        prompt: &Prompt,
+        provider: Option<&(dyn Provider + Send + Sync)>,
+    ) -> Result<Vec<TestResult>> {
+        let template = self.compose(prompt).await?;
+        run_test_cases(&template, &prompt.tests, provider).await
     }
+
+    /// Sends `request`, rendered from `prompt` with `variables`, to `provider`
+    /// and records the result in the run log.
+    ///
+    /// `on_token` is passed to the provider to stream the output as it arrives.
+    pub async fn execute(
+        &self,
+        prompt: &Prompt,
+        provider_name: &str,
+        provider: &(dyn Provider + Send + Sync),
+        request: &CompletionRequest,
+        variables: serde_json::Value,
+        on_token: Option<&mut (dyn for<'t> FnMut(&'t str) + Send)>,
+    ) -> Result<Run> {
+        let started = std::time::Instant::now();
+        let completion = provider.complete(request, on_token).await?;
+        let latency_ms = started.elapsed().as_millis() as u64;
+        let created_at = chrono::Utc::now();
+
+        let input = match request.messages.as_slice() {
+            [message] => message.content.clone(),
+            messages => chat::transcript(messages),
+        };
+        let run = Run {
+            id: hash_content(&format!("{}\n{}\n{}\n{}", prompt.hash, created_at.to_rfc3339(), input, completion.content)),
+            prompt_id: prompt.id.clone(),
+            prompt_hash: prompt.hash.clone(),
+            input,
+            variables,
+            provider: provider_name.to_string(),
+            model: completion.model,
+            output: completion.content,
+            latency_ms,
+            usage: completion.usage,
+            created_at,
+        };
+        self.storage.save_run(&run).await?;
+        Ok(run)
+    }
+
+    /// Pulls the latest changes to a shared library from its server.
+    pub async fn sync(&self) -> Result<SyncReport> {
+        self.storage.sync().await
+    }
+
+    /// Returns the recorded runs, optionally only those of the prompt with
+    /// the given id, oldest first.
+    pub async fn runs(&self, prompt_id: Option<&str>) -> Result<Vec<Run>> {
+        self.storage.load_runs(prompt_id).await
+    }
+
+    /// Finds the run whose id starts with `prefix`.
+    pub async fn find_run(&self, prefix: &str) -> Result<Run> {
+        let mut runs: Vec<Run> = self
+            .storage
+            .load_runs(None)
+            .await?
+            .into_iter()
+            .filter(|run| run.id.starts_with(prefix))
+            .collect();
+        match runs.len() {
+            1 => Ok(runs.remove(0)),
+            0 => Err(anyhow::anyhow!("No run found with id `{}`", prefix)),
+            n => Err(anyhow::anyhow!("Run id `{}` is ambiguous, it matches {} runs", prefix, n)),
+        }
+    }
+
+    /// Returns the content history of the prompt with the given id, oldest first.
+    ///
+    /// Prompts saved before history was recorded report their current content
+    /// as the only revision.
+    pub async fn history(&self, id: &str) -> Result<Vec<Revision>> {
+        let revisions = self.storage.load_revisions(id).await?;
+        if !revisions.is_empty() {
+            return Ok(revisions);
+        }
+        let prompts = self.storage.load_prompts().await?;
+        Ok(prompts
+            .into_iter()
+            .find(|p| p.id == id)
+            .map(|p| vec![Revision::new(1, &p)])
+            .unwrap_or_default())
+    }
+
+    /// Returns a single revision of the prompt with the given id.
+    pub async fn get_revision(&self, id: &str, number: usize) -> Result<Option<Revision>> {
+        let revisions = self.history(id).await?;
+        Ok(revisions.into_iter().find(|r| r.number == number))
+    }
+
+    /// Restores the content of an earlier revision.
+    ///
+    /// The history is append-only, so reverting records the restored content
+    /// as a new revision rather than discarding the later ones. A chat prompt
+    /// gets back the messages of the revision, where they were recorded.
+    pub async fn revert_prompt(&self, id: &str, number: usize) -> Result<()> {
+        let revision = self
+            .get_revision(id, number)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Revision {} not found for prompt {}", number, id))?;
+        let prompts = self.storage.load_prompts().await?;
+        let prompt = prompts
+            .iter()
+            .find(|p| p.id == id)
+            .ok_or_else(|| anyhow::anyhow!("Prompt {} not found", id))?;
+        if revision.messages.is_empty() {
+            self.edit_prompt(&prompt.hash, Some(revision.content), None, None, None, None).await
+        } else {
+            self.set_messages(&prompt.hash, revision.messages).await
+        }
+    }
+}
+
+/// Lowercases a hash prefix, failing if it is not made of hex digits.
+fn check_hash_prefix(prefix: &str) -> Result<String> {
+    let prefix = prefix.to_ascii_lowercase();
+    if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow::anyhow!("Invalid hash prefix `{}`, expected hexadecimal digits", prefix));
+    }
+    Ok(prefix)
 }
 
+/// Returns the only prompt matching a hash prefix, or an error starting with
+/// `missing` if there is none and one listing the candidates if there are several.
+fn only_match(prefix: &str, mut prompts: Vec<Prompt>, missing: &str) -> Result<Prompt> {
+    match prompts.len() {
+        1 => Ok(prompts.remove(0)),
+        0 => Err(anyhow::anyhow!("{} with hash `{}`", missing, prefix)),
+        n => {
+            prompts.sort_by(|a, b| a.hash.cmp(&b.hash));
+            let candidates: Vec<String> = prompts
+                .iter()
+                .map(|p| format!("  {} - {}", &p.hash[..12.min(p.hash.len())], p.content.lines().next().unwrap_or_default()))
+                .collect();
+            Err(anyhow::anyhow!(
+                "Hash prefix `{}` is ambiguous, it matches {} prompts:\n{}",
+                prefix,
+                n,
+                candidates.join("\n")
+            ))
+        }
+    }
+}
+
+/// Filters prompts by a query string and required tags and categories, best match first.
+///
+/// A query that is not valid query syntax is fuzzy-matched as plain text.
 pub fn search_prompts(prompts: &[crate::storage::Prompt], query: &str, tags: &[String], categories: &[String]) -> Vec<crate::storage::Prompt> {
-    let matcher = SkimMatcherV2::default();
-    prompts.iter().filter(|p| {
-        let content_match = query.is_empty() || matcher.fuzzy_match(&p.content, query).is_some();
-        let tags_match = tags.is_empty() || p.tags.as_ref().map_or(false, |ptags| {
-            tags.iter().all(|tag| ptags.contains(tag))
-        });
-        let categories_match = categories.is_empty() || p.categories.as_ref().map_or(false, |pcats| {
-            categories.iter().all(|cat| pcats.contains(cat))
-        });
-        content_match && tags_match && categories_match
-    }).cloned().collect()
-}
\ No newline at end of file
+    let filter = Query::parse(query)
+        .unwrap_or_else(|_| Query::Fuzzy(query.to_string()))
+        .with_filters(tags, categories);
+    rank_prompts(prompts, &filter).into_iter().map(|r| r.prompt).collect()
+}
+
+/// Filters prompts by a parsed query and orders them by relevance.
+///
+/// Results are sorted by descending score. Ties go to the prompt used most
+/// often, then to the one updated most recently, then to the shorter prompt,
+/// as the query covers more of it, and finally to the hash so that the order
+/// does not depend on the storage backend.
+pub fn rank_prompts(prompts: &[crate::storage::Prompt], query: &Query) -> Vec<SearchResult> {
+    let mut results: Vec<SearchResult> = prompts.iter().filter_map(|p| {
+        let query_match = query.evaluate(p)?;
+        Some(SearchResult { prompt: p.clone(), score: query_match.score, indices: query_match.indices })
+    }).collect();
+    results.sort_by(|a, b| {
+        b.score.total_cmp(&a.score)
+            .then(b.prompt.use_count.cmp(&a.prompt.use_count))
+            .then(PromptTime::Updated.of(&b.prompt).cmp(&PromptTime::Updated.of(&a.prompt)))
+            .then(a.prompt.content.len().cmp(&b.prompt.content.len()))
+            .then(a.prompt.hash.cmp(&b.prompt.hash))
+    });
+    results
+}
+
+/// A timestamp of a prompt that listings can be sorted and filtered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptTime {
+    /// When the prompt was added.
+    Created,
+    /// When the prompt last changed, or was added if it never changed.
+    Updated,
+    /// When the prompt was last used by `generate`.
+    Used,
+}
+
+impl PromptTime {
+    /// Returns this timestamp of `prompt`, if it has one.
+    pub fn of(self, prompt: &Prompt) -> Option<DateTime<Utc>> {
+        match self {
+            PromptTime::Created => prompt.created_at,
+            PromptTime::Updated => prompt.updated_at.or(prompt.created_at),
+            PromptTime::Used => prompt.last_used_at,
+        }
+    }
+}
+
+impl FromStr for PromptTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "created" => Ok(PromptTime::Created),
+            "updated" => Ok(PromptTime::Updated),
+            "used" => Ok(PromptTime::Used),
+            _ => Err(format!("unknown timestamp `{}`, expected created, updated or used", s)),
+        }
+    }
+}
+
+/// Sorts prompts by a timestamp, newest first, with prompts that lack it last.
+///
+/// Ties go to the most used prompt and then to the hash.
+pub fn sort_by_time(prompts: &mut [Prompt], time: PromptTime) {
+    prompts.sort_by(|a, b| {
+        time.of(b)
+            .cmp(&time.of(a))
+            .then(b.use_count.cmp(&a.use_count))
+            .then(a.hash.cmp(&b.hash))
+    });
+}
+
+/// Parses an age such as `30d`, `12h` or `2w` given on the command line and
+/// returns the time that long ago. A date is taken as it is, as by `parse_date`.
+pub fn parse_age(s: &str) -> std::result::Result<DateTime<Utc>, String> {
+    let unit = |suffix: char| s.strip_suffix(suffix).and_then(|n| n.parse::<i64>().ok());
+    let age = if let Some(n) = unit('h') {
+        chrono::Duration::try_hours(n)
+    } else if let Some(n) = unit('d') {
+        chrono::Duration::try_days(n)
+    } else if let Some(n) = unit('w') {
+        chrono::Duration::try_weeks(n)
+    } else {
+        return parse_date(s).map_err(|_| format!("invalid age `{}`, expected e.g. 30d, 12h, 2w or a date", s));
+    };
+    age.and_then(|age| Utc::now().checked_sub_signed(age))
+        .ok_or_else(|| format!("age `{}` is out of range", s))
+}
+
+/// Parses a date given on the command line, either RFC 3339 or `YYYY-MM-DD`
+/// for midnight UTC.
+pub fn parse_date(s: &str) -> std::result::Result<DateTime<Utc>, String> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(s) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+        .map_err(|_| format!("invalid date `{}`, expected YYYY-MM-DD or RFC 3339", s))
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use std::io::Write;
+use std::process::Command;
+
+/// A prompt as written back by the user's editor.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Document {
+    /// The body of the prompt.
+    pub content: String,
+    /// The tags listed in the frontmatter.
+    pub tags: Vec<String>,
+    /// The categories listed in the frontmatter.
+    pub categories: Vec<String>,
+}
+
+impl Document {
+    /// Renders the document as text with a frontmatter block followed by the body.
+    pub fn render(&self) -> String {
+        format!(
+            "---\ntags: {}\ncategories: {}\n---\n{}\n",
+            self.tags.join(", "),
+            self.categories.join(", "),
+            self.content
+        )
+    }
+
+    /// Parses a document previously produced by `render` and edited by the user.
+    ///
+    /// The frontmatter block is optional; without it the whole text is the body.
+    /// Only the newline that `render` puts after the body is removed from it.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut document = Document::default();
+        let mut body = text;
+
+        if text.starts_with("---") {
+            let mut lines = text.split_inclusive('\n');
+            let mut offset = lines.next().map_or(0, str::len);
+            let mut closed = false;
+            for line in lines {
+                offset += line.len();
+                if line.trim() == "---" {
+                    closed = true;
+                    break;
+                }
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let (key, value) = line
+                    .trim_end()
+                    .split_once(':')
+                    .ok_or_else(|| anyhow::anyhow!("Invalid frontmatter line: `{}`", line.trim_end()))?;
+                let values = split_list(value);
+                match key.trim() {
+                    "tags" => document.tags = values,
+                    "categories" => document.categories = values,
+                    other => return Err(anyhow::anyhow!("Unknown frontmatter field: `{}`", other)),
+                }
+            }
+            if !closed {
+                return Err(anyhow::anyhow!("Frontmatter block is not closed with `---`"));
+            }
+            body = &text[offset..];
+        }
+
+        let body = body.strip_suffix('\n').map(|b| b.strip_suffix('\r').unwrap_or(b)).unwrap_or(body);
+        document.content = body.to_string();
+        Ok(document)
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Opens `text` in the user's editor and returns the saved result.
+///
+/// The editor is taken from `$VISUAL`, then `$EDITOR`, falling back to `vi`.
+/// The variable may include arguments, e.g. `code --wait`.
+pub fn edit_text(text: &str) -> Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+
+    let mut file = tempfile::Builder::new().prefix("prompt-").suffix(".md").tempfile()?;
+    file.write_all(text.as_bytes())?;
+    file.flush()?;
+
+    let status = Command::new(program).args(parts).arg(file.path()).status()?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("Editor `{}` exited with {}", editor, status));
+    }
+
+    Ok(std::fs::read_to_string(file.path())?)
+}
@@ -11,6 +11,10 @@ pub enum AppError {
     Config(String),
     #[error("Storage error: {0}")]
     Storage(String),
+    #[error("Invalid query: {0}")]
+    Query(String),
+    #[error("Missing template variables: {}", .0.join(", "))]
+    MissingVariables(Vec<String>),
     #[error("An unexpected error occurred: {0}")]
     Anyhow(String),
 }
@@ -33,8 +37,17 @@ impl From<config::ConfigError> for AppError {
     }
 }
 
+impl From<crate::query::QueryError> for AppError {
+    fn from(err: crate::query::QueryError) -> Self {
+        AppError::Query(err.to_string())
+    }
+}
+
 impl From<anyhow::Error> for AppError {
     fn from(err: anyhow::Error) -> Self {
-        AppError::Anyhow(err.to_string())
+        match err.downcast::<crate::query::QueryError>() {
+            Ok(err) => err.into(),
+            Err(err) => AppError::Anyhow(err.to_string()),
+        }
     }
 }
@@ -0,0 +1,179 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::provider::{CompletionRequest, Provider};
+use crate::template::{missing_variables, read_data_file, ComposedTemplate};
+
+/// A set of variables to render a prompt with and the expectations on the result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestCase {
+    /// A short description of the case, unique within the prompt.
+    pub name: String,
+    /// The template variables to render the prompt with.
+    #[serde(default)]
+    pub variables: serde_json::Map<String, serde_json::Value>,
+    /// The checks the rendered prompt, or the provider's output, must pass.
+    #[serde(default)]
+    pub assertions: Vec<Assertion>,
+}
+
+/// A check on the text produced for a test case.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Assertion {
+    /// The text contains `value`.
+    Contains { value: String },
+    /// The text does not contain `value`.
+    NotContains { value: String },
+    /// The text matches the regular expression `pattern`.
+    Regex { pattern: String },
+    /// The text, ignoring surrounding whitespace, is exactly `value`.
+    Equals { value: String },
+    /// The text is at most `value` characters long.
+    MaxLength { value: usize },
+    /// The text is JSON that is valid against `schema`.
+    JsonSchema { schema: serde_json::Value },
+}
+
+impl Assertion {
+    /// Checks `text`, describing the failure if it does not pass.
+    pub fn check(&self, text: &str) -> std::result::Result<(), String> {
+        match self {
+            Assertion::Contains { value } if !text.contains(value.as_str()) => {
+                Err(format!("expected the output to contain `{}`", value))
+            }
+            Assertion::NotContains { value } if text.contains(value.as_str()) => {
+                Err(format!("expected the output not to contain `{}`", value))
+            }
+            Assertion::Regex { pattern } => {
+                let regex = regex::Regex::new(pattern).map_err(|e| format!("invalid regex `{}`: {}", pattern, e))?;
+                if regex.is_match(text) {
+                    Ok(())
+                } else {
+                    Err(format!("expected the output to match /{}/", pattern))
+                }
+            }
+            Assertion::Equals { value } if text.trim() != value.trim() => {
+                Err(format!("expected the output to equal `{}`", value))
+            }
+            Assertion::MaxLength { value } => {
+                let length = text.chars().count();
+                if length > *value {
+                    Err(format!("expected at most {} characters, got {}", value, length))
+                } else {
+                    Ok(())
+                }
+            }
+            Assertion::JsonSchema { schema } => {
+                let instance: serde_json::Value = serde_json::from_str(text.trim())
+                    .map_err(|e| format!("expected the output to be JSON: {}", e))?;
+                let validator = jsonschema::validator_for(schema).map_err(|e| format!("invalid JSON schema: {}", e))?;
+                let errors: Vec<String> = validator.iter_errors(&instance).map(|e| e.to_string()).collect();
+                if errors.is_empty() {
+                    Ok(())
+                } else {
+                    Err(format!("output does not match the JSON schema: {}", errors.join("; ")))
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The outcome of one test case.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestResult {
+    /// The name of the case.
+    pub name: String,
+    /// The text the assertions were checked against, if it was produced.
+    pub output: Option<String>,
+    /// Why the case failed; empty when it passed.
+    pub failures: Vec<String>,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Reads test cases from a JSON, TOML or YAML file.
+///
+/// The file holds either a list of cases or a table with a `cases` list,
+/// which is the only form TOML allows.
+pub fn read_test_cases(path: &Path) -> Result<Vec<TestCase>> {
+    let value = match read_data_file(path, "test case")? {
+        serde_json::Value::Object(mut table) if table.contains_key("cases") => table.remove("cases").unwrap_or_default(),
+        value => value,
+    };
+    let cases: Vec<TestCase> = serde_json::from_value(value)
+        .map_err(|e| anyhow::anyhow!("Invalid test case file `{}`: {}", path.display(), e))?;
+
+    let mut names = std::collections::HashSet::new();
+    if let Some(case) = cases.iter().find(|case| !names.insert(case.name.as_str())) {
+        return Err(anyhow::anyhow!("Test case `{}` is defined more than once", case.name));
+    }
+    Ok(cases)
+}
+
+/// Reads a list of assertions from a JSON, TOML or YAML file.
+///
+/// Like test case files, the list may be wrapped in a table, under `assertions`.
+pub fn read_assertions(path: &Path) -> Result<Vec<Assertion>> {
+    let value = match read_data_file(path, "assertion")? {
+        serde_json::Value::Object(mut table) if table.contains_key("assertions") => {
+            table.remove("assertions").unwrap_or_default()
+        }
+        value => value,
+    };
+    serde_json::from_value(value).map_err(|e| anyhow::anyhow!("Invalid assertion file `{}`: {}", path.display(), e))
+}
+
+/// Renders each case and checks its assertions.
+///
+/// Without a provider the assertions apply to the rendered prompt. With one,
+/// the rendered prompt is sent to it and the assertions apply to its output.
+/// Provider calls made here are not recorded in the run log.
+pub async fn run_test_cases(
+    template: &ComposedTemplate,
+    cases: &[TestCase],
+    provider: Option<&(dyn Provider + Send + Sync)>,
+) -> Result<Vec<TestResult>> {
+    let variables = template.variables()?;
+    let mut results = Vec::new();
+
+    for case in cases {
+        let context = tera::Context::from_value(serde_json::Value::Object(case.variables.clone()))?;
+        let missing = missing_variables(&variables, &context);
+        let output = match (missing.is_empty(), provider) {
+            (false, _) => Err(format!("Missing template variables: {}", missing.join(", "))),
+            (true, None) => template.render(&context).map_err(|e| e.to_string()),
+            (true, Some(provider)) => match template.render_messages(&context) {
+                Ok(messages) => {
+                    let request = CompletionRequest { messages, model: None };
+                    provider
+                        .complete(&request, None)
+                        .await
+                        .map(|completion| completion.content)
+                        .map_err(|e| e.to_string())
+                }
+                Err(e) => Err(e.to_string()),
+            },
+        };
+
+        results.push(match output {
+            Ok(output) => TestResult {
+                name: case.name.clone(),
+                failures: case.assertions.iter().filter_map(|a| a.check(&output).err()).collect(),
+                output: Some(output),
+            },
+            Err(error) => TestResult {
+                name: case.name.clone(),
+                output: None,
+                failures: vec![error],
+            },
+        });
+    }
+    Ok(results)
+}
@@ -0,0 +1,431 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::process::Command;
+
+use crate::storage::{JsonStorage, Prompt, Revision, Run, Storage};
+
+/// A storage implementation that keeps prompts as JSON files in a git
+/// repository and commits every change.
+///
+/// The files are laid out as for `JsonStorage`, and the storage directory can
+/// be a subdirectory of an existing repository. Only changes inside it are
+/// committed. Each change is committed once it is complete, so an edit goes
+/// in as one commit with its revision, and recorded runs stay out of git.
+pub struct GitStorage {
+    json: JsonStorage,
+    dir: PathBuf,
+    /// Whether git knows who to record as the author of commits.
+    has_identity: bool,
+}
+
+/// What a pull brought in.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize)]
+pub struct PullReport {
+    /// Whether the remote had commits that were not here yet.
+    pub updated: bool,
+    /// The hashes of remote prompts that conflicted with local ones and were
+    /// kept as separate prompts, or combined with them when both versions
+    /// have the same content.
+    pub kept_both: Vec<String>,
+}
+
+impl GitStorage {
+    /// Opens the storage, creating a git repository in its directory if it is
+    /// not inside one yet.
+    ///
+    /// If `storage_path` is `None`, the default directory of `JsonStorage` is used.
+    pub async fn new(storage_path: Option<PathBuf>) -> Result<Self> {
+        let json = JsonStorage::new(storage_path)?;
+        let dir = json.path().to_path_buf();
+        fs::create_dir_all(&dir).await?;
+        let mut storage = Self { json, dir, has_identity: true };
+        if !storage.command(&["rev-parse", "--git-dir"]).output().await?.status.success() {
+            storage.git(&["init", "--quiet"]).await?;
+        }
+        storage.has_identity = storage.command(&["config", "user.email"]).output().await?.status.success();
+        storage.ignore_runs().await?;
+        Ok(storage)
+    }
+
+    /// Keeps the recorded runs, which only matter on this machine, out of the repository.
+    async fn ignore_runs(&self) -> Result<()> {
+        let gitignore = self.dir.join(".gitignore");
+        let ignored = fs::read_to_string(&gitignore).await.unwrap_or_default();
+        let tracked = !self.git(&["ls-files", "--", "runs"]).await?.is_empty();
+        if ignored.lines().any(|line| line.trim() == "runs/") && !tracked {
+            return Ok(());
+        }
+        if !ignored.lines().any(|line| line.trim() == "runs/") {
+            let separator = if ignored.is_empty() || ignored.ends_with('\n') { "" } else { "\n" };
+            fs::write(&gitignore, format!("{}{}runs/\n", ignored, separator)).await?;
+        }
+        // Only these two changes are staged, so nothing else in the
+        // directory goes into the commit.
+        self.git(&["add", "--", ".gitignore"]).await?;
+        if tracked {
+            self.git(&["rm", "-r", "--cached", "--quiet", "--", "runs"]).await?;
+        }
+        self.git(&["commit", "--quiet", "-m", "Keep recorded runs out of git"]).await?;
+        Ok(())
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new("git");
+        command.arg("-C").arg(&self.dir);
+        if !self.has_identity {
+            command.args(["-c", "user.name=prompts-cli", "-c", "user.email=prompts-cli@localhost"]);
+        }
+        command.args(args);
+        command
+    }
+
+    /// Runs git and returns what it printed, failing with its error message.
+    async fn git(&self, args: &[&str]) -> Result<String> {
+        let output = self.command(args).output().await?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!("git {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Commits every change in the storage directory, if there is any.
+    async fn commit(&self, message: &str) -> Result<()> {
+        self.git(&["add", "--all", "--", "."]).await?;
+        if self.command(&["diff", "--cached", "--quiet", "--", "."]).status().await?.success() {
+            return Ok(());
+        }
+        self.git(&["commit", "--quiet", "-m", message, "--", "."]).await?;
+        Ok(())
+    }
+
+    async fn head(&self) -> Option<String> {
+        self.git(&["rev-parse", "--verify", "--quiet", "HEAD"]).await.ok()
+    }
+
+    /// Returns the branch to pull and push, the current one unless given.
+    async fn branch(&self, branch: Option<&str>) -> Result<String> {
+        match branch {
+            Some(branch) => Ok(branch.to_string()),
+            None => Ok(self.git(&["symbolic-ref", "--short", "HEAD"]).await?.trim().to_string()),
+        }
+    }
+
+    /// Merges the changes on `remote`, by default from the current branch.
+    ///
+    /// When the same prompt was changed on both sides, the local version
+    /// keeps its id, name and history, and the remote version is kept as a
+    /// separate prompt with its own id and the remote history. Remote prompts
+    /// whose name is taken here are renamed to `name-<hash>`. When only the
+    /// metadata of the same prompt changed on both sides, it is combined, as
+    /// the two versions share the file named by their hash.
+    pub async fn pull(&self, remote: &str, branch: Option<&str>) -> Result<PullReport> {
+        let branch = self.branch(branch).await?;
+        self.commit("Save pending changes").await?;
+        let before = self.head().await;
+        let local: HashSet<String> = self
+            .json
+            .load_prompts()
+            .await?
+            .into_iter()
+            .chain(self.json.load_trash().await?)
+            .map(|p| p.hash)
+            .collect();
+
+        let output = self
+            .command(&["pull", "--quiet", "--no-rebase", "--no-edit", "--allow-unrelated-histories", remote, &branch])
+            .output()
+            .await?;
+        let unmerged: Vec<String> = self
+            .git(&["diff", "--name-only", "--relative", "--diff-filter=U"])
+            .await?
+            .lines()
+            .map(str::to_string)
+            .collect();
+        if !output.status.success() && unmerged.is_empty() {
+            return Err(anyhow::anyhow!("git pull failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+
+        let mut remote_history = HashMap::new();
+        let mut kept_both = Vec::new();
+        for path in &unmerged {
+            if let Some((id, revisions)) = self.resolve(path, &mut kept_both).await? {
+                remote_history.insert(id, revisions);
+            }
+        }
+        for hash in self.separate(&local, &remote_history).await? {
+            if !kept_both.contains(&hash) {
+                kept_both.push(hash);
+            }
+        }
+
+        let merging = self.command(&["rev-parse", "--verify", "--quiet", "MERGE_HEAD"]).output().await?.status.success();
+        if merging {
+            self.git(&["add", "--all", "--", "."]).await?;
+            let message = format!(
+                "Merge {}/{}, keeping both versions of {} prompts",
+                remote,
+                branch,
+                kept_both.len()
+            );
+            self.git(&["commit", "--quiet", "--no-edit", "-m", &message]).await?;
+        } else {
+            self.commit(&format!("Keep both versions of {} prompts changed on {}/{}", kept_both.len(), remote, branch)).await?;
+        }
+        Ok(PullReport { updated: self.head().await != before, kept_both })
+    }
+
+    /// Resolves a file changed on both sides. A prompt file combines the
+    /// changes on both sides, adding its hash to `kept_both`, and anything
+    /// else keeps the local version. Returns the remote history of a revisions file, for the
+    /// remote prompts it belongs to.
+    async fn resolve(&self, path: &str, kept_both: &mut Vec<String>) -> Result<Option<(String, Vec<Revision>)>> {
+        let stage = |n: u8| async move { self.git(&["show", &format!(":{}:./{}", n, path)]).await.ok() };
+        let (base, ours, theirs) = (stage(1).await, stage(2).await, stage(3).await);
+        let file = Path::new(path);
+        let in_revisions = file.parent().is_some_and(|dir| dir == Path::new("revisions"));
+        let is_prompt = file.extension().is_some_and(|ext| ext == "json")
+            && file.parent().is_some_and(|dir| dir.as_os_str().is_empty() || dir == Path::new(".trash"));
+
+        let chosen = match (&ours, &theirs) {
+            (Some(ours), Some(theirs)) if is_prompt => {
+                match (serde_json::from_str::<Prompt>(ours), serde_json::from_str::<Prompt>(theirs)) {
+                    (Ok(ours), Ok(theirs)) => {
+                        let base = base.and_then(|base| serde_json::from_str(&base).ok());
+                        let merged = combine(base, ours, theirs);
+                        kept_both.push(merged.hash.clone());
+                        serde_json::to_string_pretty(&merged)?
+                    }
+                    _ => ours.clone(),
+                }
+            }
+            (Some(ours), _) => ours.clone(),
+            (None, Some(theirs)) => theirs.clone(),
+            (None, None) => return Ok(None),
+        };
+        fs::write(self.dir.join(path), chosen).await?;
+        self.git(&["add", "--", path]).await?;
+
+        match (in_revisions, theirs, file.file_stem()) {
+            (true, Some(theirs), Some(id)) => Ok(Some((id.to_string_lossy().into_owned(), serde_json::from_str(&theirs)?))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Splits off remote prompts that now share an id or a name with a local
+    /// one, and returns their hashes.
+    async fn separate(&self, local: &HashSet<String>, remote_history: &HashMap<String, Vec<Revision>>) -> Result<Vec<String>> {
+        let mut prompts = self.json.load_prompts().await?;
+        // Local prompts come first, so they keep what they have.
+        prompts.sort_by_key(|p| (!local.contains(&p.hash), p.hash.clone()));
+        let mut ids = HashSet::new();
+        let mut names: HashSet<String> = HashSet::new();
+        let mut separated = Vec::new();
+        for mut prompt in prompts {
+            let mut changed = false;
+            if !ids.insert(prompt.id.clone()) {
+                let history = match remote_history.get(&prompt.id) {
+                    Some(history) => history.clone(),
+                    None => self.json.load_revisions(&prompt.id).await?,
+                };
+                prompt.id = prompt.hash.clone();
+                ids.insert(prompt.id.clone());
+                if self.json.load_revisions(&prompt.id).await?.is_empty() {
+                    for revision in &history {
+                        self.json.save_revision(&prompt.id, revision).await?;
+                    }
+                }
+                changed = true;
+            }
+            if let Some(name) = prompt.name.clone().filter(|n| names.contains(n)) {
+                prompt.name = Some(format!("{}-{}", name, &prompt.hash[..8.min(prompt.hash.len())]));
+                changed = true;
+            }
+            let aliases = prompt.aliases.len();
+            prompt.aliases.retain(|alias| !names.contains(alias));
+            changed |= prompt.aliases.len() != aliases;
+            names.extend(prompt.names().map(str::to_string));
+
+            if changed {
+                self.json.delete_prompt(&prompt.hash).await?;
+                self.json.save_prompt(&mut prompt).await?;
+                separated.push(prompt.hash);
+            }
+        }
+        Ok(separated)
+    }
+
+    /// Lists the prompt files written in an older format and, unless `dry_run`
+    /// is set, rewrites and commits them; see `JsonStorage::upgrade_files`.
+    pub async fn upgrade_files(&self, dry_run: bool) -> Result<Vec<PathBuf>> {
+        let files = self.json.upgrade_files(dry_run).await?;
+        if !dry_run {
+            self.commit(&format!("Upgrade {} prompt files to format {}", files.len(), crate::storage::JSON_FORMAT_VERSION)).await?;
+        }
+        Ok(files)
+    }
+
+    /// Pushes the committed changes to `remote`, by default to the current branch.
+    pub async fn push(&self, remote: &str, branch: Option<&str>) -> Result<()> {
+        let branch = self.branch(branch).await?;
+        self.commit("Save pending changes").await?;
+        let output = self.command(&["push", "--quiet", remote, &format!("HEAD:{}", branch)]).output().await?;
+        if !output.status.success() {
+            let message = String::from_utf8_lossy(&output.stderr);
+            if message.contains("[rejected]") {
+                return Err(anyhow::anyhow!(
+                    "{}/{} has changes that are not here yet; run `prompts-cli pull` first",
+                    remote,
+                    branch
+                ));
+            }
+            return Err(anyhow::anyhow!("git push failed: {}", message.trim()));
+        }
+        Ok(())
+    }
+}
+
+/// Combines two versions of a prompt with the same content, changed on
+/// different machines since `base`, the version they share.
+///
+/// Tags, categories and aliases added or removed on either side are added or
+/// removed, and the uses on both sides are counted. Where the sides disagree
+/// on anything else, the version updated last wins, and the other name is
+/// kept as an alias.
+fn combine(base: Option<Prompt>, ours: Prompt, theirs: Prompt) -> Prompt {
+    let base = base.unwrap_or_default();
+    let (mut merged, other) = if theirs.updated_at > ours.updated_at { (theirs, ours) } else { (ours, theirs) };
+    let three_way = |base: &[String], a: &[String], b: &[String]| -> Vec<String> {
+        let mut values: Vec<String> = a.iter().filter(|v| b.contains(v) || !base.contains(v)).cloned().collect();
+        for value in b.iter().filter(|v| !a.contains(v) && !base.contains(v)) {
+            values.push(value.clone());
+        }
+        values
+    };
+    let list = |field: &Option<Vec<String>>| field.clone().unwrap_or_default();
+    let lists = |base: &Option<Vec<String>>, a: &Option<Vec<String>>, b: &Option<Vec<String>>| {
+        (a.is_some() || b.is_some()).then(|| three_way(&list(base), &list(a), &list(b)))
+    };
+    merged.tags = lists(&base.tags, &merged.tags, &other.tags);
+    merged.categories = lists(&base.categories, &merged.categories, &other.categories);
+    merged.aliases = three_way(&base.aliases, &merged.aliases, &other.aliases);
+    if merged.name == base.name && other.name != base.name {
+        merged.name = other.name.clone();
+    }
+    if let Some(name) = other.name.filter(|name| merged.name.as_ref() != Some(name) && base.name.as_ref() != Some(name)) {
+        merged.aliases.push(name);
+    }
+    if let Some(name) = &merged.name {
+        merged.aliases.retain(|alias| alias != name);
+    }
+    merged.created_at = match (merged.created_at, other.created_at) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    merged.last_used_at = merged.last_used_at.max(other.last_used_at);
+    merged.use_count = (merged.use_count + other.use_count).saturating_sub(base.use_count);
+    merged
+}
+
+/// Describes a prompt in a commit message, by name if it has one.
+fn describe(prompt: &Prompt) -> String {
+    let summary = prompt.content.lines().next().unwrap_or_default();
+    let summary: String = summary.chars().take(50).collect();
+    match &prompt.name {
+        Some(name) => format!("`{}`: {}", name, summary),
+        None => format!("{}: {}", &prompt.hash[..12.min(prompt.hash.len())], summary),
+    }
+}
+
+#[async_trait]
+impl Storage for GitStorage {
+    async fn save_prompt(&self, prompt: &mut Prompt) -> Result<()> {
+        self.json.save_prompt(prompt).await?;
+        self.commit(&format!("Save prompt {}", describe(prompt))).await
+    }
+
+    async fn load_prompts(&self) -> Result<Vec<Prompt>> {
+        self.json.load_prompts().await
+    }
+
+    async fn find_by_hash_prefix(&self, prefix: &str) -> Result<Vec<Prompt>> {
+        self.json.find_by_hash_prefix(prefix).await
+    }
+
+    async fn delete_prompt(&self, hash: &str) -> Result<()> {
+        let prompt = self.json.find_by_hash_prefix(hash).await?.into_iter().find(|p| p.hash == hash);
+        self.json.delete_prompt(hash).await?;
+        match prompt {
+            Some(prompt) => self.commit(&format!("Delete prompt {}", describe(&prompt))).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn replace_prompt(&self, old_hash: &str, prompt: &mut Prompt) -> Result<()> {
+        self.json.replace_prompt(old_hash, prompt).await?;
+        self.commit(&format!("Save prompt {}", describe(prompt))).await
+    }
+
+    async fn trash_prompt(&self, hash: &str, at: DateTime<Utc>) -> Result<()> {
+        self.json.trash_prompt(hash, at).await?;
+        match self.json.load_trash().await?.into_iter().find(|p| p.hash == hash) {
+            Some(prompt) => self.commit(&format!("Move prompt {} to the trash", describe(&prompt))).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn load_trash(&self) -> Result<Vec<Prompt>> {
+        self.json.load_trash().await
+    }
+
+    async fn save_trashed(&self, prompt: &Prompt) -> Result<()> {
+        self.json.save_trashed(prompt).await?;
+        self.commit(&format!("Save prompt {} to the trash", describe(prompt))).await
+    }
+
+    async fn restore_prompt(&self, hash: &str) -> Result<()> {
+        self.json.restore_prompt(hash).await?;
+        match self.json.find_by_hash_prefix(hash).await?.into_iter().find(|p| p.hash == hash) {
+            Some(prompt) => self.commit(&format!("Restore prompt {} from the trash", describe(&prompt))).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn purge_prompt(&self, hash: &str) -> Result<()> {
+        let prompt = self.json.load_trash().await?.into_iter().find(|p| p.hash == hash);
+        self.json.purge_prompt(hash).await?;
+        match prompt {
+            Some(prompt) => self.commit(&format!("Purge prompt {} from the trash", describe(&prompt))).await,
+            None => Ok(()),
+        }
+    }
+
+    // Revisions are saved before the prompt they belong to, which commits them.
+    async fn save_revision(&self, id: &str, revision: &Revision) -> Result<()> {
+        self.json.save_revision(id, revision).await
+    }
+
+    async fn load_revisions(&self, id: &str) -> Result<Vec<Revision>> {
+        self.json.load_revisions(id).await
+    }
+
+    async fn save_run(&self, run: &Run) -> Result<()> {
+        self.json.save_run(run).await
+    }
+
+    async fn load_runs(&self, prompt_id: Option<&str>) -> Result<Vec<Run>> {
+        self.json.load_runs(prompt_id).await
+    }
+
+    async fn record_use(&self, hash: &str, at: DateTime<Utc>) -> Result<()> {
+        self.json.record_use(hash, at).await?;
+        match self.json.find_by_hash_prefix(hash).await?.into_iter().find(|p| p.hash == hash) {
+            Some(prompt) => self.commit(&format!("Record a use of prompt {}", describe(&prompt))).await,
+            None => Ok(()),
+        }
+    }
+}
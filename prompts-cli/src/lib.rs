@@ -1,7 +1,21 @@
+pub mod batch;
+pub mod chat;
+pub mod compare;
 pub mod core;
 pub mod storage;
 pub mod error;
+pub mod editor;
+pub mod eval;
+pub mod git;
+pub mod migrate;
+pub mod picker;
+pub mod provider;
+pub mod query;
+pub mod template;
+pub mod tokens;
 
-pub use crate::core::{Prompts, search_prompts};
-pub use crate::storage::{Storage, JsonStorage, LibSQLStorage, Prompt};
+pub use crate::core::{Prompts, PromptTime, SearchResult, rank_prompts, search_prompts};
+pub use crate::storage::{Storage, JsonStorage, LibSQLStorage, Prompt, Revision, Run};
+pub use crate::git::GitStorage;
 pub use crate::error::AppError;
+pub use crate::query::{Query, QueryError};
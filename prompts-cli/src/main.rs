@@ -1,20 +1,20 @@
 use clap::Parser;
-use prompts_cli::{AppError, Prompt, Prompts, JsonStorage, LibSQLStorage, Storage};
-use std::io::{self, Read};
-use std::path::PathBuf;
+use prompts_cli::{batch, chat, compare, core::{self as prompts_core, PromptTime}, editor::{self, Document}, eval, git::GitStorage, migrate::{self, StorageSpec}, picker, provider, storage, template, tokens, AppError, Prompt, Prompts, JsonStorage, LibSQLStorage, Storage};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 use config::{Config, File, FileFormat};
+use crossterm::style::Stylize;
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Default, serde::Deserialize)]
 struct AppConfig {
+    /// The author recorded on new prompts; defaults to the git user name.
+    #[serde(default)]
+    author: Option<String>,
     storage: StorageConfig,
-}
-
-impl Default for AppConfig {
-    fn default() -> Self {
-        Self {
-            storage: StorageConfig::default(),
-        }
-    }
+    #[serde(default)]
+    providers: provider::ProvidersConfig,
+    #[serde(default)]
+    tokens: tokens::TokensConfig,
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -22,6 +22,21 @@ struct StorageConfig {
     #[serde(default = "default_storage_type")]
     r#type: String,
     path: Option<PathBuf>,
+    /// The sqld server a `libsql-replica` storage syncs with.
+    #[serde(default)]
+    sync_url: Option<String>,
+    /// The environment variable holding the server's auth token.
+    #[serde(default = "default_auth_token_env")]
+    auth_token_env: String,
+    /// Sync the replica after every change, not only when it is opened.
+    #[serde(default)]
+    sync_on_write: bool,
+    /// The git remote a `git` storage pulls from and pushes to.
+    #[serde(default = "default_remote")]
+    remote: String,
+    /// The branch to pull and push, the current one if not set.
+    #[serde(default)]
+    branch: Option<String>,
 }
 
 impl Default for StorageConfig {
@@ -29,14 +44,40 @@ impl Default for StorageConfig {
         Self {
             r#type: default_storage_type(),
             path: None,
+            sync_url: None,
+            auth_token_env: default_auth_token_env(),
+            sync_on_write: false,
+            remote: default_remote(),
+            branch: None,
         }
     }
 }
 
+impl StorageConfig {
+    fn replica_config(&self) -> Result<storage::ReplicaConfig, AppError> {
+        let sync_url = self.sync_url.clone().ok_or_else(|| {
+            AppError::Storage("The libsql-replica storage needs storage.sync_url to be set".to_string())
+        })?;
+        Ok(storage::ReplicaConfig {
+            sync_url,
+            auth_token: std::env::var(&self.auth_token_env).unwrap_or_default(),
+            sync_on_write: self.sync_on_write,
+        })
+    }
+}
+
 fn default_storage_type() -> String {
     "json".to_string()
 }
 
+fn default_auth_token_env() -> String {
+    "LIBSQL_AUTH_TOKEN".to_string()
+}
+
+fn default_remote() -> String {
+    "origin".to_string()
+}
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Cli {
@@ -50,37 +91,332 @@ struct Cli {
     command: Commands,
 }
 
+/// Options controlling how a single prompt is chosen from several matches.
+#[derive(clap::Args, Debug)]
+struct SelectArgs {
+    /// Use the best match instead of asking when several prompts match
+    #[arg(long)]
+    first: bool,
+    /// Select the prompt with this hash or id prefix instead of searching, like an `@prefix` query
+    #[arg(long)]
+    hash: Option<String>,
+}
+
+/// Options giving the variables a prompt is rendered with.
+#[derive(clap::Args, Debug)]
+struct VariableArgs {
+    /// Variables to use for templating (key=value pairs)
+    #[arg(short, long, value_parser = parse_key_val, action = clap::ArgAction::Append)]
+    variables: Vec<(String, String)>,
+    /// JSON, TOML or YAML file of variables; nested values are available to loops
+    #[arg(long, action = clap::ArgAction::Append)]
+    vars_file: Vec<PathBuf>,
+    /// Set a variable to the contents of a file (key=path)
+    #[arg(long, value_parser = parse_key_path, action = clap::ArgAction::Append)]
+    var_from_file: Vec<(String, PathBuf)>,
+    /// Read variables from environment variables with this prefix, e.g. PROMPT_NAME sets `name`
+    #[arg(long)]
+    env_prefix: Option<String>,
+}
+
+impl VariableArgs {
+    fn context(&self) -> Result<tera::Context, AppError> {
+        let sources = template::VariableSources {
+            files: self.vars_file.clone(),
+            env_prefix: self.env_prefix.clone(),
+            file_contents: self.var_from_file.clone(),
+            values: self.variables.clone(),
+        };
+        sources.context().map_err(|e| AppError::Anyhow(e.to_string()))
+    }
+}
+
+/// Options for counting the tokens in a prompt.
+#[derive(clap::Args, Debug)]
+struct TokenArgs {
+    /// Count the tokens in the prompt, warning if it exceeds the configured context limit
+    #[arg(long)]
+    count_tokens: bool,
+    /// The encoding to count tokens with, `cl100k_base` or `o200k_base`
+    #[arg(long, requires = "count_tokens")]
+    encoding: Option<tokens::Encoding>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum RunsCommand {
+    /// Lists recorded runs, newest first
+    List {
+        /// A query to select the prompt whose runs to list; all runs are listed without one
+        query: Option<String>,
+        /// Tags for the prompt (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        tags: Option<Vec<String>>,
+        /// The maximum number of runs to list
+        #[arg(short, long)]
+        limit: Option<usize>,
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Shows the input, variables and output of a run
+    Show {
+        /// The id of the run, or a unique prefix of it
+        id: String,
+    },
+    /// Compares the variables, input and output of two runs
+    Diff {
+        /// The id of the earlier run, or a unique prefix of it
+        first: String,
+        /// The id of the later run, or a unique prefix of it
+        second: String,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum DbCommand {
+    /// Brings the configured storage up to the current schema or file format
+    Migrate {
+        /// Only list the changes that would be made
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum TrashCommand {
+    /// Lists deleted prompts, most recently deleted first
+    List,
+    /// Deletes prompts in the trash for good
+    Purge {
+        /// Only purge prompts deleted longer ago than this, e.g. `30d`, `12h`, `2w` or a date
+        #[arg(long, value_parser = prompts_core::parse_age)]
+        older_than: Option<chrono::DateTime<chrono::Utc>>,
+        /// Purge without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum TestCommand {
+    /// Runs a prompt's test cases, failing if any of them fail
+    Run {
+        /// The query to search for a prompt
+        query: Option<String>,
+        /// Tags for the prompt (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        tags: Option<Vec<String>>,
+        /// Check the output of this provider instead of the rendered prompt; `mock` needs no setup
+        #[arg(long)]
+        provider: Option<String>,
+        /// Only run the cases with these names (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        case: Option<Vec<String>>,
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Replaces a prompt's test cases with those in a JSON, TOML or YAML file
+    Set {
+        /// The query to search for a prompt
+        query: Option<String>,
+        /// The file holding the test cases
+        #[arg(short, long)]
+        file: PathBuf,
+        /// Tags for the prompt (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        tags: Option<Vec<String>>,
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Prints a prompt's test cases
+    Show {
+        /// The query to search for a prompt
+        query: Option<String>,
+        /// Tags for the prompt (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        tags: Option<Vec<String>>,
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+}
+
 #[derive(Parser, Debug)]
 enum Commands {
-    /// Lists all the prompts
+    /// Lists all the prompts, or those matching a query
     List {
+        /// A query to filter the prompts, e.g. `tag:rust OR tag:go -tag:draft`
+        query: Option<String>,
         /// Tags for the prompt (comma-separated)
         #[arg(short, long, value_delimiter = ',')]
         tags: Option<Vec<String>>,
+        /// Sort by when prompts were `created`, `updated` or last `used`, newest first
+        #[arg(long)]
+        sort: Option<PromptTime>,
+        /// Only list prompts created, updated or used (as chosen by --sort, updated by default) on or after this date
+        #[arg(long, value_parser = prompts_core::parse_date)]
+        since: Option<chrono::DateTime<chrono::Utc>>,
+        /// Only list prompts created, updated or used before this date; prompts never used count as before any date
+        #[arg(long, value_parser = prompts_core::parse_date)]
+        before: Option<chrono::DateTime<chrono::Utc>>,
+        #[command(flatten)]
+        tokens: TokenArgs,
     },
     /// Shows a specific prompt
     Show {
-        /// The fuzzy query to search for a prompt
+        /// The query to search for a prompt
+        query: Option<String>,
+        /// Tags for the prompt (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        tags: Option<Vec<String>>,
+        /// Show the content of an earlier revision instead of the current one
+        #[arg(short, long)]
+        revision: Option<usize>,
+        /// The maximum number of matches to print when several prompts match
+        #[arg(short, long)]
+        limit: Option<usize>,
+        #[command(flatten)]
+        tokens: TokenArgs,
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Searches for prompts, best match first
+    Search {
+        /// The query to search for
+        query: String,
+        /// Tags for the prompt (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        tags: Option<Vec<String>>,
+        /// The maximum number of results to print
+        #[arg(short, long)]
+        limit: Option<usize>,
+    },
+    /// Shows the revision history of a prompt
+    History {
+        /// The query to search for a prompt
         query: Option<String>,
         /// Tags for the prompt (comma-separated)
         #[arg(short, long, value_delimiter = ',')]
         tags: Option<Vec<String>>,
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Browses the responses recorded by `generate --run`
+    Runs {
+        #[command(subcommand)]
+        command: RunsCommand,
+    },
+    /// Restores a prompt to the content of an earlier revision
+    Revert {
+        /// The query to search for a prompt
+        query: Option<String>,
+        /// The revision number to restore
+        #[arg(short, long)]
+        revision: usize,
+        /// Tags for the prompt (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        tags: Option<Vec<String>>,
+        #[command(flatten)]
+        select: SelectArgs,
     },
 
     /// Generates text based on a prompt
     Generate {
-        /// The fuzzy query to search for a prompt
+        /// The query to search for a prompt
+        query: Option<String>,
+        /// Tags for the prompt (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        tags: Option<Vec<String>>,
+        #[command(flatten)]
+        variables: VariableArgs,
+        /// Render the prompt once per row of a CSV or JSONL file, writing JSONL results
+        #[arg(long)]
+        batch: Option<PathBuf>,
+        /// Write batch results to this file instead of stdout
+        #[arg(long, requires = "batch")]
+        batch_output: Option<PathBuf>,
+        /// Number of threads to render batch rows on (defaults to the number of CPUs)
+        #[arg(long, requires = "batch")]
+        jobs: Option<usize>,
+        /// Send the rendered prompt to the configured provider and stream the reply
+        #[arg(long, conflicts_with = "batch")]
+        run: bool,
+        /// The provider to run the prompt with, `openai` or `ollama`
+        #[arg(long, requires = "run")]
+        provider: Option<String>,
+        /// The model to use instead of the configured one
+        #[arg(long, requires = "run")]
+        model: Option<String>,
+        /// Print the stored prompts pulled in through include, import or extends to stderr
+        #[arg(long)]
+        explain: bool,
+        /// Print the rendered prompt as `text`, or as OpenAI-style `messages` JSON
+        #[arg(long, value_parser = ["text", "messages"], conflicts_with_all = ["batch", "run"])]
+        format: Option<String>,
+        #[command(flatten)]
+        tokens: TokenArgs,
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Runs two or more prompts on the same inputs and compares their outputs
+    Compare {
+        /// Queries selecting the prompts to compare, one per prompt
+        #[arg(required = true, num_args = 2..)]
+        queries: Vec<String>,
+        /// Tags for the prompts (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        tags: Option<Vec<String>>,
+        #[command(flatten)]
+        variables: VariableArgs,
+        /// Run each prompt once per row of a CSV or JSONL file
+        #[arg(long)]
+        batch: Option<PathBuf>,
+        /// The provider to run the prompts with; defaults to the configured one
+        #[arg(long)]
+        provider: Option<String>,
+        /// The model to use instead of the configured one
+        #[arg(long)]
+        model: Option<String>,
+        /// A JSON, TOML or YAML file of assertions to score each output by
+        #[arg(long)]
+        assertions: Option<PathBuf>,
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Manages and runs the test cases attached to a prompt
+    Test {
+        #[command(subcommand)]
+        command: TestCommand,
+    },
+    /// Counts the tokens in a rendered prompt
+    Tokens {
+        /// The query to search for a prompt
+        query: Option<String>,
+        /// Tags for the prompt (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        tags: Option<Vec<String>>,
+        #[command(flatten)]
+        variables: VariableArgs,
+        /// The encoding to count tokens with, `cl100k_base` or `o200k_base`
+        #[arg(long)]
+        encoding: Option<tokens::Encoding>,
+        /// The model to budget for instead of the configured one, e.g. `gpt-4o`
+        #[arg(long)]
+        model: Option<String>,
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Lists the template variables a prompt uses
+    Vars {
+        /// The query to search for a prompt
         query: Option<String>,
         /// Tags for the prompt (comma-separated)
         #[arg(short, long, value_delimiter = ',')]
         tags: Option<Vec<String>>,
-        /// Variables to use for templating (key=value pairs)
-        #[arg(short, long, value_parser = parse_key_val, action = clap::ArgAction::Append)]
-        variables: Vec<(String, String)>,
+        #[command(flatten)]
+        select: SelectArgs,
     },
     /// Adds a new prompt
     Add {
-        /// The text content of the prompt
+        /// The text content of the prompt (opens $EDITOR if omitted on a terminal)
         text: Option<String>,
         /// Tags for the prompt (comma-separated)
         #[arg(short, long, value_delimiter = ',')]
@@ -88,10 +424,22 @@ enum Commands {
         /// Categories for the prompt (comma-separated)
         #[arg(short, long, value_delimiter = ',')]
         categories: Option<Vec<String>>,
+        /// A unique name to refer to the prompt by instead of a query, e.g. `code-review`
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Further unique names for the prompt (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        aliases: Option<Vec<String>>,
+        /// Add a chat prompt, reading the text as a transcript of `[system]`, `[user]` and `[assistant]` messages
+        #[arg(long)]
+        chat: bool,
+        /// Add a chat prompt from a JSON, TOML or YAML file of messages
+        #[arg(long, conflicts_with_all = ["text", "chat"])]
+        messages: Option<PathBuf>,
     },
-    /// Edits an existing prompt
+    /// Edits an existing prompt, in $EDITOR unless changes are given as flags
     Edit {
-        /// The fuzzy query to search for a prompt
+        /// The query to search for a prompt
         query: Option<String>,
         /// Tags for filtering (comma-separated)
         #[arg(long, value_delimiter = ',')]
@@ -99,6 +447,21 @@ enum Commands {
         /// The new text content of the prompt
         #[arg(short, long)]
         text: Option<String>,
+        /// Replace the messages of a chat prompt with those in a JSON, TOML or YAML file
+        #[arg(long, conflicts_with = "text")]
+        messages: Option<PathBuf>,
+        /// Give the prompt a new unique name
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Remove the name of the prompt
+        #[arg(long, conflicts_with = "name")]
+        clear_name: bool,
+        /// Aliases to add to the prompt (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        add_aliases: Option<Vec<String>>,
+        /// Aliases to remove from the prompt (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        remove_aliases: Option<Vec<String>>,
         /// Tags to add to the prompt (comma-separated)
         #[arg(long, value_delimiter = ',')]
         add_tags: Option<Vec<String>>,
@@ -111,20 +474,60 @@ enum Commands {
         /// Categories to remove from the prompt (comma-separated)
         #[arg(long, value_delimiter = ',')]
         remove_categories: Option<Vec<String>>,
+        #[command(flatten)]
+        select: SelectArgs,
     },
-    /// Deletes a prompt
+    /// Moves a prompt to the trash
     Delete {
-        /// The fuzzy query to search for a prompt
+        /// The query to search for a prompt
         query: Option<String>,
         /// Tags for the prompt (comma-separated)
         #[arg(short, long, value_delimiter = ',')]
         tags: Option<Vec<String>>,
+        /// Delete without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Lists or purges deleted prompts
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommand,
+    },
+    /// Brings a deleted prompt back from the trash
+    Restore {
+        /// The hash of the deleted prompt, or a unique prefix of it
+        hash: String,
     },
     /// Imports prompts from a directory
     Import {
         /// The directory to import prompts from
         path: PathBuf,
     },
+    /// Manages the configured storage itself
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+    /// Pulls the latest changes to a shared prompt library from its server
+    Sync,
+    /// Merges the prompts changed on the git remote of a `git` storage
+    Pull,
+    /// Pushes the prompts changed here to the git remote of a `git` storage
+    Push,
+    /// Copies all prompts, their history and runs from one storage backend to another
+    Migrate {
+        /// The storage to copy from, as `json:PATH` or `libsql:PATH`
+        #[arg(long)]
+        from: StorageSpec,
+        /// The storage to copy to, as `json:PATH` or `libsql:PATH`
+        #[arg(long)]
+        to: StorageSpec,
+        /// Point the config file at the target storage once the copy is verified
+        #[arg(long)]
+        switch_config: bool,
+    },
     /// Exports prompts to a directory
     Export {
         /// The directory to export prompts to
@@ -144,87 +547,922 @@ fn get_input(input: Option<String>, prompt_message: &str) -> anyhow::Result<Stri
     }
 }
 
+/// Finds the prompts matching a command's query, asking for one when none is given.
+///
+/// With `--hash` the prompt with that hash or id prefix is selected instead.
+async fn find_prompts(
+    prompts_api: &Prompts,
+    query: &Option<String>,
+    tags: &Option<Vec<String>>,
+    select: &SelectArgs,
+    prompt_message: &str,
+) -> Result<Vec<Prompt>, AppError> {
+    let query = match (&select.hash, query) {
+        (Some(_), Some(_)) => return Err(AppError::Anyhow("Give either a query or --hash, not both".to_string())),
+        (Some(hash), None) => return Ok(vec![prompts_api.find_by_hash(hash).await?]),
+        (None, query) => get_input(query.clone(), prompt_message)?,
+    };
+    Ok(prompts_api.show_prompt(&query, tags.clone()).await?)
+}
+
+/// Narrows search results down to the single prompt a command should act on.
+///
+/// With `--first` the best match is used. When several prompts match and stdout is a terminal, the user picks one
+/// interactively. Otherwise the results are printed as JSON so that scripts
+/// can disambiguate, and `None` is returned.
+fn select_prompt(mut search_results: Vec<Prompt>, select: &SelectArgs) -> anyhow::Result<Option<Prompt>> {
+    if search_results.len() == 1 || (select.first && !search_results.is_empty()) {
+        search_results.truncate(1);
+        return Ok(search_results.pop());
+    }
+    if search_results.len() > 1 && io::stdout().is_terminal() {
+        return picker::pick(search_results);
+    }
+    let result_json = serde_json::to_string_pretty(&search_results)?;
+    println!("{}", result_json);
+    Ok(None)
+}
+
+/// Opens `document` in the user's editor and parses the result.
+///
+/// Returns `None`, after telling the user, when the file was left unchanged
+/// or its body was emptied.
+fn edit_document(document: &Document) -> anyhow::Result<Option<Document>> {
+    let original = document.render();
+    let edited = editor::edit_text(&original)?;
+    if edited == original {
+        println!("No changes made, aborting.");
+        return Ok(None);
+    }
+    let edited = Document::parse(&edited)?;
+    if edited.content.trim().is_empty() {
+        println!("Prompt is empty, aborting.");
+        return Ok(None);
+    }
+    Ok(Some(edited))
+}
+
+/// Emphasises the characters at `indices` for display on a terminal.
+fn highlight(text: &str, indices: &[usize]) -> String {
+    text.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if indices.contains(&i) {
+                c.to_string().bold().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+fn difference(left: &[String], right: &[String]) -> Vec<String> {
+    left.iter().filter(|item| !right.contains(item)).cloned().collect()
+}
+
+/// Prints the tree of prompts pulled into `root` to stderr.
+fn explain_inclusions(root: &Prompt, inclusions: &[template::Inclusion], prompts: &[Prompt]) {
+    let first_line = |content: &str| content.lines().next().unwrap_or_default().to_string();
+    eprintln!("{} - {}", &root.hash[..12], first_line(&root.content));
+    for inclusion in inclusions {
+        let content = prompts
+            .iter()
+            .find(|p| p.hash == inclusion.hash)
+            .map(|p| first_line(&p.content))
+            .unwrap_or_default();
+        eprintln!(
+            "{}{} \"{}\" -> {} - {}",
+            "  ".repeat(inclusion.depth),
+            inclusion.kind,
+            inclusion.name,
+            &inclusion.hash[..12],
+            content
+        );
+    }
+}
+
+/// Prints the size of a prompt to stderr, warning when it does not fit the context window.
+fn report_tokens(count: &tokens::TokenCount) {
+    eprintln!("{}", count);
+    if count.exceeds_limit() {
+        report_limit(count);
+    }
+}
+
+/// Warns on stderr that a prompt does not fit the context window.
+fn report_limit(count: &tokens::TokenCount) {
+    let limit = count.context_limit.unwrap_or_default();
+    match &count.model {
+        Some(model) => eprintln!("Warning: {} tokens exceeds the {}-token context of {}", count.tokens, limit, model),
+        None => eprintln!("Warning: {} tokens exceeds the {}-token context limit", count.tokens, limit),
+    }
+}
+
+/// Records that a prompt was used, warning on stderr instead of failing, as
+/// when an offline replica cannot be written to.
+async fn record_use(prompts_api: &Prompts, hash: &str) {
+    if let Err(e) = prompts_api.record_use(hash).await {
+        eprintln!("Warning: could not record the use of prompt {} ({})", &hash[..12.min(hash.len())], e);
+    }
+}
+
+/// Asks for the value of a template variable on the terminal.
+fn ask_for_variable(name: &str) -> anyhow::Result<String> {
+    eprint!("{}: ", name);
+    io::stderr().flush()?;
+    let mut value = String::new();
+    io::stdin().read_line(&mut value)?;
+    Ok(value.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Asks a yes or no question on the terminal, defaulting to no.
+///
+/// Without a terminal to ask on this fails, so that scripts have to pass
+/// `--yes` to delete anything.
+fn confirm(question: &str) -> anyhow::Result<bool> {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Err(anyhow::anyhow!("No terminal to confirm on; pass --yes to delete without confirmation"));
+    }
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Returns the user name from the git configuration, if git is installed and has one.
+fn git_user_name() -> Option<String> {
+    let output = std::process::Command::new("git").args(["config", "user.name"]).output().ok()?;
+    let name = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (output.status.success() && !name.is_empty()).then_some(name)
+}
+
+/// Creates a prompt from text, parsed as a transcript of messages for a chat prompt.
+fn new_prompt(
+    content: &str,
+    chat: bool,
+    tags: Option<Vec<String>>,
+    categories: Option<Vec<String>>,
+) -> Result<Prompt, AppError> {
+    if chat {
+        let messages = chat::parse_transcript(content).map_err(|e| AppError::Anyhow(e.to_string()))?;
+        Ok(Prompt::chat(messages, tags, categories))
+    } else {
+        Ok(Prompt::new(content, tags, categories))
+    }
+}
+
 fn parse_key_val(s: &str) -> Result<(String, String), String> {
     let pos = s.find('=').ok_or_else(|| format!("invalid KEY=VALUE: no '=' found in `{}`", s))?;
     Ok((s[..pos].to_string(), s[pos + 1..].to_string()))
 }
 
-async fn run_cli(cli: Cli) -> Result<(), AppError> {
-    let app_config: AppConfig = if let Some(config_path) = &cli.config {
-        Config::builder()
-            .add_source(File::new(config_path.to_str().unwrap(), FileFormat::Toml))
-            .build()?.try_deserialize()?
+fn parse_key_path(s: &str) -> Result<(String, PathBuf), String> {
+    let (key, path) = parse_key_val(s)?;
+    Ok((key, PathBuf::from(path)))
+}
+
+/// Returns the config file to read, and whether it has to exist.
+///
+/// A file given with `--config` or `PROMPTS_CLI_CONFIG_PATH` must exist; the
+/// default one in the user's config directory is optional.
+fn config_path(cli: &Cli) -> anyhow::Result<(PathBuf, bool)> {
+    if let Some(path) = &cli.config {
+        return Ok((path.clone(), true));
+    }
+    if let Ok(path) = std::env::var("PROMPTS_CLI_CONFIG_PATH") {
+        return Ok((PathBuf::from(path), true));
+    }
+    let path = match std::env::var("PROMPTS_CLI_CONFIG_DIR_FOR_TESTING") {
+        Ok(path) => PathBuf::from(path).join("prompts-cli/config.toml"),
+        Err(_) => dirs::config_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?
+            .join("prompts-cli/config.toml"),
+    };
+    Ok((path, false))
+}
+
+/// Copies the prompts in `from` to `to`, pointing the configuration file at
+/// `to` afterwards if `switch_config` is set.
+async fn copy_storage(
+    from: &StorageSpec,
+    to: &StorageSpec,
+    switch_config: bool,
+    config_file: &Path,
+    output_json: bool,
+) -> Result<(), AppError> {
+    if from == to {
+        return Err(AppError::Anyhow("The source and target storage are the same".to_string()));
+    }
+    let source = from.open().await?;
+    let target = to.open().await?;
+    let report = migrate::migrate(source.as_ref(), target.as_ref()).await?;
+    if switch_config {
+        switch_storage(config_file, to)?;
+    }
+    if output_json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
     } else {
-        let config_builder = match std::env::var("PROMPTS_CLI_CONFIG_PATH") {
-            Ok(path) => Config::builder().add_source(File::new(&path, FileFormat::Toml).required(true)),
-            Err(_) => {
-                let config_path = match std::env::var("PROMPTS_CLI_CONFIG_DIR_FOR_TESTING") {
-                    Ok(path) => PathBuf::from(path).join("prompts-cli/config.toml"),
-                    Err(_) => dirs::config_dir()
-                        .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?
-                        .join("prompts-cli/config.toml"),
-                };
-                Config::builder()
-                    .add_source(File::new(config_path.to_str().unwrap(), FileFormat::Toml).required(false))
+        println!(
+            "Copied {} prompts ({} already present), {} from the trash, {} revisions and {} runs to {}.",
+            report.prompts_copied,
+            report.prompts_skipped,
+            report.trash_copied,
+            report.revisions_copied,
+            report.runs_copied,
+            to
+        );
+        println!("Verified {} prompts in the target.", report.target_prompts);
+        if switch_config {
+            println!("Switched {} to the new storage.", config_file.display());
+        }
+    }
+    Ok(())
+}
+
+/// Upgrades the configured storage to the current schema or file format, or
+/// with `dry_run` only lists what that would do.
+async fn migrate_storage(config: &StorageConfig, dry_run: bool) -> Result<(), AppError> {
+    let storage_path = config.path.clone();
+    match config.r#type.as_str() {
+        "json" => {
+            let storage = JsonStorage::new(storage_path)?;
+            let files = storage.upgrade_files(dry_run).await?;
+            let verb = if dry_run { "Would upgrade" } else { "Upgraded" };
+            println!("{} {} prompt files to format {}.", verb, files.len(), storage::JSON_FORMAT_VERSION);
+            for file in files {
+                println!("  {}", file.display());
             }
-        };
-        config_builder.build()?.try_deserialize().unwrap_or_default()
+        }
+        "git" => {
+            let storage = GitStorage::new(storage_path).await?;
+            let files = storage.upgrade_files(dry_run).await?;
+            let verb = if dry_run { "Would upgrade" } else { "Upgraded" };
+            println!("{} {} prompt files to format {}.", verb, files.len(), storage::JSON_FORMAT_VERSION);
+            for file in files {
+                println!("  {}", file.display());
+            }
+        }
+        "libsql" => {
+            let pending = LibSQLStorage::pending_migrations(storage_path.clone()).await?;
+            if !dry_run {
+                LibSQLStorage::new(storage_path).await?;
+            }
+            let verb = if dry_run { "Would apply" } else { "Applied" };
+            println!("{} {} migrations to reach schema version {}.", verb, pending.len(), storage::SCHEMA_VERSION);
+            for migration in pending {
+                let backup = if migration.destructive { " (backs up the database first)" } else { "" };
+                println!("  {:>3} {}{}", migration.version, migration.description, backup);
+            }
+        }
+        "libsql-replica" => {
+            // The replica only knows the schema as of its last sync, and the
+            // server keeps its own backups.
+            let replica_path = LibSQLStorage::replica_path(storage_path.clone())?;
+            let pending = LibSQLStorage::pending_migrations(Some(replica_path)).await?;
+            if !dry_run {
+                LibSQLStorage::new_replica(storage_path, config.replica_config()?).await?;
+            }
+            let verb = if dry_run { "Would apply" } else { "Applied" };
+            println!("{} {} migrations to reach schema version {}.", verb, pending.len(), storage::SCHEMA_VERSION);
+            for migration in pending {
+                println!("  {:>3} {}", migration.version, migration.description);
+            }
+        }
+        _ => return Err(AppError::Storage("Invalid storage type".to_string())),
+    }
+    Ok(())
+}
+
+/// Sets the storage type and path in a config file, keeping the rest of it.
+fn switch_storage(config_path: &Path, storage: &StorageSpec) -> anyhow::Result<()> {
+    let text = if config_path.exists() { std::fs::read_to_string(config_path)? } else { String::new() };
+    let mut document: toml_edit::DocumentMut = text.parse()?;
+    let path = std::fs::canonicalize(&storage.path)?;
+    document["storage"]["type"] = toml_edit::value(storage.kind.as_str());
+    document["storage"]["path"] = toml_edit::value(path.to_string_lossy().as_ref());
+    if let Some(parent) = config_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(config_path, document.to_string())?;
+    Ok(())
+}
+
+async fn run_cli(cli: Cli) -> Result<(), AppError> {
+    let (config_file, required) = config_path(&cli)?;
+    let config = Config::builder()
+        .add_source(File::new(config_file.to_str().unwrap(), FileFormat::Toml).required(required))
+        .build()?;
+    let app_config: AppConfig = if cli.config.is_some() {
+        config.try_deserialize()?
+    } else {
+        config.try_deserialize().unwrap_or_default()
     };
 
-    let storage_path = app_config.storage.path;
+    let storage_path = app_config.storage.path.clone();
+
+    // Opening a LibSQL store applies its migrations, so they are listed first.
+    if let Commands::Db { command: DbCommand::Migrate { dry_run } } = &cli.command {
+        return migrate_storage(&app_config.storage, *dry_run).await;
+    }
+
+    // Migrating opens its own source and target, which need not include the configured storage.
+    if let Commands::Migrate { from, to, switch_config } = &cli.command {
+        return copy_storage(from, to, *switch_config, &config_file, cli.output.as_deref() == Some("json")).await;
+    }
+
+    if let Commands::Pull | Commands::Push = &cli.command {
+        if app_config.storage.r#type != "git" {
+            return Err(AppError::Storage("Pull and push need storage.type to be `git`".to_string()));
+        }
+        let storage = GitStorage::new(storage_path).await?;
+        let (remote, branch) = (&app_config.storage.remote, app_config.storage.branch.as_deref());
+        if let Commands::Push = &cli.command {
+            storage.push(remote, branch).await?;
+            println!("Pushed to {}.", remote);
+            return Ok(());
+        }
+        let report = storage.pull(remote, branch).await?;
+        if cli.output.as_deref() == Some("json") {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else if !report.updated {
+            println!("Already up to date.");
+        } else {
+            println!("Pulled from {}.", remote);
+            for hash in &report.kept_both {
+                println!("Kept the remote version of a conflicting prompt as {}.", &hash[..12.min(hash.len())]);
+            }
+        }
+        return Ok(());
+    }
 
     let storage: Box<dyn Storage + Send + Sync> = match app_config.storage.r#type.as_str() {
         "json" => Box::new(JsonStorage::new(storage_path)?),
+        "git" => Box::new(GitStorage::new(storage_path).await?),
         "libsql" => Box::new(LibSQLStorage::new(storage_path).await?),
+        "libsql-replica" => {
+            let replica = LibSQLStorage::new_replica(storage_path, app_config.storage.replica_config()?).await?;
+            if let Some(reason) = replica.offline() {
+                eprintln!("Warning: working offline from the local replica, changes will fail ({})", reason);
+            }
+            Box::new(replica)
+        }
         _ => return Err(AppError::Storage("Invalid storage type".to_string())),
     };
 
     let prompts_api = Prompts::new(storage);
+    let output_json = cli.output.as_deref() == Some("json");
 
     match &cli.command {
-        Commands::List { tags } => {
-            let prompts = prompts_api.list_prompts(tags.clone()).await?;
+        Commands::List { query, tags, sort, since, before, tokens } => {
+            let mut prompts = match query {
+                Some(query) => prompts_api.show_prompt(query, tags.clone()).await?,
+                None => prompts_api.list_prompts(tags.clone()).await?,
+            };
+            let time = sort.unwrap_or(PromptTime::Updated);
+            if let Some(since) = since {
+                prompts.retain(|p| time.of(p).is_some_and(|t| t >= *since));
+            }
+            if let Some(before) = before {
+                // A prompt never used was last used before any date, but one without
+                // a creation time, saved by an older version, cannot be placed.
+                prompts.retain(|p| match time.of(p) {
+                    Some(t) => t < *before,
+                    None => time == PromptTime::Used,
+                });
+            }
+            if let Some(sort) = sort {
+                prompts_core::sort_by_time(&mut prompts, *sort);
+            }
+            let budget = app_config.tokens.budget(None, tokens.encoding);
             for prompt in prompts {
-                println!("{} - {}", &prompt.hash[..12], prompt.content);
+                let mut label = prompt.hash[..12].to_string();
+                // An edited prompt keeps the id it was added with, which
+                // selects it whatever its content becomes.
+                if prompt.id != prompt.hash {
+                    label = format!("{} @{}", label, &prompt.id[..12.min(prompt.id.len())]);
+                }
+                if let Some(name) = &prompt.name {
+                    label = format!("{} {}", label, name);
+                }
+                if tokens.count_tokens {
+                    let count = budget.count(&prompt.content);
+                    println!("{} {:>6} - {}", label, count.tokens, prompt.content);
+                } else {
+                    println!("{} - {}", label, prompt.content);
+                }
             }
         }
-        Commands::Show { query, tags } => {
-            let query_str = get_input(query.clone(), "Enter a query to search for a prompt:")?;
-            let search_results = prompts_api.show_prompt(&query_str, tags.clone()).await?;
+        Commands::Show { query, tags, revision, limit, tokens, select } => {
+            let mut search_results = find_prompts(&prompts_api, query, tags, select, "Enter a query to search for a prompt:").await?;
+            if let Some(limit) = limit {
+                search_results.truncate(*limit);
+            }
 
-            if search_results.len() == 1 {
-                println!("{}", search_results[0].content);
+            if let Some(prompt) = select_prompt(search_results, select)? {
+                let content = match revision {
+                    Some(number) => {
+                        let revision = prompts_api.get_revision(&prompt.id, *number).await?
+                            .ok_or_else(|| anyhow::anyhow!("Revision {} not found for prompt {}", number, &prompt.id[..12.min(prompt.id.len())]))?;
+                        revision.content
+                    }
+                    None => prompt.content,
+                };
+                println!("{}", content);
+                if tokens.count_tokens {
+                    report_tokens(&app_config.tokens.budget(None, tokens.encoding).count(&content));
+                }
+            }
+        }
+        Commands::Search { query, tags, limit } => {
+            let search_results = prompts_api.search(query, tags.clone(), *limit).await?;
+            if output_json {
+                println!("{}", serde_json::to_string_pretty(&search_results)?);
             } else {
-                let result_json = serde_json::to_string_pretty(&search_results)?;
-                println!("{}", result_json);
+                let highlight_matches = io::stdout().is_terminal();
+                for result in search_results {
+                    let content = if highlight_matches {
+                        highlight(&result.prompt.content, &result.indices)
+                    } else {
+                        result.prompt.content.clone()
+                    };
+                    println!("{} ({:.3}) - {}", &result.prompt.hash[..12], result.score, content);
+                }
+            }
+        }
+        Commands::History { query, tags, select } => {
+            let search_results = find_prompts(&prompts_api, query, tags, select, "Enter a query to search for a prompt:").await?;
+
+            if let Some(prompt) = select_prompt(search_results, select)? {
+                println!("Prompt {}", &prompt.id[..12.min(prompt.id.len())]);
+                for revision in prompts_api.history(&prompt.id).await? {
+                    let marker = if revision.hash == prompt.hash { "*" } else { " " };
+                    let summary = revision.content.lines().next().unwrap_or_default();
+                    println!("{} {} {} - {}", marker, revision.number, &revision.hash[..12], summary);
+                }
+            }
+        }
+        Commands::Runs { command } => match command {
+            RunsCommand::List { query, tags, limit, select } => {
+                let mut runs = match (query, &select.hash) {
+                    (None, None) => prompts_api.runs(None).await?,
+                    _ => {
+                        let search_results = find_prompts(&prompts_api, query, tags, select, "").await?;
+                        match select_prompt(search_results, select)? {
+                            Some(prompt) => prompts_api.runs(Some(&prompt.id)).await?,
+                            None => return Ok(()),
+                        }
+                    }
+                };
+                runs.reverse();
+                if let Some(limit) = limit {
+                    runs.truncate(*limit);
+                }
+
+                if output_json {
+                    println!("{}", serde_json::to_string_pretty(&runs)?);
+                } else {
+                    for run in runs {
+                        let tokens = run.usage.as_ref()
+                            .map(|usage| format!("{} tokens", usage.total_tokens))
+                            .unwrap_or_else(|| "- tokens".to_string());
+                        println!(
+                            "{} {} {} {}/{} {}ms {} - {}",
+                            &run.id[..12],
+                            run.created_at.format("%Y-%m-%d %H:%M:%S"),
+                            &run.prompt_hash[..12],
+                            run.provider,
+                            run.model,
+                            run.latency_ms,
+                            tokens,
+                            run.output.lines().next().unwrap_or_default()
+                        );
+                    }
+                }
+            }
+            RunsCommand::Show { id } => {
+                let run = prompts_api.find_run(id).await?;
+                if output_json {
+                    println!("{}", serde_json::to_string_pretty(&run)?);
+                } else {
+                    println!("Run {}", run.id);
+                    println!("Prompt:    {} ({})", &run.prompt_id[..12], &run.prompt_hash[..12]);
+                    println!("Provider:  {}/{}", run.provider, run.model);
+                    println!("Date:      {}", run.created_at.format("%Y-%m-%d %H:%M:%S UTC"));
+                    println!("Latency:   {}ms", run.latency_ms);
+                    if let Some(usage) = &run.usage {
+                        println!(
+                            "Tokens:    {} prompt + {} completion = {}",
+                            usage.prompt_tokens, usage.completion_tokens, usage.total_tokens
+                        );
+                    }
+                    println!("Variables: {}", run.variables);
+                    println!("\n--- Input\n{}\n\n--- Output\n{}", run.input, run.output);
+                }
+            }
+            RunsCommand::Diff { first, second } => {
+                let first = prompts_api.find_run(first).await?;
+                let second = prompts_api.find_run(second).await?;
+                let sections = [
+                    (
+                        "variables",
+                        serde_json::to_string_pretty(&first.variables)?,
+                        serde_json::to_string_pretty(&second.variables)?,
+                    ),
+                    ("input", first.input.clone(), second.input.clone()),
+                    ("output", first.output.clone(), second.output.clone()),
+                ];
+                let diffs: Vec<(&str, Option<String>)> = sections
+                    .iter()
+                    .map(|(label, old, new)| {
+                        let diff = (old != new).then(|| {
+                            similar::TextDiff::from_lines(&format!("{}\n", old), &format!("{}\n", new))
+                                .unified_diff()
+                                .header(&format!("{} {}", &first.id[..12], label), &format!("{} {}", &second.id[..12], label))
+                                .to_string()
+                        });
+                        (*label, diff)
+                    })
+                    .collect();
+
+                if output_json {
+                    let mut result = serde_json::Map::new();
+                    result.insert("first".to_string(), first.id.clone().into());
+                    result.insert("second".to_string(), second.id.clone().into());
+                    for (label, diff) in &diffs {
+                        result.insert(label.to_string(), diff.clone().into());
+                    }
+                    println!("{}", serde_json::to_string_pretty(&result)?);
+                } else {
+                    println!(
+                        "{} {} {}/{} -> {} {} {}/{}",
+                        &first.id[..12], &first.prompt_hash[..12], first.provider, first.model,
+                        &second.id[..12], &second.prompt_hash[..12], second.provider, second.model
+                    );
+                    let color = io::stdout().is_terminal();
+                    for (label, diff) in diffs {
+                        match diff {
+                            None => println!("{} unchanged", label),
+                            Some(diff) => {
+                                for line in diff.lines() {
+                                    if color && line.starts_with('+') && !line.starts_with("+++") {
+                                        println!("{}", line.green());
+                                    } else if color && line.starts_with('-') && !line.starts_with("---") {
+                                        println!("{}", line.red());
+                                    } else {
+                                        println!("{}", line);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        },
+        Commands::Revert { query, revision, tags, select } => {
+            let search_results = find_prompts(&prompts_api, query, tags, select, "Enter a query to find the prompt to revert:").await?;
+
+            if let Some(prompt) = select_prompt(search_results, select)? {
+                prompts_api.revert_prompt(&prompt.id, *revision).await?;
+                println!("Prompt {} reverted to revision {}.", &prompt.id[..12.min(prompt.id.len())], revision);
             }
         }
 
-        Commands::Generate { query, tags, variables } => {
-            let query_str = get_input(query.clone(), "Enter a query to search for a prompt:")?;
-            let search_results = prompts_api.show_prompt(&query_str, tags.clone()).await?;
+        Commands::Generate {
+            query,
+            tags,
+            variables,
+            batch,
+            batch_output,
+            jobs,
+            run,
+            provider,
+            model,
+            explain,
+            format,
+            tokens,
+            select,
+        } => {
+            let search_results = find_prompts(&prompts_api, query, tags, select, "Enter a query to search for a prompt:").await?;
+
+            if let Some(prompt) = select_prompt(search_results, select)? {
+                let mut context = variables.context()?;
 
-            if search_results.len() == 1 {
-                let prompt = &search_results[0];
-                let mut context = tera::Context::new();
-                for (key, value) in variables {
-                    context.insert(key, &value);
+                let composed = prompts_api.compose(&prompt).await?;
+                if *explain {
+                    explain_inclusions(&prompt, &composed.inclusions, &prompts_api.list_prompts(None).await?);
                 }
-                let rendered_prompt = tera::Tera::one_off(&prompt.content, &context, false)
+
+                if let Some(batch) = batch {
+                    if tokens.count_tokens {
+                        return Err(AppError::Anyhow("--count-tokens cannot be used with --batch".to_string()));
+                    }
+                    let rows = batch::read_rows(batch).map_err(|e| AppError::Anyhow(e.to_string()))?;
+                    let jobs = jobs.unwrap_or_else(|| {
+                        std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+                    });
+                    let results = batch::render_batch(&composed, &prompt.hash, &context, rows, jobs)
+                        .map_err(|e| AppError::Anyhow(e.to_string()))?;
+
+                    let mut out: Box<dyn Write> = match batch_output {
+                        Some(path) => Box::new(io::BufWriter::new(std::fs::File::create(path)?)),
+                        None => Box::new(io::stdout().lock()),
+                    };
+                    for result in &results {
+                        writeln!(out, "{}", serde_json::to_string(result)?)?;
+                    }
+                    out.flush()?;
+                    record_use(&prompts_api, &prompt.hash).await;
+
+                    let failed = results.iter().filter(|r| r.error.is_some()).count();
+                    if failed > 0 {
+                        return Err(AppError::Anyhow(format!("{} of {} rows failed to render", failed, results.len())));
+                    }
+                    return Ok(());
+                }
+
+                let template_variables = composed.variables()
+                    .map_err(|e| AppError::Anyhow(e.to_string()))?;
+                let missing = template::missing_variables(&template_variables, &context);
+                if !missing.is_empty() {
+                    if !output_json && io::stdin().is_terminal() && io::stderr().is_terminal() {
+                        for name in missing {
+                            context.insert(&name, &ask_for_variable(&name)?);
+                        }
+                    } else {
+                        return Err(AppError::MissingVariables(missing));
+                    }
+                }
+
+                let messages = composed.render_messages(&context)
                     .map_err(|e| AppError::Anyhow(e.to_string()))?;
-                println!("{}", rendered_prompt);
+                record_use(&prompts_api, &prompt.hash).await;
+                let rendered_prompt = match messages.as_slice() {
+                    [message] if !prompt.is_chat() => message.content.clone(),
+                    messages => chat::transcript(messages),
+                };
+                if tokens.count_tokens {
+                    let budget = app_config.tokens.budget(model.as_deref(), tokens.encoding);
+                    report_tokens(&budget.count(&rendered_prompt));
+                }
+                if !*run {
+                    if format.as_deref() == Some("messages") {
+                        println!("{}", serde_json::to_string_pretty(&messages)?);
+                    } else {
+                        println!("{}", rendered_prompt);
+                    }
+                    return Ok(());
+                }
+
+                let provider_name = match provider {
+                    Some(name) => name.clone(),
+                    None => app_config.providers.default_name()?,
+                };
+                let provider = app_config.providers.provider(&provider_name)?;
+                let request = provider::CompletionRequest {
+                    messages,
+                    model: model.clone(),
+                };
+                let variables = context.into_json();
+                if output_json {
+                    let run = prompts_api
+                        .execute(&prompt, &provider_name, provider.as_ref(), &request, variables, None)
+                        .await?;
+                    println!("{}", serde_json::to_string_pretty(&run)?);
+                } else {
+                    let mut print_token = |token: &str| {
+                        print!("{}", token);
+                        let _ = io::stdout().flush();
+                    };
+                    let run = prompts_api
+                        .execute(&prompt, &provider_name, provider.as_ref(), &request, variables, Some(&mut print_token))
+                        .await?;
+                    println!();
+                    if let Some(usage) = &run.usage {
+                        eprintln!(
+                            "{}: {} prompt + {} completion = {} tokens",
+                            run.model, usage.prompt_tokens, usage.completion_tokens, usage.total_tokens
+                        );
+                    }
+                    eprintln!("Recorded run {}.", &run.id[..12]);
+                }
+            }
+        }
+        Commands::Compare { queries, tags, variables, batch, provider, model, assertions, select } => {
+            if select.hash.is_some() {
+                return Err(AppError::Anyhow("--hash selects a single prompt, use `@prefix` queries to compare by hash".to_string()));
+            }
+            let mut prompts = Vec::new();
+            for query in queries {
+                let search_results = prompts_api.show_prompt(query, tags.clone()).await?;
+                match select_prompt(search_results, select)? {
+                    Some(prompt) => prompts.push(prompt),
+                    None => return Ok(()),
+                }
+            }
+
+            let context = variables.context()?;
+            let rows = match batch {
+                Some(batch) => batch::read_rows(batch).map_err(|e| AppError::Anyhow(e.to_string()))?,
+                None => vec![Ok(tera::Context::new())],
+            };
+            let assertions = match assertions {
+                Some(path) => eval::read_assertions(path).map_err(|e| AppError::Anyhow(e.to_string()))?,
+                None => Vec::new(),
+            };
+            let provider_name = match provider {
+                Some(name) => name.clone(),
+                None => app_config.providers.default_name()?,
+            };
+            let provider = app_config.providers.provider(&provider_name)?;
+            let options = compare::CompareOptions {
+                provider_name: &provider_name,
+                provider: provider.as_ref(),
+                model: model.clone(),
+                assertions: &assertions,
+            };
+            let comparisons = compare::compare_prompts(&prompts_api, &prompts, &context, rows, &options).await?;
+
+            if output_json {
+                println!("{}", serde_json::to_string_pretty(&comparisons)?);
             } else {
-                let result_json = serde_json::to_string_pretty(&search_results)?;
-                println!("{}", result_json);
+                for (row, group) in comparisons.chunk_by(|a, b| a.row == b.row).enumerate() {
+                    if row > 0 {
+                        println!();
+                    }
+                    println!("Input {}", row);
+                    println!("  {:<12}  {:>7}  {:>9}  {:>7}  {:>5}", "PROMPT", "LENGTH", "LATENCY", "TOKENS", "SCORE");
+                    for comparison in group {
+                        let tokens = comparison.usage.as_ref()
+                            .map(|usage| usage.total_tokens.to_string())
+                            .unwrap_or_else(|| "-".to_string());
+                        let score = if assertions.is_empty() {
+                            "-".to_string()
+                        } else {
+                            format!("{}/{}", comparison.passed, assertions.len())
+                        };
+                        println!(
+                            "  {:<12}  {:>7}  {:>9}  {:>7}  {:>5}",
+                            &comparison.prompt_hash[..12],
+                            comparison.length,
+                            format!("{}ms", comparison.latency_ms),
+                            tokens,
+                            score
+                        );
+                    }
+                    for comparison in group {
+                        println!("\n  --- {}", &comparison.prompt_hash[..12]);
+                        if let Some(output) = &comparison.output {
+                            for line in output.lines() {
+                                println!("  {}", line);
+                            }
+                        }
+                        for failure in &comparison.failures {
+                            println!("  ! {}", failure);
+                        }
+                    }
+                }
+            }
+        }
+        Commands::Test { command } => match command {
+            TestCommand::Run { query, tags, provider, case, select } => {
+                let search_results = find_prompts(&prompts_api, query, tags, select, "Enter a query to search for a prompt:").await?;
+
+                if let Some(mut prompt) = select_prompt(search_results, select)? {
+                    if let Some(names) = case {
+                        prompt.tests.retain(|test| names.contains(&test.name));
+                    }
+                    if prompt.tests.is_empty() {
+                        println!("Prompt {} has no test cases.", &prompt.hash[..12]);
+                        return Ok(());
+                    }
+
+                    let provider = provider
+                        .as_deref()
+                        .map(|name| app_config.providers.provider(name))
+                        .transpose()?;
+                    let results = prompts_api.test_prompt(&prompt, provider.as_deref()).await?;
+                    let failed = results.iter().filter(|r| !r.passed()).count();
+
+                    if output_json {
+                        println!("{}", serde_json::to_string_pretty(&results)?);
+                    } else {
+                        for result in &results {
+                            if result.passed() {
+                                println!("PASS {}", result.name);
+                            } else {
+                                println!("FAIL {}", result.name);
+                                for failure in &result.failures {
+                                    println!("  - {}", failure);
+                                }
+                            }
+                        }
+                        println!("{} tests, {} passed, {} failed", results.len(), results.len() - failed, failed);
+                    }
+                    if failed > 0 {
+                        return Err(AppError::Anyhow(format!("{} of {} tests failed", failed, results.len())));
+                    }
+                }
+            }
+            TestCommand::Set { query, file, tags, select } => {
+                let cases = eval::read_test_cases(file).map_err(|e| AppError::Anyhow(e.to_string()))?;
+                let search_results = find_prompts(&prompts_api, query, tags, select, "Enter a query to search for a prompt:").await?;
+
+                if let Some(prompt) = select_prompt(search_results, select)? {
+                    let count = cases.len();
+                    prompts_api.set_tests(&prompt.hash, cases).await?;
+                    println!("Saved {} test cases for prompt {}.", count, &prompt.hash[..12]);
+                }
+            }
+            TestCommand::Show { query, tags, select } => {
+                let search_results = find_prompts(&prompts_api, query, tags, select, "Enter a query to search for a prompt:").await?;
+
+                if let Some(prompt) = select_prompt(search_results, select)? {
+                    if output_json {
+                        println!("{}", serde_json::to_string_pretty(&prompt.tests)?);
+                    } else {
+                        print!("{}", serde_yaml::to_string(&prompt.tests).map_err(|e| AppError::Anyhow(e.to_string()))?);
+                    }
+                }
+            }
+        },
+        Commands::Tokens { query, tags, variables, encoding, model, select } => {
+            let search_results = find_prompts(&prompts_api, query, tags, select, "Enter a query to search for a prompt:").await?;
+
+            if let Some(prompt) = select_prompt(search_results, select)? {
+                let context = variables.context()?;
+                let composed = prompts_api.compose(&prompt).await?;
+                let template_variables = composed.variables()
+                    .map_err(|e| AppError::Anyhow(e.to_string()))?;
+                let missing = template::missing_variables(&template_variables, &context);
+                let text = if missing.is_empty() {
+                    composed.render(&context).map_err(|e| AppError::Anyhow(e.to_string()))?
+                } else {
+                    eprintln!("Counting the unrendered template, missing variables: {}", missing.join(", "));
+                    prompt.content.clone()
+                };
+
+                let count = app_config.tokens.budget(model.as_deref(), *encoding).count(&text);
+                if output_json {
+                    println!("{}", serde_json::to_string_pretty(&count)?);
+                } else {
+                    println!("{}", count);
+                }
+                if count.exceeds_limit() {
+                    report_limit(&count);
+                }
+            }
+        }
+        Commands::Vars { query, tags, select } => {
+            let search_results = find_prompts(&prompts_api, query, tags, select, "Enter a query to search for a prompt:").await?;
+
+            if let Some(prompt) = select_prompt(search_results, select)? {
+                let template_variables = prompts_api.compose(&prompt).await?.variables()
+                    .map_err(|e| AppError::Anyhow(e.to_string()))?;
+                if output_json {
+                    println!("{}", serde_json::to_string_pretty(&template_variables)?);
+                } else {
+                    for variable in template_variables {
+                        if variable.required {
+                            println!("{}", variable.name);
+                        } else {
+                            println!("{} (optional)", variable.name);
+                        }
+                    }
+                }
             }
         }
         Commands::Add {
             text,
             tags,
             categories,
+            name,
+            aliases,
+            chat,
+            messages,
         } => {
-            let text_content = get_input(text.clone(), "Enter the prompt text:")?;
-            let mut prompt = Prompt::new(&text_content, tags.clone(), categories.clone());
+            let mut prompt = if let Some(path) = messages {
+                let messages = chat::read_messages(path).map_err(|e| AppError::Anyhow(e.to_string()))?;
+                Prompt::chat(messages, tags.clone(), categories.clone())
+            } else if text.is_none() && io::stdin().is_terminal() {
+                let template = Document {
+                    content: if *chat { "[system]\n\n[user]".to_string() } else { String::new() },
+                    tags: tags.clone().unwrap_or_default(),
+                    categories: categories.clone().unwrap_or_default(),
+                };
+                let Some(document) = edit_document(&template)? else {
+                    return Ok(());
+                };
+                let tags = Some(document.tags).filter(|t| !t.is_empty());
+                let categories = Some(document.categories).filter(|c| !c.is_empty());
+                new_prompt(&document.content, *chat, tags, categories)?
+            } else {
+                let text_content = get_input(text.clone(), "Enter the prompt text:")?;
+                new_prompt(&text_content, *chat, tags.clone(), categories.clone())?
+            };
+            prompt.name = name.clone();
+            prompt.aliases = aliases.clone().unwrap_or_default();
+            prompt.author = app_config.author.clone().or_else(git_user_name);
             if prompts_api.add_prompt(&mut prompt).await? {
                 println!("Prompt added successfully with hash: {}", &prompt.hash[..12]);
             } else {
@@ -235,52 +1473,165 @@ async fn run_cli(cli: Cli) -> Result<(), AppError> {
             query,
             filter_tags,
             text,
+            messages,
+            name,
+            clear_name,
+            add_aliases,
+            remove_aliases,
             add_tags,
             remove_tags,
             add_categories,
             remove_categories,
+            select,
         } => {
-            let query_str = get_input(query.clone(), "Enter a query to find the prompt to edit:")?;
-            let search_results = prompts_api.show_prompt(&query_str, filter_tags.clone()).await?;
+            let search_results = find_prompts(&prompts_api, query, filter_tags, select, "Enter a query to find the prompt to edit:").await?;
 
-            if search_results.len() == 1 {
-                let old_prompt = &search_results[0];
+            if let Some(old_prompt) = select_prompt(search_results, select)? {
                 let old_prompt_hash = old_prompt.hash.clone();
+                let messages = match messages {
+                    Some(_) if !old_prompt.is_chat() => {
+                        return Err(AppError::Anyhow(format!("Prompt {} is not a chat prompt", &old_prompt_hash[..12])));
+                    }
+                    Some(path) => Some(chat::read_messages(path).map_err(|e| AppError::Anyhow(e.to_string()))?),
+                    None => None,
+                };
+                let text = text.clone();
+                let rename = name.is_some() || *clear_name || add_aliases.is_some() || remove_aliases.is_some();
+                if rename {
+                    let new_name = if *clear_name { None } else { name.clone().or(old_prompt.name.clone()) };
+                    let mut aliases = old_prompt.aliases.clone();
+                    for alias in add_aliases.iter().flatten() {
+                        if !aliases.contains(alias) {
+                            aliases.push(alias.clone());
+                        }
+                    }
+                    aliases.retain(|a| !remove_aliases.as_deref().unwrap_or_default().contains(a));
+                    prompts_api.set_names(&old_prompt_hash, new_name, aliases).await?;
+                }
+                let no_changes = !rename
+                    && text.is_none()
+                    && messages.is_none()
+                    && add_tags.is_none()
+                    && remove_tags.is_none()
+                    && add_categories.is_none()
+                    && remove_categories.is_none();
+                let use_editor = no_changes && io::stdin().is_terminal();
+                let text = if no_changes && !use_editor {
+                    Some(get_input(None, "Enter the new prompt text:")?)
+                } else {
+                    text
+                };
 
-                prompts_api.edit_prompt(
-                    &old_prompt_hash,
-                    text.clone(),
-                    add_tags.clone(),
-                    remove_tags.clone(),
-                    add_categories.clone(),
-                    remove_categories.clone(),
-                ).await?;
+                if use_editor {
+                    let old_tags = old_prompt.tags.clone().unwrap_or_default();
+                    let old_categories = old_prompt.categories.clone().unwrap_or_default();
+                    let original = Document {
+                        content: old_prompt.content.clone(),
+                        tags: old_tags.clone(),
+                        categories: old_categories.clone(),
+                    };
+                    let Some(document) = edit_document(&original)? else {
+                        return Ok(());
+                    };
+
+                    // Leaving the body alone keeps the messages of a chat
+                    // prompt as they are, rather than parsed from the transcript.
+                    let content = (document.content != original.content).then_some(document.content);
+                    prompts_api.edit_prompt(
+                        &old_prompt_hash,
+                        content,
+                        Some(difference(&document.tags, &old_tags)),
+                        Some(difference(&old_tags, &document.tags)),
+                        Some(difference(&document.categories, &old_categories)),
+                        Some(difference(&old_categories, &document.categories)),
+                    ).await?;
+                } else if text.is_some()
+                    || add_tags.is_some()
+                    || remove_tags.is_some()
+                    || add_categories.is_some()
+                    || remove_categories.is_some()
+                {
+                    prompts_api.edit_prompt(
+                        &old_prompt_hash,
+                        text,
+                        add_tags.clone(),
+                        remove_tags.clone(),
+                        add_categories.clone(),
+                        remove_categories.clone(),
+                    ).await?;
+                }
+                // Tags and categories are edited first as they leave the hash unchanged.
+                if let Some(messages) = messages {
+                    prompts_api.set_messages(&old_prompt_hash, messages).await?;
+                }
 
                 println!("Prompt {} updated.", &old_prompt_hash[..12]);
-            } else {
-                let result_json = serde_json::to_string_pretty(&search_results)?;
-                println!("{}", result_json);
             }
         }
-        Commands::Delete { query, tags } => {
-            let query_str = get_input(query.clone(), "Enter a query to find the prompt to delete:")?;
-            let search_results = prompts_api.show_prompt(&query_str, tags.clone()).await?;
+        Commands::Delete { query, tags, yes, select } => {
+            let search_results = find_prompts(&prompts_api, query, tags, select, "Enter a query to find the prompt to delete:").await?;
 
-            if search_results.len() == 1 {
-                let prompt_hash = search_results[0].hash.clone();
+            if let Some(prompt) = select_prompt(search_results, select)? {
+                let prompt_hash = prompt.hash.clone();
+                let question = format!(
+                    "Delete prompt {} - {}?",
+                    &prompt_hash[..12],
+                    prompt.content.lines().next().unwrap_or_default()
+                );
+                if !*yes && !confirm(&question)? {
+                    println!("Prompt {} was not deleted.", &prompt_hash[..12]);
+                    return Ok(());
+                }
                 prompts_api.delete_prompt(&prompt_hash).await?;
-                println!("Prompt {} deleted successfully.", &prompt_hash[..12]);
-            } else {
-                let result_json = serde_json::to_string_pretty(&search_results)?;
-                println!("{}", result_json);
+                println!(
+                    "Prompt {} deleted successfully. Run `restore {}` to undo.",
+                    &prompt_hash[..12],
+                    &prompt_hash[..12]
+                );
             }
         }
+        Commands::Trash { command } => match command {
+            TrashCommand::List => {
+                let prompts = prompts_api.list_trash().await?;
+                if output_json {
+                    println!("{}", serde_json::to_string_pretty(&prompts)?);
+                } else {
+                    for prompt in prompts {
+                        let deleted_at = prompt.deleted_at
+                            .map(|at| at.format("%Y-%m-%d %H:%M:%S").to_string())
+                            .unwrap_or_default();
+                        println!(
+                            "{} {} - {}",
+                            &prompt.hash[..12],
+                            deleted_at,
+                            prompt.content.lines().next().unwrap_or_default()
+                        );
+                    }
+                }
+            }
+            TrashCommand::Purge { older_than, yes } => {
+                let question = match older_than {
+                    Some(before) => format!("Delete prompts trashed before {} for good?", before.format("%Y-%m-%d %H:%M:%S")),
+                    None => "Delete every prompt in the trash for good?".to_string(),
+                };
+                if !*yes && !confirm(&question)? {
+                    println!("Nothing was purged.");
+                    return Ok(());
+                }
+                let purged = prompts_api.purge_trash(*older_than).await?;
+                println!("Purged {} prompts.", purged.len());
+            }
+        },
+        Commands::Restore { hash } => {
+            let prompt = prompts_api.restore_prompt(hash).await?;
+            println!("Prompt {} restored.", &prompt.hash[..12]);
+        }
         Commands::Import { path } => {
             let mut imported_count = 0;
             for entry in std::fs::read_dir(path)? {
                 let entry = entry?;
                 let path = entry.path();
-                if path.is_file() && path.extension().map_or(false, |ext| ext == "json") {
+                if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
                     let json = std::fs::read_to_string(&path)?;
                     let mut prompt: Prompt = serde_json::from_str(&json)?;
                     prompts_api.add_prompt(&mut prompt).await?;
@@ -289,6 +1640,19 @@ async fn run_cli(cli: Cli) -> Result<(), AppError> {
             }
             println!("Imported {} prompts.", imported_count);
         }
+        Commands::Db { .. } | Commands::Migrate { .. } => unreachable!("handled before the storage is opened"),
+        Commands::Pull | Commands::Push => unreachable!("handled before the storage is opened"),
+        Commands::Sync => {
+            let report = prompts_api.sync().await?;
+            if output_json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                match report.frame_no {
+                    Some(frame_no) => println!("Synced {} frames, up to frame {}.", report.frames_synced, frame_no),
+                    None => println!("Synced {} frames, the server has no changes yet.", report.frames_synced),
+                }
+            }
+        }
         Commands::Export { path } => {
             std::fs::create_dir_all(path)?;
             let prompts = prompts_api.list_prompts(None).await?;
@@ -0,0 +1,183 @@
+use anyhow::Result;
+use chrono::{DateTime, SubsecRound, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::git::GitStorage;
+use crate::storage::{JsonStorage, LibSQLStorage, Prompt, Storage};
+
+/// A storage backend and its location, written `json:PATH`, `libsql:PATH` or
+/// `git:PATH`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageSpec {
+    /// The backend, as in the `storage.type` setting.
+    pub kind: String,
+    /// The directory of a JSON or git store, or the database file of a LibSQL store.
+    pub path: PathBuf,
+}
+
+impl StorageSpec {
+    /// Opens the storage, creating it if it does not exist yet.
+    pub async fn open(&self) -> Result<Box<dyn Storage + Send + Sync>> {
+        Ok(match self.kind.as_str() {
+            "json" => {
+                std::fs::create_dir_all(&self.path)?;
+                Box::new(JsonStorage::new(Some(self.path.clone()))?)
+            }
+            "git" => Box::new(GitStorage::new(Some(self.path.clone())).await?),
+            _ => {
+                if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    std::fs::create_dir_all(parent)?;
+                }
+                Box::new(LibSQLStorage::new(Some(self.path.clone())).await?)
+            }
+        })
+    }
+}
+
+impl FromStr for StorageSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (kind, path) = s
+            .split_once(':')
+            .filter(|(_, path)| !path.is_empty())
+            .ok_or_else(|| format!("invalid storage `{}`, expected json:PATH, libsql:PATH or git:PATH", s))?;
+        match kind {
+            "json" | "libsql" | "git" => Ok(Self { kind: kind.to_string(), path: PathBuf::from(path) }),
+            _ => Err(format!("unknown storage type `{}`, expected json, libsql or git", kind)),
+        }
+    }
+}
+
+impl fmt::Display for StorageSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.kind, self.path.display())
+    }
+}
+
+/// What a migration copied, and what it found already in place.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize)]
+pub struct MigrationReport {
+    /// Live prompts copied to the target.
+    pub prompts_copied: usize,
+    /// Live prompts the target already had.
+    pub prompts_skipped: usize,
+    /// Prompts in the trash copied to the target's trash.
+    pub trash_copied: usize,
+    /// Revisions copied to the target.
+    pub revisions_copied: usize,
+    /// Recorded runs copied to the target.
+    pub runs_copied: usize,
+    /// Live prompts in the target after the migration.
+    pub target_prompts: usize,
+}
+
+/// Copies every prompt, with its metadata, revisions and runs, and the trash
+/// from one storage to another, then checks the target against the source.
+///
+/// Anything the target already has is left alone, so an interrupted migration
+/// can be run again to finish it, and running it twice changes nothing.
+pub async fn migrate(from: &dyn Storage, to: &dyn Storage) -> Result<MigrationReport> {
+    let mut report = MigrationReport::default();
+    let source = from.load_prompts().await?;
+    let source_trash = from.load_trash().await?;
+    let target: HashSet<String> = to.load_prompts().await?.into_iter().map(|p| p.hash).collect();
+    let target_trash: HashSet<String> = to.load_trash().await?.into_iter().map(|p| p.hash).collect();
+
+    // Revisions go first, so that storages committing each saved prompt include them.
+    let ids: HashSet<&str> = source.iter().chain(&source_trash).map(|p| p.id.as_str()).collect();
+    for id in ids {
+        let copied: HashSet<usize> = to.load_revisions(id).await?.iter().map(|r| r.number).collect();
+        for revision in from.load_revisions(id).await? {
+            if !copied.contains(&revision.number) {
+                to.save_revision(id, &revision).await?;
+                report.revisions_copied += 1;
+            }
+        }
+    }
+
+    for prompt in &source {
+        if target.contains(&prompt.hash) {
+            report.prompts_skipped += 1;
+            continue;
+        }
+        to.save_prompt(&mut prompt.clone()).await?;
+        report.prompts_copied += 1;
+    }
+    for prompt in &source_trash {
+        if target.contains(&prompt.hash) || target_trash.contains(&prompt.hash) {
+            continue;
+        }
+        // Saved straight to the trash, since a live prompt may have its name by now.
+        to.save_trashed(prompt).await?;
+        report.trash_copied += 1;
+    }
+
+    let copied_runs: HashSet<String> = to.load_runs(None).await?.into_iter().map(|r| r.id).collect();
+    for run in from.load_runs(None).await? {
+        if !copied_runs.contains(&run.id) {
+            to.save_run(&run).await?;
+            report.runs_copied += 1;
+        }
+    }
+
+    report.target_prompts = verify(&source, &source_trash, to).await?;
+    Ok(report)
+}
+
+/// Checks that the target holds every source prompt and trashed prompt
+/// unchanged, and returns how many live prompts it has.
+///
+/// A trashed prompt the target had live before the migration is left live.
+async fn verify(source: &[Prompt], source_trash: &[Prompt], to: &dyn Storage) -> Result<usize> {
+    let target = to.load_prompts().await?;
+    let target_trash = to.load_trash().await?;
+    let by_hash: HashMap<&str, &Prompt> = target.iter().map(|p| (p.hash.as_str(), p)).collect();
+    let trash_by_hash: HashMap<&str, &Prompt> = target_trash.iter().map(|p| (p.hash.as_str(), p)).collect();
+    let live = source.iter().map(|p| (p, by_hash.get(p.hash.as_str()).copied()));
+    let trashed = source_trash
+        .iter()
+        .filter(|p| trash_by_hash.contains_key(p.hash.as_str()) || !by_hash.contains_key(p.hash.as_str()))
+        .map(|p| (p, trash_by_hash.get(p.hash.as_str()).copied()));
+    let mut missing = Vec::new();
+    for (prompt, copy) in live.chain(trashed) {
+        match copy {
+            None => missing.push(&prompt.hash[..12.min(prompt.hash.len())]),
+            Some(copy) if comparable(copy) != comparable(prompt) => {
+                return Err(anyhow::anyhow!(
+                    "Prompt {} differs between the source and the target",
+                    &prompt.hash[..12.min(prompt.hash.len())]
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+    if !missing.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Migration incomplete, {} prompts are missing from the target: {}",
+            missing.len(),
+            missing.join(", ")
+        ));
+    }
+    Ok(target.len())
+}
+
+/// Returns `prompt` with the differences that storing it can introduce
+/// smoothed over: LibSQL reads absent tags and categories back as empty
+/// lists, and keeps timestamps to the millisecond.
+fn comparable(prompt: &Prompt) -> Prompt {
+    let millis = |time: Option<DateTime<Utc>>| time.map(|t| t.trunc_subsecs(3));
+    Prompt {
+        tags: prompt.tags.clone().filter(|tags| !tags.is_empty()),
+        categories: prompt.categories.clone().filter(|categories| !categories.is_empty()),
+        created_at: millis(prompt.created_at),
+        updated_at: millis(prompt.updated_at),
+        last_used_at: millis(prompt.last_used_at),
+        deleted_at: millis(prompt.deleted_at),
+        ..prompt.clone()
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use std::io::Write;
+
+use crate::storage::Prompt;
+
+/// The state of the interactive prompt picker.
+///
+/// The state is kept separate from terminal handling so that filtering and
+/// navigation can be driven without a TTY.
+pub struct Picker {
+    prompts: Vec<Prompt>,
+    query: String,
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl Picker {
+    /// Creates a new `Picker` over the given candidates with an empty filter.
+    pub fn new(prompts: Vec<Prompt>) -> Self {
+        let matches = (0..prompts.len()).collect();
+        Self {
+            prompts,
+            query: String::new(),
+            matches,
+            selected: 0,
+        }
+    }
+
+    /// The current filter text.
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Appends a character to the filter and re-filters the candidates.
+    pub fn push_char(&mut self, c: char) {
+        self.query.push(c);
+        self.refilter();
+    }
+
+    /// Removes the last character of the filter and re-filters the candidates.
+    pub fn pop_char(&mut self) {
+        self.query.pop();
+        self.refilter();
+    }
+
+    /// Moves the selection one entry up.
+    pub fn move_up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Moves the selection one entry down.
+    pub fn move_down(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    /// The candidates matching the current filter, best match first.
+    pub fn matches(&self) -> Vec<&Prompt> {
+        self.matches.iter().map(|&i| &self.prompts[i]).collect()
+    }
+
+    /// The index of the selected entry within `matches`.
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    /// The currently selected prompt, if any candidate matches the filter.
+    pub fn selected(&self) -> Option<&Prompt> {
+        self.matches.get(self.selected).map(|&i| &self.prompts[i])
+    }
+
+    fn refilter(&mut self) {
+        let matcher = SkimMatcherV2::default();
+        let mut scored: Vec<(i64, usize)> = self
+            .prompts
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| {
+                if self.query.is_empty() {
+                    Some((0, i))
+                } else {
+                    matcher.fuzzy_match(&p.content, &self.query).map(|score| (score, i))
+                }
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+}
+
+/// Lets the user choose one of `prompts` interactively on the terminal.
+///
+/// The picker is drawn on stderr so that stdout only receives the command's
+/// real output. Returns `None` if the user cancels.
+pub fn pick(prompts: Vec<Prompt>) -> Result<Option<Prompt>> {
+    let mut picker = Picker::new(prompts);
+    let mut out = std::io::stderr();
+
+    terminal::enable_raw_mode()?;
+    queue!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let result = run(&mut picker, &mut out);
+    queue!(out, cursor::Show, terminal::LeaveAlternateScreen)?;
+    out.flush()?;
+    terminal::disable_raw_mode()?;
+
+    Ok(result?.cloned())
+}
+
+fn run<'a, W: Write>(picker: &'a mut Picker, out: &mut W) -> Result<Option<&'a Prompt>> {
+    loop {
+        draw(picker, out)?;
+        if let Event::Key(KeyEvent { code, modifiers, kind: KeyEventKind::Press, .. }) = event::read()? {
+            let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+            match code {
+                KeyCode::Enter => break,
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Char('c') if ctrl => return Ok(None),
+                KeyCode::Up => picker.move_up(),
+                KeyCode::Char('p') if ctrl => picker.move_up(),
+                KeyCode::Down => picker.move_down(),
+                KeyCode::Char('n') if ctrl => picker.move_down(),
+                KeyCode::Backspace => picker.pop_char(),
+                KeyCode::Char(c) if !ctrl => picker.push_char(c),
+                _ => {}
+            }
+        }
+    }
+    Ok(picker.selected())
+}
+
+fn draw<W: Write>(picker: &Picker, out: &mut W) -> Result<()> {
+    let (width, height) = terminal::size()?;
+    let width = width as usize;
+    let list_height = (height as usize / 2).saturating_sub(2).max(1);
+    let matches = picker.matches();
+    let offset = picker.selected_index().saturating_sub(list_height - 1);
+
+    queue!(out, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+    queue!(out, Print(format!("> {}", picker.query())))?;
+    queue!(out, cursor::MoveToNextLine(1))?;
+    queue!(out, Print(truncate(&format!("  {}/{} prompts", matches.len(), picker.prompts.len()), width)))?;
+
+    for (i, prompt) in matches.iter().enumerate().skip(offset).take(list_height) {
+        queue!(out, cursor::MoveToNextLine(1))?;
+        let summary = prompt.content.lines().next().unwrap_or_default();
+        let line = truncate(&format!("{} {} - {}", if i == picker.selected_index() { ">" } else { " " }, &prompt.hash[..12.min(prompt.hash.len())], summary), width);
+        if i == picker.selected_index() {
+            queue!(out, SetAttribute(Attribute::Reverse), Print(line), SetAttribute(Attribute::Reset))?;
+        } else {
+            queue!(out, Print(line))?;
+        }
+    }
+
+    queue!(out, cursor::MoveTo(0, (list_height + 2) as u16))?;
+    queue!(out, Print("─".repeat(width)))?;
+    if let Some(prompt) = picker.selected() {
+        let mut preview = Vec::new();
+        if let Some(tags) = prompt.tags.as_ref().filter(|t| !t.is_empty()) {
+            preview.push(format!("tags: {}", tags.join(", ")));
+        }
+        if let Some(categories) = prompt.categories.as_ref().filter(|c| !c.is_empty()) {
+            preview.push(format!("categories: {}", categories.join(", ")));
+        }
+        if !preview.is_empty() {
+            preview.push(String::new());
+        }
+        preview.extend(prompt.content.lines().map(str::to_string));
+
+        let preview_height = (height as usize).saturating_sub(list_height + 3);
+        for line in preview.iter().take(preview_height) {
+            queue!(out, cursor::MoveToNextLine(1), Print(truncate(line, width)))?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn truncate(line: &str, width: usize) -> String {
+    line.chars().take(width).collect()
+}
@@ -0,0 +1,46 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::{Completion, CompletionRequest, Provider};
+
+/// Settings for the mock provider.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct MockConfig {
+    /// A fixed reply. Without one the provider echoes the last message back.
+    pub response: Option<String>,
+}
+
+/// A deterministic provider that makes no network calls, for tests and
+/// offline checks of prompt test suites.
+pub struct MockProvider {
+    config: MockConfig,
+}
+
+impl MockProvider {
+    pub fn new(config: MockConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl Provider for MockProvider {
+    async fn complete(
+        &self,
+        request: &CompletionRequest,
+        on_token: Option<&mut (dyn for<'t> FnMut(&'t str) + Send)>,
+    ) -> Result<Completion> {
+        let content = match &self.config.response {
+            Some(response) => response.clone(),
+            None => request.messages.last().map(|m| m.content.clone()).unwrap_or_default(),
+        };
+        if let Some(on_token) = on_token {
+            on_token(&content);
+        }
+        Ok(Completion {
+            model: request.model.clone().unwrap_or_else(|| "mock".to_string()),
+            content,
+            usage: None,
+        })
+    }
+}
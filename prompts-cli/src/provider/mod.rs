@@ -0,0 +1,124 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+mod mock;
+mod ollama;
+mod openai;
+
+pub use mock::{MockConfig, MockProvider};
+pub use ollama::{OllamaApi, OllamaConfig, OllamaProvider};
+pub use openai::{OpenAIConfig, OpenAIProvider};
+
+/// A message in a chat conversation sent to a provider.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Message {
+    /// Who the message is from: `system`, `user` or `assistant`.
+    pub role: String,
+    /// The text of the message.
+    pub content: String,
+}
+
+impl Message {
+    pub fn user(content: &str) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: content.to_string(),
+        }
+    }
+}
+
+/// A request for a completion of a conversation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompletionRequest {
+    /// The conversation so far.
+    pub messages: Vec<Message>,
+    /// The model to use instead of the provider's configured one.
+    pub model: Option<String>,
+}
+
+/// Token counts reported by a provider for one completion.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+/// A provider's answer to a completion request.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Completion {
+    /// The model that produced the completion, as reported by the provider.
+    pub model: String,
+    /// The generated text.
+    pub content: String,
+    /// Token usage, if the provider reported it.
+    pub usage: Option<Usage>,
+}
+
+/// A language model backend that completes conversations.
+#[async_trait]
+pub trait Provider {
+    /// Sends `request` and waits for the whole completion.
+    ///
+    /// When `on_token` is given the response is streamed, and each piece of
+    /// text is passed to it as soon as it arrives.
+    async fn complete(
+        &self,
+        request: &CompletionRequest,
+        on_token: Option<&mut (dyn for<'t> FnMut(&'t str) + Send)>,
+    ) -> Result<Completion>;
+}
+
+/// The `[providers]` section of the configuration file.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProvidersConfig {
+    /// The provider to use when none is named. Defaults to the first
+    /// configured one of `openai` and `ollama`.
+    pub default: Option<String>,
+    /// An OpenAI-compatible chat completions endpoint.
+    pub openai: Option<OpenAIConfig>,
+    /// A local Ollama server.
+    pub ollama: Option<OllamaConfig>,
+    /// The deterministic mock provider, which is available without configuration.
+    pub mock: Option<MockConfig>,
+}
+
+impl ProvidersConfig {
+    /// Returns the name of the provider to use when none is given.
+    pub fn default_name(&self) -> Result<String> {
+        if let Some(name) = &self.default {
+            return Ok(name.clone());
+        }
+        if self.openai.is_some() {
+            Ok("openai".to_string())
+        } else if self.ollama.is_some() {
+            Ok("ollama".to_string())
+        } else {
+            Err(anyhow::anyhow!(
+                "No provider is configured, add a [providers.openai] or [providers.ollama] table to config.toml"
+            ))
+        }
+    }
+
+    /// Builds the provider configured under `[providers.<name>]`.
+    pub fn provider(&self, name: &str) -> Result<Box<dyn Provider + Send + Sync>> {
+        let not_configured = || {
+            anyhow::anyhow!(
+                "Provider `{}` is not configured, add a [providers.{}] table to config.toml",
+                name,
+                name
+            )
+        };
+        match name {
+            "openai" => Ok(Box::new(OpenAIProvider::new(
+                self.openai.clone().ok_or_else(not_configured)?,
+            ))),
+            "ollama" => Ok(Box::new(OllamaProvider::new(
+                self.ollama.clone().ok_or_else(not_configured)?,
+            ))),
+            "mock" => Ok(Box::new(MockProvider::new(self.mock.clone().unwrap_or_default()))),
+            _ => Err(anyhow::anyhow!("Unknown provider `{}`", name)),
+        }
+    }
+}
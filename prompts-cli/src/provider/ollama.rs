@@ -0,0 +1,181 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::{Completion, CompletionRequest, Message, Provider, Usage};
+
+/// Which Ollama endpoint to send requests to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OllamaApi {
+    /// `/api/chat`, which takes the conversation as messages.
+    #[default]
+    Chat,
+    /// `/api/generate`, which takes a single prompt and an optional system prompt.
+    Generate,
+}
+
+/// Settings for a local Ollama server.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct OllamaConfig {
+    /// The server address, e.g. `http://localhost:11434`.
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+    /// The model to run unless one is given per request, e.g. `llama3.2`.
+    pub model: String,
+    /// The endpoint to use.
+    #[serde(default)]
+    pub api: OllamaApi,
+}
+
+fn default_base_url() -> String {
+    "http://localhost:11434".to_string()
+}
+
+/// A client for the Ollama HTTP API.
+pub struct OllamaProvider {
+    config: OllamaConfig,
+    client: reqwest::Client,
+}
+
+impl OllamaProvider {
+    pub fn new(config: OllamaConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+/// A response object from either endpoint; streamed responses send one per line.
+#[derive(Deserialize)]
+struct OllamaResponse {
+    model: Option<String>,
+    /// Set by `/api/chat`.
+    message: Option<Message>,
+    /// Set by `/api/generate`.
+    response: Option<String>,
+    #[serde(default)]
+    done: bool,
+    prompt_eval_count: Option<u64>,
+    eval_count: Option<u64>,
+    error: Option<String>,
+}
+
+impl OllamaResponse {
+    fn text(&self) -> &str {
+        match (&self.message, &self.response) {
+            (Some(message), _) => &message.content,
+            (None, Some(response)) => response,
+            (None, None) => "",
+        }
+    }
+
+    fn usage(&self) -> Option<Usage> {
+        if !self.done || (self.prompt_eval_count.is_none() && self.eval_count.is_none()) {
+            return None;
+        }
+        let prompt_tokens = self.prompt_eval_count.unwrap_or(0);
+        let completion_tokens = self.eval_count.unwrap_or(0);
+        Some(Usage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        })
+    }
+}
+
+#[async_trait]
+impl Provider for OllamaProvider {
+    async fn complete(
+        &self,
+        request: &CompletionRequest,
+        on_token: Option<&mut (dyn for<'t> FnMut(&'t str) + Send)>,
+    ) -> Result<Completion> {
+        let base_url = self.config.base_url.trim_end_matches('/');
+        let model = request.model.clone().unwrap_or_else(|| self.config.model.clone());
+        let stream = on_token.is_some();
+
+        let (url, body) = match self.config.api {
+            OllamaApi::Chat => (
+                format!("{}/api/chat", base_url),
+                serde_json::json!({ "model": model, "messages": request.messages, "stream": stream }),
+            ),
+            OllamaApi::Generate => {
+                let (system, prompt): (Vec<&Message>, Vec<&Message>) =
+                    request.messages.iter().partition(|m| m.role == "system");
+                let join = |messages: Vec<&Message>| {
+                    messages.iter().map(|m| m.content.as_str()).collect::<Vec<_>>().join("\n\n")
+                };
+                let mut body = serde_json::json!({ "model": model, "prompt": join(prompt), "stream": stream });
+                if !system.is_empty() {
+                    body["system"] = serde_json::Value::String(join(system));
+                }
+                (format!("{}/api/generate", base_url), body)
+            }
+        };
+
+        let mut response = self
+            .client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to reach `{}`, is Ollama running? {}", url, e))?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("Provider returned {}: {}", status, text.trim()));
+        }
+
+        let Some(on_token) = on_token else {
+            let response: OllamaResponse = response.json().await?;
+            if let Some(error) = response.error {
+                return Err(anyhow::anyhow!("Provider returned an error: {}", error));
+            }
+            return Ok(Completion {
+                content: response.text().to_string(),
+                usage: response.usage(),
+                model: response.model.unwrap_or(model),
+            });
+        };
+
+        // Streamed responses are newline-delimited JSON objects, the last one marked `done`.
+        let mut completion = Completion {
+            model,
+            content: String::new(),
+            usage: None,
+        };
+        let mut buffer: Vec<u8> = Vec::new();
+        'stream: while let Some(bytes) = response.chunk().await? {
+            buffer.extend_from_slice(&bytes);
+            while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+
+                let chunk: OllamaResponse = serde_json::from_str(line)
+                    .map_err(|e| anyhow::anyhow!("Invalid chunk from provider: {}: {}", e, line))?;
+                if let Some(error) = chunk.error {
+                    return Err(anyhow::anyhow!("Provider returned an error: {}", error));
+                }
+                let text = chunk.text();
+                if !text.is_empty() {
+                    on_token(text);
+                    completion.content.push_str(text);
+                }
+                if let Some(model) = &chunk.model {
+                    completion.model = model.clone();
+                }
+                if chunk.done {
+                    completion.usage = chunk.usage();
+                    break 'stream;
+                }
+            }
+        }
+        Ok(completion)
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::{Completion, CompletionRequest, Provider, Usage};
+
+/// Settings for an OpenAI-compatible `/chat/completions` endpoint.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct OpenAIConfig {
+    /// The API base URL, up to and including the version, e.g. `http://localhost:8080/v1`.
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+    /// The model to request unless one is given per request.
+    pub model: String,
+    /// The environment variable holding the API key. Requests are sent
+    /// without authentication when it is unset, for local servers.
+    #[serde(default = "default_api_key_env")]
+    pub api_key_env: String,
+}
+
+fn default_base_url() -> String {
+    "https://api.openai.com/v1".to_string()
+}
+
+fn default_api_key_env() -> String {
+    "OPENAI_API_KEY".to_string()
+}
+
+/// A client for OpenAI's chat completions API and servers compatible with it.
+pub struct OpenAIProvider {
+    config: OpenAIConfig,
+    client: reqwest::Client,
+}
+
+impl OpenAIProvider {
+    pub fn new(config: OpenAIConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    model: Option<String>,
+    choices: Vec<ChatChoice>,
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ResponseMessage,
+}
+
+/// The reply in a response; `content` is null when the model only calls tools or refuses.
+#[derive(Deserialize)]
+struct ResponseMessage {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatChunk {
+    model: Option<String>,
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    usage: Option<Usage>,
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct ChunkChoice {
+    delta: Delta,
+}
+
+#[derive(Deserialize)]
+struct Delta {
+    content: Option<String>,
+}
+
+#[async_trait]
+impl Provider for OpenAIProvider {
+    async fn complete(
+        &self,
+        request: &CompletionRequest,
+        on_token: Option<&mut (dyn for<'t> FnMut(&'t str) + Send)>,
+    ) -> Result<Completion> {
+        let url = format!("{}/chat/completions", self.config.base_url.trim_end_matches('/'));
+        let model = request.model.clone().unwrap_or_else(|| self.config.model.clone());
+
+        let mut body = serde_json::json!({
+            "model": model,
+            "messages": request.messages,
+            "stream": on_token.is_some(),
+        });
+        if on_token.is_some() {
+            body["stream_options"] = serde_json::json!({ "include_usage": true });
+        }
+
+        let mut builder = self.client.post(&url).json(&body);
+        if let Some(key) = std::env::var(&self.config.api_key_env).ok().filter(|k| !k.is_empty()) {
+            builder = builder.bearer_auth(key);
+        }
+        let mut response = builder
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to reach `{}`: {}", url, e))?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("Provider returned {}: {}", status, text.trim()));
+        }
+
+        let Some(on_token) = on_token else {
+            let response: ChatResponse = response.json().await?;
+            let content = response
+                .choices
+                .into_iter()
+                .next()
+                .map(|choice| choice.message.content.unwrap_or_default())
+                .ok_or_else(|| anyhow::anyhow!("Provider returned no choices"))?;
+            return Ok(Completion {
+                model: response.model.unwrap_or(model),
+                content,
+                usage: response.usage,
+            });
+        };
+
+        // Server-sent events: one `data: <json>` line per chunk, ending with `data: [DONE]`.
+        let mut completion = Completion {
+            model,
+            content: String::new(),
+            usage: None,
+        };
+        let mut buffer: Vec<u8> = Vec::new();
+        'stream: while let Some(bytes) = response.chunk().await? {
+            buffer.extend_from_slice(&bytes);
+            while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+                    continue;
+                };
+                if data == "[DONE]" {
+                    break 'stream;
+                }
+
+                let chunk: ChatChunk = serde_json::from_str(data)
+                    .map_err(|e| anyhow::anyhow!("Invalid chunk from provider: {}: {}", e, data))?;
+                if let Some(error) = chunk.error {
+                    return Err(anyhow::anyhow!("Provider returned an error: {}", error));
+                }
+                if let Some(model) = chunk.model {
+                    completion.model = model;
+                }
+                if chunk.usage.is_some() {
+                    completion.usage = chunk.usage;
+                }
+                for choice in chunk.choices {
+                    if let Some(text) = choice.delta.content.filter(|t| !t.is_empty()) {
+                        on_token(&text);
+                        completion.content.push_str(&text);
+                    }
+                }
+            }
+        }
+        Ok(completion)
+    }
+}
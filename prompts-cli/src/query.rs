@@ -0,0 +1,418 @@
+//! A small query language for finding prompts.
+//!
+//! A query is a list of terms that must all match:
+//!
+//! - `word` fuzzy-matches the prompt content, as does `content~word`.
+//! - `"exact phrase"` and `content:"exact phrase"` match a case-insensitive substring.
+//! - `tag:rust` and `category:review` match a tag or category exactly.
+//! - `-term` and `NOT term` exclude prompts matching `term`. A negated bare
+//!   word only excludes prompts containing that whole word, so `-go` keeps
+//!   "Review this algorithm".
+//! - `a OR b` matches either term and binds tighter than the implicit AND, so
+//!   `tag:rust OR tag:go -tag:draft` means `(tag:rust OR tag:go) AND NOT tag:draft`.
+//! - Parentheses group terms, and `AND` may be written explicitly.
+
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use std::fmt;
+
+use crate::storage::Prompt;
+
+/// A parsed search query.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Query {
+    /// Matches prompts matching every sub-query. An empty list matches everything.
+    And(Vec<Query>),
+    /// Matches prompts matching any sub-query.
+    Or(Vec<Query>),
+    /// Matches prompts not matching the sub-query.
+    Not(Box<Query>),
+    /// Matches prompts with the given tag.
+    Tag(String),
+    /// Matches prompts in the given category.
+    Category(String),
+    /// Matches prompts whose content contains the text, ignoring case.
+    Phrase(String),
+    /// Matches prompts whose content fuzzy-matches the text.
+    Fuzzy(String),
+}
+
+impl Default for Query {
+    fn default() -> Self {
+        Query::all()
+    }
+}
+
+/// How well a prompt matched a query.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct QueryMatch {
+    /// The sum of the fuzzy scores of the matched terms.
+    pub score: f64,
+    /// The character positions in the content that matched a content term.
+    pub indices: Vec<usize>,
+}
+
+/// An error in the syntax of a query, pointing at the offending token.
+#[derive(Debug, PartialEq, Clone)]
+pub struct QueryError {
+    /// What went wrong.
+    pub message: String,
+    /// The query that failed to parse.
+    pub query: String,
+    /// The byte offset of the offending token.
+    pub position: usize,
+    /// The byte length of the offending token.
+    pub len: usize,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let column = self.query[..self.position].chars().count();
+        let width = self.query[self.position..self.position + self.len].chars().count().max(1);
+        write!(
+            f,
+            "{} at column {}\n  {}\n  {}{}",
+            self.message,
+            column + 1,
+            self.query,
+            " ".repeat(column),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl Query {
+    /// A query matching every prompt.
+    pub fn all() -> Self {
+        Query::And(Vec::new())
+    }
+
+    /// Parses a query string. An empty string matches every prompt.
+    pub fn parse(input: &str) -> Result<Self, QueryError> {
+        let tokens = lex(input)?;
+        let mut parser = Parser { input, tokens, pos: 0 };
+        let query = parser.parse_and()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(parser.error_at(token, "unexpected `)`"));
+        }
+        Ok(query)
+    }
+
+    /// Combines this query with required tags and categories.
+    pub fn with_filters(self, tags: &[String], categories: &[String]) -> Self {
+        if tags.is_empty() && categories.is_empty() {
+            return self;
+        }
+        let mut terms = match self {
+            Query::And(terms) => terms,
+            other => vec![other],
+        };
+        terms.extend(tags.iter().cloned().map(Query::Tag));
+        terms.extend(categories.iter().cloned().map(Query::Category));
+        Query::And(terms)
+    }
+
+    /// Whether this query matches every prompt.
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Query::And(terms) if terms.is_empty())
+    }
+
+    /// Whether this query contains a fuzzy content term.
+    pub fn has_fuzzy_terms(&self) -> bool {
+        match self {
+            Query::And(terms) | Query::Or(terms) => terms.iter().any(Query::has_fuzzy_terms),
+            Query::Not(query) => query.has_fuzzy_terms(),
+            Query::Fuzzy(_) => true,
+            _ => false,
+        }
+    }
+
+    /// The content terms that count towards a match, excluding negated ones.
+    pub fn positive_content_terms(&self) -> Vec<&Query> {
+        match self {
+            Query::And(terms) | Query::Or(terms) => terms.iter().flat_map(Query::positive_content_terms).collect(),
+            Query::Phrase(_) | Query::Fuzzy(_) => vec![self],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Evaluates the query against a prompt, returning `None` if it does not match.
+    pub fn evaluate(&self, prompt: &Prompt) -> Option<QueryMatch> {
+        self.evaluate_with(prompt, &SkimMatcherV2::default(), false)
+    }
+
+    /// Evaluates the query, matching bare words as whole words rather than
+    /// fuzzily when `negated`, since a fuzzy match would exclude far too much.
+    fn evaluate_with(&self, prompt: &Prompt, matcher: &SkimMatcherV2, negated: bool) -> Option<QueryMatch> {
+        match self {
+            Query::And(terms) => {
+                let mut result = QueryMatch::default();
+                for term in terms {
+                    let term_match = term.evaluate_with(prompt, matcher, negated)?;
+                    result.score += term_match.score;
+                    result.indices.extend(term_match.indices);
+                }
+                result.indices.sort_unstable();
+                result.indices.dedup();
+                Some(result)
+            }
+            Query::Or(terms) => terms
+                .iter()
+                .filter_map(|term| term.evaluate_with(prompt, matcher, negated))
+                .max_by(|a, b| a.score.total_cmp(&b.score)),
+            Query::Not(query) => match query.evaluate_with(prompt, matcher, true) {
+                Some(_) => None,
+                None => Some(QueryMatch::default()),
+            },
+            Query::Tag(tag) => prompt
+                .tags
+                .as_ref()
+                .is_some_and(|tags| tags.contains(tag))
+                .then(QueryMatch::default),
+            Query::Category(category) => prompt
+                .categories
+                .as_ref()
+                .is_some_and(|categories| categories.contains(category))
+                .then(QueryMatch::default),
+            Query::Phrase(phrase) => {
+                let content = prompt.content.to_lowercase();
+                let start = content.find(&phrase.to_lowercase())?;
+                let first = content[..start].chars().count();
+                let indices = (first..first + phrase.chars().count()).collect();
+                Some(QueryMatch { score: 0.0, indices })
+            }
+            Query::Fuzzy(text) if negated => find_words(&prompt.content, text).then(QueryMatch::default),
+            Query::Fuzzy(text) => matcher
+                .fuzzy_indices(&prompt.content, text)
+                .map(|(score, indices)| QueryMatch { score: score as f64, indices }),
+        }
+    }
+}
+
+/// Whether `text` occurs in `content`, ignoring case, without letters or
+/// digits running on either side of it.
+fn find_words(content: &str, text: &str) -> bool {
+    let content = content.to_lowercase();
+    let text = text.to_lowercase();
+    if text.is_empty() {
+        return false;
+    }
+    content.match_indices(&text).any(|(start, _)| {
+        let before = content[..start].chars().next_back();
+        let after = content[start + text.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum TokenKind {
+    Term(Query),
+    LParen,
+    RParen,
+    Minus,
+    Or,
+    And,
+    Not,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+fn error(input: &str, message: impl Into<String>, start: usize, end: usize) -> QueryError {
+    QueryError {
+        message: message.into(),
+        query: input.to_string(),
+        position: start,
+        len: end - start,
+    }
+}
+
+/// Reads a quoted string starting at the opening quote, returning its
+/// contents and the offset just past the closing quote.
+fn read_quoted(input: &str, start: usize) -> Result<(String, usize), QueryError> {
+    let rest = &input[start + 1..];
+    match rest.find('"') {
+        Some(close) => Ok((rest[..close].to_string(), start + 1 + close + 1)),
+        None => Err(error(input, "unterminated quote", start, start + 1)),
+    }
+}
+
+fn lex(input: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < input.len() {
+        let c = input[pos..].chars().next().unwrap_or_default();
+        let start = pos;
+        if c.is_whitespace() {
+            pos += c.len_utf8();
+            continue;
+        }
+        let kind = match c {
+            '(' => {
+                pos += 1;
+                TokenKind::LParen
+            }
+            ')' => {
+                pos += 1;
+                TokenKind::RParen
+            }
+            '-' => {
+                pos += 1;
+                TokenKind::Minus
+            }
+            '"' => {
+                let (phrase, end) = read_quoted(input, start)?;
+                pos = end;
+                if phrase.trim().is_empty() {
+                    return Err(error(input, "empty phrase", start, end));
+                }
+                TokenKind::Term(Query::Phrase(phrase))
+            }
+            _ => {
+                let end = input[start..]
+                    .find(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == '"')
+                    .map_or(input.len(), |i| start + i);
+                let word = &input[start..end];
+                pos = end;
+                match word {
+                    "OR" => TokenKind::Or,
+                    "AND" => TokenKind::And,
+                    "NOT" => TokenKind::Not,
+                    _ => match word.find([':', '~']) {
+                        None => TokenKind::Term(Query::Fuzzy(word.to_string())),
+                        Some(split) => {
+                            let field = &word[..split];
+                            let fuzzy = word[split..].starts_with('~');
+                            let mut value = word[split + 1..].to_string();
+                            if value.is_empty() && input[end..].starts_with('"') {
+                                let (quoted, quoted_end) = read_quoted(input, end)?;
+                                value = quoted;
+                                pos = quoted_end;
+                            }
+                            if value.is_empty() {
+                                return Err(error(input, format!("expected a value after `{}`", &word[..=split]), start, pos));
+                            }
+                            let term = match (field, fuzzy) {
+                                ("tag", false) => Query::Tag(value),
+                                ("category", false) => Query::Category(value),
+                                ("content", false) => Query::Phrase(value),
+                                ("content", true) => Query::Fuzzy(value),
+                                ("tag" | "category", true) => {
+                                    return Err(error(input, format!("`{}` does not support fuzzy matching, use `{}:`", field, field), start, pos));
+                                }
+                                _ => {
+                                    return Err(error(
+                                        input,
+                                        format!("unknown field `{}`, expected `tag`, `category` or `content` (quote the term to search for it literally)", field),
+                                        start,
+                                        start + split,
+                                    ));
+                                }
+                            };
+                            TokenKind::Term(term)
+                        }
+                    },
+                }
+            }
+        };
+        tokens.push(Token { kind, start, end: pos });
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error_at(&self, token: &Token, message: &str) -> QueryError {
+        error(self.input, message, token.start, token.end)
+    }
+
+    fn error_at_end(&self, message: &str) -> QueryError {
+        let end = self.input.trim_end().len();
+        error(self.input, message, end, end)
+    }
+
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|t| &t.kind)
+    }
+
+    fn parse_and(&mut self) -> Result<Query, QueryError> {
+        let mut terms = Vec::new();
+        while let Some(kind) = self.peek() {
+            match kind {
+                TokenKind::RParen => break,
+                TokenKind::And if !terms.is_empty() => {
+                    let and = self.tokens[self.pos].clone();
+                    self.pos += 1;
+                    if matches!(self.peek(), None | Some(TokenKind::RParen)) {
+                        return Err(self.error_at(&and, "expected a term after `AND`"));
+                    }
+                }
+                _ => terms.push(self.parse_or()?),
+            }
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Query::And(terms) })
+    }
+
+    fn parse_or(&mut self) -> Result<Query, QueryError> {
+        let mut terms = vec![self.parse_unary()?];
+        while let Some(TokenKind::Or) = self.peek() {
+            let or = self.tokens[self.pos].clone();
+            self.pos += 1;
+            if matches!(self.peek(), None | Some(TokenKind::RParen) | Some(TokenKind::Or) | Some(TokenKind::And)) {
+                return Err(self.error_at(&or, "expected a term after `OR`"));
+            }
+            terms.push(self.parse_unary()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Query::Or(terms) })
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, QueryError> {
+        let Some(token) = self.tokens.get(self.pos).cloned() else {
+            return Err(self.error_at_end("expected a search term"));
+        };
+        match token.kind {
+            TokenKind::Minus | TokenKind::Not => {
+                self.pos += 1;
+                if matches!(self.peek(), None | Some(TokenKind::RParen) | Some(TokenKind::Or) | Some(TokenKind::And)) {
+                    let operator = if token.kind == TokenKind::Minus { "-" } else { "NOT" };
+                    return Err(self.error_at(&token, &format!("expected a term after `{}`", operator)));
+                }
+                Ok(Query::Not(Box::new(self.parse_unary()?)))
+            }
+            TokenKind::LParen => {
+                self.pos += 1;
+                let query = self.parse_and()?;
+                match self.peek() {
+                    Some(TokenKind::RParen) => {
+                        self.pos += 1;
+                        if query.is_empty() {
+                            return Err(self.error_at(&token, "empty group"));
+                        }
+                        Ok(query)
+                    }
+                    _ => Err(self.error_at(&token, "unclosed `(`")),
+                }
+            }
+            TokenKind::Term(query) => {
+                self.pos += 1;
+                Ok(query)
+            }
+            TokenKind::RParen => Err(self.error_at(&token, "unexpected `)`")),
+            TokenKind::Or => Err(self.error_at(&token, "unexpected `OR`")),
+            TokenKind::And => Err(self.error_at(&token, "unexpected `AND`")),
+        }
+    }
+}
@@ -3,14 +3,31 @@ use async_trait::async_trait;
 use dirs;
 use sha2::{Sha256, Digest};
 use serde::{Serialize, Deserialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use libsql::Connection;
-use libsql::Builder;
+use libsql::{Builder, Database, SyncProtocol};
 use std::fs;
+use chrono::{DateTime, SecondsFormat, Utc};
+use crate::chat;
+use crate::eval::TestCase;
+use crate::provider::{Message, Usage};
+use crate::query::Query;
 
 /// Represents a prompt with its content, metadata, and a unique hash.
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
 pub struct Prompt {
+    /// A stable identifier that survives content edits.
+    ///
+    /// New prompts use the hash of their first revision. Prompts saved before
+    /// identifiers existed fall back to their current hash when loaded.
+    #[serde(default)]
+    pub id: String,
+    /// An optional unique name to address the prompt by, e.g. `code-review`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Further unique names the prompt can be addressed by.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
     /// The text content of the prompt.
     pub content: String,
     /// Optional tags associated with the prompt.
@@ -19,6 +36,34 @@ pub struct Prompt {
     pub categories: Option<Vec<String>>,
     /// A unique SHA256 hash of the prompt's content, used for identification.
     pub hash: String,
+    /// Test cases checked by the `test` command.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tests: Vec<TestCase>,
+    /// When the prompt was added. Prompts saved before timestamps were
+    /// recorded have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the content, tags or other details of the prompt last changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Who added the prompt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// When the prompt was last rendered by `generate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// How many times the prompt has been rendered by `generate`.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub use_count: u64,
+    /// When the prompt was moved to the trash; `None` for a live prompt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// The messages of a chat prompt; empty for a plain text prompt.
+    ///
+    /// The content of a chat prompt is the transcript of its messages, so it
+    /// can be searched and shown like any other prompt.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<Message>,
 }
 
 impl Prompt {
@@ -26,14 +71,198 @@ impl Prompt {
     ///
     /// The `hash` is automatically generated from the content.
     pub fn new(content: &str, tags: Option<Vec<String>>, categories: Option<Vec<String>>) -> Self {
-        let hash = Sha256::digest(content.as_bytes());
+        let hash = hash_content(content);
         Self {
+            id: hash.clone(),
+            name: None,
+            aliases: Vec::new(),
             content: content.to_string(),
             tags,
             categories,
-            hash: format!("{:x}", hash),
+            hash,
+            tests: Vec::new(),
+            messages: Vec::new(),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a chat prompt from a list of messages.
+    ///
+    /// The `hash` is generated from the messages rather than the transcript.
+    pub fn chat(messages: Vec<Message>, tags: Option<Vec<String>>, categories: Option<Vec<String>>) -> Self {
+        let hash = chat::hash_messages(&messages);
+        Self {
+            id: hash.clone(),
+            name: None,
+            aliases: Vec::new(),
+            content: chat::transcript(&messages),
+            tags,
+            categories,
+            hash,
+            tests: Vec::new(),
+            messages,
+            ..Default::default()
+        }
+    }
+
+    /// Whether this is a chat prompt rather than plain text.
+    pub fn is_chat(&self) -> bool {
+        !self.messages.is_empty()
+    }
+
+    /// The name and aliases of the prompt.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.name.iter().chain(&self.aliases).map(String::as_str)
+    }
+
+    /// Replaces the content and updates the hash to match.
+    ///
+    /// The content of a chat prompt is parsed as a transcript.
+    pub fn set_content(&mut self, content: &str) -> Result<()> {
+        if self.is_chat() {
+            self.set_messages(chat::parse_transcript(content)?)?;
+        } else {
+            self.content = content.to_string();
+            self.hash = hash_content(content);
+        }
+        Ok(())
+    }
+
+    /// Replaces the messages of a chat prompt and updates the content and hash to match.
+    pub fn set_messages(&mut self, messages: Vec<Message>) -> Result<()> {
+        if !self.is_chat() {
+            return Err(anyhow::anyhow!("Prompt {} is not a chat prompt", &self.hash[..12.min(self.hash.len())]));
+        }
+        self.content = chat::transcript(&messages);
+        self.hash = chat::hash_messages(&messages);
+        self.messages = messages;
+        Ok(())
+    }
+}
+
+fn is_zero(count: &u64) -> bool {
+    *count == 0
+}
+
+/// A single entry in the append-only content history of a prompt.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Revision {
+    /// The 1-based position of this revision in the history.
+    pub number: usize,
+    /// The SHA256 hash of the content at this revision.
+    pub hash: String,
+    /// The text content at this revision.
+    pub content: String,
+    /// The messages at this revision, for a chat prompt.
+    ///
+    /// Reverting restores these as they were, since parsing the transcript
+    /// in `content` need not give them back.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<Message>,
+}
+
+impl Revision {
+    /// Records the current content of `prompt` as revision `number`.
+    pub fn new(number: usize, prompt: &Prompt) -> Self {
+        Self {
+            number,
+            hash: prompt.hash.clone(),
+            content: prompt.content.clone(),
+            messages: prompt.messages.clone(),
+        }
+    }
+}
+
+/// A recorded execution of a prompt against a provider.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Run {
+    /// A SHA256 hash identifying the run.
+    pub id: String,
+    /// The stable id of the prompt that was run.
+    pub prompt_id: String,
+    /// The hash of the prompt's content when it was run.
+    pub prompt_hash: String,
+    /// The rendered prompt sent to the provider.
+    pub input: String,
+    /// The template variables used to render the prompt.
+    pub variables: serde_json::Value,
+    /// The name of the provider, e.g. `openai`.
+    pub provider: String,
+    /// The model that produced the output.
+    pub model: String,
+    /// The text the model produced.
+    pub output: String,
+    /// How long the provider took to answer, in milliseconds.
+    pub latency_ms: u64,
+    /// Token counts, if the provider reported them.
+    pub usage: Option<Usage>,
+    /// When the run finished.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Computes the SHA256 hash used to identify prompt content.
+pub fn hash_content(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Checks that `name` can be used as a prompt name or alias.
+///
+/// Names are slugs: letters, digits, `-`, `_` and `.`, starting with a
+/// letter or digit, so they cannot be mistaken for query syntax.
+pub fn check_name(name: &str) -> Result<()> {
+    let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphanumeric())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Invalid prompt name `{}`, use letters, digits, `-`, `_` and `.`",
+            name
+        ))
+    }
+}
+
+/// Checks that the names of `prompt` are valid and not used by any of `others`.
+fn check_names_available(prompt: &Prompt, others: &[Prompt]) -> Result<()> {
+    let mut seen = std::collections::HashSet::new();
+    for name in prompt.names() {
+        check_name(name)?;
+        if !seen.insert(name) {
+            return Err(anyhow::anyhow!("Name `{}` is given more than once", name));
+        }
+        if let Some(other) = others.iter().find(|p| p.hash != prompt.hash && p.names().any(|n| n == name)) {
+            return Err(name_taken(name, &other.hash));
         }
     }
+    Ok(())
+}
+
+/// The error for a name already used by the prompt with the given hash.
+pub(crate) fn name_taken(name: &str, hash: &str) -> anyhow::Error {
+    anyhow::anyhow!("Name `{}` is already used by prompt {}", name, &hash[..12.min(hash.len())])
+}
+
+/// A search to be run natively by a storage backend.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct StorageQuery {
+    /// The prompts to match.
+    pub filter: Query,
+    /// The maximum number of results to return.
+    pub limit: Option<usize>,
+}
+
+/// A prompt matched by a search, together with how well it matched.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchResult {
+    /// The matched prompt.
+    pub prompt: Prompt,
+    /// The relevance score; higher is more relevant. Zero when no query was given.
+    ///
+    /// This is the fuzzy match score for in-memory searches and the negated
+    /// BM25 rank for backends with a full-text index.
+    pub score: f64,
+    /// The character positions in the prompt's content that matched the query.
+    pub indices: Vec<usize>,
 }
 
 /// A trait defining the interface for prompt storage.
@@ -44,7 +273,80 @@ pub trait Storage {
     /// Loads all prompts from the storage.
     async fn load_prompts(&self) -> Result<Vec<Prompt>>;
     /// Deletes a prompt from the storage by its hash.
+    ///
+    /// The prompt is removed for good; see `trash_prompt` for a delete that
+    /// can be undone.
     async fn delete_prompt(&self, hash: &str) -> Result<()>;
+    /// Replaces the prompt with hash `old_hash` by `prompt`, as one change.
+    async fn replace_prompt(&self, old_hash: &str, prompt: &mut Prompt) -> Result<()> {
+        self.delete_prompt(old_hash).await?;
+        self.save_prompt(prompt).await
+    }
+    /// Moves the prompt with the given hash to the trash, recording `at` as
+    /// its deletion time. Trashed prompts are left out of every other lookup.
+    async fn trash_prompt(&self, hash: &str, at: DateTime<Utc>) -> Result<()>;
+    /// Loads the prompts in the trash.
+    async fn load_trash(&self) -> Result<Vec<Prompt>>;
+    /// Saves a prompt straight into the trash, as when copying a trash from
+    /// another storage. It keeps its names, which live prompts may be using,
+    /// and its deletion time, or the current time if it has none.
+    async fn save_trashed(&self, prompt: &Prompt) -> Result<()>;
+    /// Moves the prompt with the given hash out of the trash.
+    async fn restore_prompt(&self, hash: &str) -> Result<()>;
+    /// Deletes the prompt with the given hash from the trash for good.
+    async fn purge_prompt(&self, hash: &str) -> Result<()>;
+    /// Appends a revision to the history of the prompt with the given id.
+    async fn save_revision(&self, id: &str, revision: &Revision) -> Result<()>;
+    /// Loads the revision history of the prompt with the given id, oldest first.
+    async fn load_revisions(&self, id: &str) -> Result<Vec<Revision>>;
+    /// Records a run of a prompt.
+    async fn save_run(&self, run: &Run) -> Result<()>;
+    /// Loads the recorded runs, optionally only those of the prompt with the
+    /// given id, oldest first.
+    async fn load_runs(&self, prompt_id: Option<&str>) -> Result<Vec<Run>>;
+    /// Finds the prompt with the given name or alias.
+    async fn find_by_name(&self, name: &str) -> Result<Option<Prompt>> {
+        let prompts = self.load_prompts().await?;
+        Ok(prompts.into_iter().find(|p| p.names().any(|n| n == name)))
+    }
+    /// Records that the prompt with the given hash was used at `at`.
+    async fn record_use(&self, hash: &str, at: DateTime<Utc>) -> Result<()> {
+        let prompts = self.load_prompts().await?;
+        if let Some(mut prompt) = prompts.into_iter().find(|p| p.hash == hash) {
+            prompt.last_used_at = Some(at);
+            prompt.use_count += 1;
+            self.replace_prompt(hash, &mut prompt).await?;
+        }
+        Ok(())
+    }
+    /// Loads the prompts whose hash or id starts with `prefix`, a lowercase hex
+    /// string.
+    async fn find_by_hash_prefix(&self, prefix: &str) -> Result<Vec<Prompt>> {
+        let prompts = self.load_prompts().await?;
+        Ok(prompts.into_iter().filter(|p| p.hash.starts_with(prefix) || p.id.starts_with(prefix)).collect())
+    }
+    /// Runs a search inside the storage, best match first.
+    ///
+    /// Backends without native search return `None`, and callers filter the
+    /// output of `load_prompts` in memory instead.
+    async fn query_prompts(&self, _query: &StorageQuery) -> Result<Option<Vec<SearchResult>>> {
+        Ok(None)
+    }
+    /// Brings a local copy of a shared library up to date with its server.
+    async fn sync(&self) -> Result<SyncReport> {
+        Err(anyhow::anyhow!(
+            "This storage has no server to sync with; set storage.type to `libsql-replica` and storage.sync_url to use one"
+        ))
+    }
+}
+
+/// What a sync pulled from the server.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize)]
+pub struct SyncReport {
+    /// The number of frames, changed database pages, pulled from the server.
+    pub frames_synced: usize,
+    /// The server's last committed frame, if it has any.
+    pub frame_no: Option<u64>,
 }
 
 /// A storage implementation that uses JSON files.
@@ -79,15 +381,99 @@ impl JsonStorage {
         };
         Ok(Self { storage_path: path })
     }
+
+    /// Returns the directory holding the prompt files.
+    pub fn path(&self) -> &Path {
+        &self.storage_path
+    }
+
+    fn revisions_path(&self, id: &str) -> PathBuf {
+        self.storage_path.join("revisions").join(format!("{}.json", id))
+    }
+
+    fn runs_dir(&self) -> PathBuf {
+        self.storage_path.join("runs")
+    }
+
+    fn trash_dir(&self) -> PathBuf {
+        self.storage_path.join(".trash")
+    }
+
+    /// Lists the prompt files, live and in the trash, written in an older
+    /// format, and rewrites them in the current one unless `dry_run` is set.
+    pub async fn upgrade_files(&self, dry_run: bool) -> Result<Vec<PathBuf>> {
+        let mut upgraded = Vec::new();
+        for dir in [self.storage_path.clone(), self.trash_dir()] {
+            if !dir.exists() {
+                continue;
+            }
+            let mut read_dir = tokio::fs::read_dir(&dir).await?;
+            while let Some(entry) = read_dir.next_entry().await? {
+                let path = entry.path();
+                if !path.is_file() || path.extension().is_none_or(|ext| ext != "json") {
+                    continue;
+                }
+                let (prompt, format) = read_prompt_file(&path).await?;
+                if format < JSON_FORMAT_VERSION {
+                    if !dry_run {
+                        tokio::fs::write(&path, prompt_file_json(&prompt)?).await?;
+                    }
+                    upgraded.push(path);
+                }
+            }
+        }
+        upgraded.sort();
+        Ok(upgraded)
+    }
+}
+
+/// The format of the prompt files written by `JsonStorage`, recorded in each
+/// file as `format`. Files without it are format 1.
+pub const JSON_FORMAT_VERSION: u32 = 2;
+
+/// Reads a prompt file, upgrading an older format as it goes, and returns
+/// the prompt with the format the file was written in.
+async fn read_prompt_file(path: &Path) -> Result<(Prompt, u32)> {
+    let mut value: serde_json::Value = serde_json::from_str(&tokio::fs::read_to_string(path).await?)?;
+    if !value.is_object() {
+        return Err(anyhow::anyhow!("{} does not hold a prompt", path.display()));
+    }
+    let format = value.get("format").and_then(|f| f.as_u64()).unwrap_or(1) as u32;
+    if format > JSON_FORMAT_VERSION {
+        return Err(anyhow::anyhow!(
+            "{} has format {}, but this version of prompts-cli only reads up to {}",
+            path.display(),
+            format,
+            JSON_FORMAT_VERSION
+        ));
+    }
+    // Format 2: prompts saved before ids existed take their hash as their id.
+    if format < 2 {
+        let hash = value.get("hash").cloned().unwrap_or_default();
+        if value.get("id").and_then(|id| id.as_str()).is_none_or(str::is_empty) {
+            value["id"] = hash;
+        }
+    }
+    Ok((serde_json::from_value(value)?, format))
+}
+
+/// Serializes a prompt for a prompt file, marked with the current format.
+fn prompt_file_json(prompt: &Prompt) -> Result<String> {
+    let mut value = serde_json::to_value(prompt)?;
+    value["format"] = JSON_FORMAT_VERSION.into();
+    Ok(serde_json::to_string_pretty(&value)?)
 }
 
 #[async_trait]
 impl Storage for JsonStorage {
     async fn save_prompt(&self, prompt: &mut Prompt) -> Result<()> {
+        if prompt.names().next().is_some() {
+            check_names_available(prompt, &self.load_prompts().await?)?;
+        }
         let file_path = self.storage_path.join(format!("{}.json", prompt.hash));
-        let json = serde_json::to_string_pretty(prompt)?;
-        tokio::fs::write(file_path, json).await?;
-        Ok(())
+        tokio::fs::write(file_path, prompt_file_json(prompt)?).await?;
+        // Saving a prompt again replaces any copy of it in the trash.
+        self.purge_prompt(&prompt.hash).await
     }
 
     async fn load_prompts(&self) -> Result<Vec<Prompt>> {
@@ -95,10 +481,8 @@ impl Storage for JsonStorage {
         let mut read_dir = tokio::fs::read_dir(&self.storage_path).await?;
         while let Some(entry) = read_dir.next_entry().await? {
             let path = entry.path();
-            if path.is_file() && path.extension().map_or(false, |ext| ext == "json") {
-                let json = tokio::fs::read_to_string(&path).await?;
-                let prompt: Prompt = serde_json::from_str(&json)?;
-                prompts.push(prompt);
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+                prompts.push(read_prompt_file(&path).await?.0);
             }
         }
         Ok(prompts)
@@ -111,13 +495,138 @@ impl Storage for JsonStorage {
         }
         Ok(())
     }
+
+    async fn trash_prompt(&self, hash: &str, at: DateTime<Utc>) -> Result<()> {
+        let file_path = self.storage_path.join(format!("{}.json", hash));
+        if !file_path.exists() {
+            return Ok(());
+        }
+        let (mut prompt, _) = read_prompt_file(&file_path).await?;
+        prompt.deleted_at = Some(at);
+        tokio::fs::create_dir_all(self.trash_dir()).await?;
+        tokio::fs::write(self.trash_dir().join(format!("{}.json", hash)), prompt_file_json(&prompt)?).await?;
+        tokio::fs::remove_file(file_path).await?;
+        Ok(())
+    }
+
+    async fn load_trash(&self) -> Result<Vec<Prompt>> {
+        let trash_dir = self.trash_dir();
+        if !trash_dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut prompts = Vec::new();
+        let mut read_dir = tokio::fs::read_dir(trash_dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let path = entry.path();
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+                prompts.push(read_prompt_file(&path).await?.0);
+            }
+        }
+        Ok(prompts)
+    }
+
+    async fn save_trashed(&self, prompt: &Prompt) -> Result<()> {
+        let prompt = Prompt { deleted_at: Some(prompt.deleted_at.unwrap_or_else(Utc::now)), ..prompt.clone() };
+        tokio::fs::create_dir_all(self.trash_dir()).await?;
+        tokio::fs::write(self.trash_dir().join(format!("{}.json", prompt.hash)), prompt_file_json(&prompt)?).await?;
+        Ok(())
+    }
+
+    async fn restore_prompt(&self, hash: &str) -> Result<()> {
+        let trash_path = self.trash_dir().join(format!("{}.json", hash));
+        if !trash_path.exists() {
+            return Ok(());
+        }
+        let (mut prompt, _) = read_prompt_file(&trash_path).await?;
+        prompt.deleted_at = None;
+        self.save_prompt(&mut prompt).await
+    }
+
+    async fn purge_prompt(&self, hash: &str) -> Result<()> {
+        let file_path = self.trash_dir().join(format!("{}.json", hash));
+        if file_path.exists() {
+            tokio::fs::remove_file(file_path).await?;
+        }
+        Ok(())
+    }
+
+    async fn save_revision(&self, id: &str, revision: &Revision) -> Result<()> {
+        let mut revisions = self.load_revisions(id).await?;
+        revisions.push(revision.clone());
+        let file_path = self.revisions_path(id);
+        if let Some(parent) = file_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let json = serde_json::to_string_pretty(&revisions)?;
+        tokio::fs::write(file_path, json).await?;
+        Ok(())
+    }
+
+    async fn load_revisions(&self, id: &str) -> Result<Vec<Revision>> {
+        let file_path = self.revisions_path(id);
+        if !file_path.exists() {
+            return Ok(Vec::new());
+        }
+        let json = tokio::fs::read_to_string(file_path).await?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    async fn save_run(&self, run: &Run) -> Result<()> {
+        let runs_dir = self.runs_dir();
+        tokio::fs::create_dir_all(&runs_dir).await?;
+        let json = serde_json::to_string_pretty(run)?;
+        tokio::fs::write(runs_dir.join(format!("{}.json", run.id)), json).await?;
+        Ok(())
+    }
+
+    async fn load_runs(&self, prompt_id: Option<&str>) -> Result<Vec<Run>> {
+        let runs_dir = self.runs_dir();
+        if !runs_dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut runs = Vec::new();
+        let mut read_dir = tokio::fs::read_dir(runs_dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let path = entry.path();
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+                let json = tokio::fs::read_to_string(&path).await?;
+                let run: Run = serde_json::from_str(&json)?;
+                if prompt_id.is_none_or(|id| run.prompt_id == id) {
+                    runs.push(run);
+                }
+            }
+        }
+        runs.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(runs)
+    }
 }
 
 /// A storage implementation that uses a LibSQL database.
 ///
-/// All prompts are stored in a single database file.
+/// All prompts are stored in a single database file, which can also be an
+/// embedded replica of a database shared through a sqld server.
 pub struct LibSQLStorage {
     conn: Connection,
+    replica: Option<Replica>,
+}
+
+/// Where an embedded replica syncs from, for the `libsql-replica` storage type.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplicaConfig {
+    /// The URL of the sqld server holding the shared database.
+    pub sync_url: String,
+    /// The token to authenticate with, empty if the server needs none.
+    pub auth_token: String,
+    /// Whether to sync after every change, so that the replica also picks up
+    /// other people's changes, rather than only when it is opened.
+    pub sync_on_write: bool,
+}
+
+struct Replica {
+    db: Database,
+    config: ReplicaConfig,
+    /// Why the server could not be reached when the replica was opened.
+    offline: Option<String>,
 }
 
 impl LibSQLStorage {
@@ -126,76 +635,766 @@ impl LibSQLStorage {
     /// If `storage_path` is `None`, a default database file is used.
     /// This will also create the necessary tables if they don't exist.
     pub async fn new(storage_path: Option<PathBuf>) -> Result<Self> {
-        let db_path = match storage_path {
-            Some(path) => path,
-            None => {
-                let mut path = get_default_storage_dir()?;
-                fs::create_dir_all(&path)?;
-                path.push("prompts.db");
-                path
+        let db_path = database_path(storage_path, "prompts.db")?;
+        // Only a database that held data before it was opened needs backing up.
+        let existed = db_path.metadata().is_ok_and(|m| m.len() > 0);
+        let db = Builder::new_local(db_path.to_str().unwrap()).build().await?;
+        let conn = db.connect()?;
+        migrate_schema(&conn, existed.then_some(db_path.as_path())).await?;
+        Ok(Self { conn, replica: None })
+    }
+
+    /// Opens an embedded replica of a shared database, syncing it with the
+    /// server first.
+    ///
+    /// If `storage_path` is `None`, a default replica file is used. Writes go
+    /// to the server and reads are served from the replica. When the server
+    /// cannot be reached, a replica that was synced before still opens, but
+    /// only for reading; see `offline`.
+    pub async fn new_replica(storage_path: Option<PathBuf>, config: ReplicaConfig) -> Result<Self> {
+        let db_path = Self::replica_path(storage_path)?;
+        // Version 1 of the sync protocol keeps the replica readable offline.
+        let db = Builder::new_remote_replica(&db_path, config.sync_url.clone(), config.auth_token.clone())
+            .sync_protocol(SyncProtocol::V1)
+            .build()
+            .await?;
+        let conn = db.connect()?;
+        let offline = db.sync().await.err().map(|e| e.to_string());
+        match &offline {
+            // Migrations are writes, so they run on the server for everyone.
+            None => migrate_schema(&conn, None).await?,
+            Some(reason) => {
+                if schema_version(&conn).await? != SCHEMA_VERSION {
+                    return Err(anyhow::anyhow!(
+                        "Cannot reach the sync server at {} to set up the replica: {}",
+                        config.sync_url,
+                        reason
+                    ));
+                }
             }
-        };
+        }
+        Ok(Self { conn, replica: Some(Replica { db, config, offline }) })
+    }
+
+    /// Returns the replica file to open, the default one if no path is given.
+    pub fn replica_path(storage_path: Option<PathBuf>) -> Result<PathBuf> {
+        database_path(storage_path, "replica.db")
+    }
+
+    /// Returns why the server of a replica could not be reached when it was
+    /// opened, or `None` if it was reached or the database is not a replica.
+    pub fn offline(&self) -> Option<&str> {
+        self.replica.as_ref().and_then(|r| r.offline.as_deref())
+    }
 
+    /// Fails when the replica is offline, as every change has to go through
+    /// the server.
+    fn check_writable(&self) -> Result<()> {
+        match &self.replica {
+            Some(Replica { config, offline: Some(reason), .. }) => Err(anyhow::anyhow!(
+                "Cannot change the prompt library while the sync server at {} is unreachable ({}). \
+                 Reading still works from the local replica.",
+                config.sync_url,
+                reason
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Syncs a replica after a change if it is configured to.
+    async fn after_write(&self) -> Result<()> {
+        if let Some(replica) = self.replica.as_ref().filter(|r| r.config.sync_on_write) {
+            replica.db.sync().await?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the trashed copy of a prompt, if there is one.
+    /// Inserts a row for `prompt`, in the trash if `deleted_at` is set. Its
+    /// names are stored with it but not claimed in `prompt_names`.
+    async fn insert_prompt(&self, prompt: &Prompt, deleted_at: Option<DateTime<Utc>>) -> Result<()> {
+        let tags = serde_json::to_string(&prompt.tags.as_deref().unwrap_or_default())?;
+        let categories = serde_json::to_string(&prompt.categories.as_deref().unwrap_or_default())?;
+
+        let tests = (!prompt.tests.is_empty()).then(|| serde_json::to_string(&prompt.tests)).transpose()?;
+        let messages = prompt.is_chat().then(|| serde_json::to_string(&prompt.messages)).transpose()?;
+        let aliases = (!prompt.aliases.is_empty()).then(|| serde_json::to_string(&prompt.aliases)).transpose()?;
+
+        self.conn.execute(
+            "INSERT INTO prompts (hash, content, tags, categories, id, tests, messages, name, aliases,
+                created_at, updated_at, author, last_used_at, use_count, deleted_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            libsql::params![
+                prompt.hash.clone(),
+                prompt.content.clone(),
+                tags,
+                categories,
+                prompt.id.clone(),
+                tests,
+                messages,
+                prompt.name.clone(),
+                aliases,
+                format_timestamp(prompt.created_at),
+                format_timestamp(prompt.updated_at),
+                prompt.author.clone(),
+                format_timestamp(prompt.last_used_at),
+                prompt.use_count as i64,
+                format_timestamp(deleted_at),
+            ],
+        ).await?;
+        Ok(())
+    }
+
+    async fn purge_trashed(&self, hash: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM prompts WHERE hash = ?1 AND deleted_at IS NOT NULL",
+            libsql::params![hash],
+        ).await?;
+        Ok(())
+    }
+
+    /// Lists the schema migrations a database still needs, oldest first,
+    /// without applying them. `storage_path` is as for `new`.
+    pub async fn pending_migrations(storage_path: Option<PathBuf>) -> Result<Vec<&'static SchemaMigration>> {
+        let db_path = database_path(storage_path, "prompts.db")?;
+        if !db_path.exists() {
+            return Ok(SCHEMA_MIGRATIONS.iter().collect());
+        }
         let db = Builder::new_local(db_path.to_str().unwrap()).build().await?;
-        let conn = db.connect()?;
+        let version = schema_version(&db.connect()?).await?;
+        Ok(SCHEMA_MIGRATIONS.iter().filter(|m| m.version > version).collect())
+    }
+}
+
+/// Returns the database file to open, `file_name` in the default storage
+/// directory if no path is given.
+fn database_path(storage_path: Option<PathBuf>, file_name: &str) -> Result<PathBuf> {
+    Ok(match storage_path {
+        Some(path) => path,
+        None => {
+            let mut path = get_default_storage_dir()?;
+            fs::create_dir_all(&path)?;
+            path.push(file_name);
+            path
+        }
+    })
+}
+
+/// One change to the LibSQL schema, applied in order when a database is opened.
+#[derive(Debug)]
+pub struct SchemaMigration {
+    /// The schema version the migration brings the database to.
+    pub version: u32,
+    /// What the migration does, for `db migrate`.
+    pub description: &'static str,
+    /// Whether the migration rewrites or removes existing data, so that the
+    /// database is backed up before it runs.
+    pub destructive: bool,
+    steps: &'static [MigrationStep],
+}
+
+#[derive(Debug)]
+enum MigrationStep {
+    /// Statements run as they are; they must be safe to run on a database
+    /// created before schema versions were recorded.
+    Sql(&'static str),
+    /// Adds a column unless the table already has it.
+    AddColumn { table: &'static str, column: &'static str, definition: &'static str },
+}
 
-        conn.execute(
+/// The schema version of a database with every migration applied.
+pub const SCHEMA_VERSION: u32 = 9;
+
+/// The schema migrations, oldest first.
+///
+/// Databases created before versions were recorded are at version 0 and run
+/// every migration, so each step tolerates finding its change already made.
+pub const SCHEMA_MIGRATIONS: &[SchemaMigration] = &[
+    SchemaMigration {
+        version: 1,
+        description: "Create the prompts, revisions and runs tables",
+        destructive: false,
+        steps: &[MigrationStep::Sql(
             "CREATE TABLE IF NOT EXISTS prompts (
                 hash TEXT PRIMARY KEY,
                 content TEXT NOT NULL,
                 tags TEXT,
                 categories TEXT
-            )",
-            (),
+            );
+            CREATE TABLE IF NOT EXISTS revisions (
+                prompt_id TEXT NOT NULL,
+                number INTEGER NOT NULL,
+                hash TEXT NOT NULL,
+                content TEXT NOT NULL,
+                PRIMARY KEY (prompt_id, number)
+            );
+            CREATE TABLE IF NOT EXISTS runs (
+                id TEXT PRIMARY KEY,
+                prompt_id TEXT NOT NULL,
+                prompt_hash TEXT NOT NULL,
+                input TEXT NOT NULL,
+                variables TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                output TEXT NOT NULL,
+                latency_ms INTEGER NOT NULL,
+                prompt_tokens INTEGER,
+                completion_tokens INTEGER,
+                total_tokens INTEGER,
+                created_at TEXT NOT NULL
+            );",
+        )],
+    },
+    SchemaMigration {
+        version: 2,
+        description: "Add prompt ids and test cases",
+        destructive: false,
+        steps: &[
+            MigrationStep::AddColumn { table: "prompts", column: "id", definition: "TEXT" },
+            MigrationStep::AddColumn { table: "prompts", column: "tests", definition: "TEXT" },
+        ],
+    },
+    SchemaMigration {
+        version: 3,
+        description: "Fill in the ids of prompts saved before ids existed",
+        destructive: true,
+        steps: &[MigrationStep::Sql("UPDATE prompts SET id = hash WHERE id IS NULL OR id = '';")],
+    },
+    SchemaMigration {
+        version: 4,
+        description: "Index prompt content for full-text search",
+        destructive: false,
+        steps: &[MigrationStep::Sql(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS prompts_fts USING fts5(
+                content,
+                content = 'prompts',
+                content_rowid = 'rowid'
+            );
+            CREATE TRIGGER IF NOT EXISTS prompts_fts_insert AFTER INSERT ON prompts BEGIN
+                INSERT INTO prompts_fts (rowid, content) VALUES (new.rowid, new.content);
+            END;
+            CREATE TRIGGER IF NOT EXISTS prompts_fts_delete AFTER DELETE ON prompts BEGIN
+                INSERT INTO prompts_fts (prompts_fts, rowid, content) VALUES ('delete', old.rowid, old.content);
+            END;
+            CREATE TRIGGER IF NOT EXISTS prompts_fts_update AFTER UPDATE ON prompts BEGIN
+                INSERT INTO prompts_fts (prompts_fts, rowid, content) VALUES ('delete', old.rowid, old.content);
+                INSERT INTO prompts_fts (rowid, content) VALUES (new.rowid, new.content);
+            END;
+            INSERT INTO prompts_fts (prompts_fts) VALUES ('rebuild');",
+        )],
+    },
+    SchemaMigration {
+        version: 5,
+        description: "Add chat messages",
+        destructive: false,
+        steps: &[MigrationStep::AddColumn { table: "prompts", column: "messages", definition: "TEXT" }],
+    },
+    SchemaMigration {
+        version: 6,
+        description: "Add prompt names and aliases",
+        destructive: false,
+        steps: &[
+            MigrationStep::AddColumn { table: "prompts", column: "name", definition: "TEXT" },
+            MigrationStep::AddColumn { table: "prompts", column: "aliases", definition: "TEXT" },
+            // Names and aliases share one key, so no name can refer to two prompts.
+            MigrationStep::Sql(
+                "CREATE TABLE IF NOT EXISTS prompt_names (
+                    name TEXT PRIMARY KEY,
+                    hash TEXT NOT NULL
+                );",
+            ),
+        ],
+    },
+    SchemaMigration {
+        version: 7,
+        description: "Add timestamps, authors and usage counts",
+        destructive: false,
+        steps: &[
+            MigrationStep::AddColumn { table: "prompts", column: "created_at", definition: "TEXT" },
+            MigrationStep::AddColumn { table: "prompts", column: "updated_at", definition: "TEXT" },
+            MigrationStep::AddColumn { table: "prompts", column: "author", definition: "TEXT" },
+            MigrationStep::AddColumn { table: "prompts", column: "last_used_at", definition: "TEXT" },
+            MigrationStep::AddColumn { table: "prompts", column: "use_count", definition: "INTEGER NOT NULL DEFAULT 0" },
+        ],
+    },
+    SchemaMigration {
+        version: 8,
+        description: "Add the trash",
+        destructive: false,
+        steps: &[MigrationStep::AddColumn { table: "prompts", column: "deleted_at", definition: "TEXT" }],
+    },
+    SchemaMigration {
+        version: 9,
+        description: "Add the messages of chat revisions",
+        destructive: false,
+        steps: &[MigrationStep::AddColumn { table: "revisions", column: "messages", definition: "TEXT" }],
+    },
+];
+
+/// Returns the schema version recorded in a database, 0 if it has none.
+async fn schema_version(conn: &Connection) -> Result<u32> {
+    let mut rows = conn.query(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'schema_version'",
+        (),
+    ).await?;
+    if rows.next().await?.is_none() {
+        return Ok(0);
+    }
+    let mut rows = conn.query("SELECT coalesce(max(version), 0) FROM schema_version", ()).await?;
+    let version: i64 = match rows.next().await? {
+        Some(row) => row.get(0)?,
+        None => 0,
+    };
+    Ok(version as u32)
+}
+
+/// Applies the migrations a database has not had yet, each in a transaction.
+///
+/// Before the first destructive one, the database at `backup_path` is copied
+/// next to it as `<file>.v<version>.bak`.
+async fn migrate_schema(conn: &Connection, backup_path: Option<&Path>) -> Result<()> {
+    let version = schema_version(conn).await?;
+    if version > SCHEMA_VERSION {
+        return Err(anyhow::anyhow!(
+            "The database has schema version {}, but this version of prompts-cli only knows up to {}",
+            version,
+            SCHEMA_VERSION
+        ));
+    }
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        )",
+        (),
+    ).await?;
+
+    let mut backed_up = false;
+    for migration in SCHEMA_MIGRATIONS.iter().filter(|m| m.version > version) {
+        if migration.destructive && !backed_up {
+            if let Some(path) = backup_path {
+                let mut backup = path.as_os_str().to_owned();
+                backup.push(format!(".v{}.bak", version));
+                conn.execute("VACUUM INTO ?1", libsql::params![backup.to_string_lossy().to_string()]).await?;
+            }
+            backed_up = true;
+        }
+        let tx = conn.transaction().await?;
+        for step in migration.steps {
+            match step {
+                MigrationStep::Sql(sql) => tx.execute_batch(sql).await.map(|_| ())?,
+                MigrationStep::AddColumn { table, column, definition } => ensure_column(&tx, table, column, definition).await?,
+            }
+        }
+        tx.execute(
+            "INSERT INTO schema_version (version, description, applied_at) VALUES (?1, ?2, ?3)",
+            libsql::params![migration.version as i64, migration.description, format_timestamp(Some(Utc::now()))],
         ).await?;
+        tx.commit().await?;
+    }
+    Ok(())
+}
+
+/// Adds a column to a table created before the column existed.
+async fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let mut rows = conn.query(&format!("SELECT name FROM pragma_table_info('{}')", table), ()).await?;
+    while let Some(row) = rows.next().await? {
+        let name: String = row.get(0)?;
+        if name == column {
+            return Ok(());
+        }
+    }
+    conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), ()).await?;
+    Ok(())
+}
 
-        Ok(Self { conn })
+/// Converts a content term into an FTS5 query, matching fuzzy words by prefix.
+fn fts_match_expression(term: &Query) -> Option<String> {
+    let expression = match term {
+        Query::Phrase(phrase) => format!("\"{}\"", phrase.replace('"', "")),
+        Query::Fuzzy(word) => format!("\"{}\"*", word.replace('"', "")),
+        _ => return None,
+    };
+    (expression.trim_matches(|c| c == '"' || c == '*').chars().any(char::is_alphanumeric)).then_some(expression)
+}
+
+/// Translates a query into an SQL condition on the `prompts` table aliased as `p`.
+fn query_to_sql(query: &Query, params: &mut Vec<libsql::Value>) -> String {
+    let mut param = |value: &str| {
+        params.push(value.to_string().into());
+        format!("?{}", params.len())
+    };
+    match query {
+        Query::And(terms) if terms.is_empty() => "1 = 1".to_string(),
+        Query::Or(terms) if terms.is_empty() => "1 = 0".to_string(),
+        Query::And(terms) | Query::Or(terms) => {
+            let separator = if matches!(query, Query::And(_)) { " AND " } else { " OR " };
+            let conditions: Vec<String> = terms.iter().map(|term| query_to_sql(term, params)).collect();
+            format!("({})", conditions.join(separator))
+        }
+        Query::Not(query) => format!("NOT {}", query_to_sql(query, params)),
+        Query::Tag(tag) => format!("EXISTS (SELECT 1 FROM json_each(p.tags) WHERE value = {})", param(tag)),
+        Query::Category(category) => format!("EXISTS (SELECT 1 FROM json_each(p.categories) WHERE value = {})", param(category)),
+        Query::Phrase(phrase) => format!("(instr(lower(p.content), lower({})) > 0)", param(phrase)),
+        Query::Fuzzy(_) => match fts_match_expression(query) {
+            Some(expression) => format!("p.rowid IN (SELECT rowid FROM prompts_fts WHERE prompts_fts MATCH {})", param(&expression)),
+            // Searches run queries with bare words in memory; here they only
+            // match the words the index holds.
+            None => "1 = 0".to_string(),
+        },
     }
 }
 
+/// The columns `row_to_prompt` reads, in order, from the `prompts` table aliased as `p`.
+const PROMPT_COLUMNS: &str = "p.hash, p.content, p.tags, p.categories, p.id, p.tests, p.messages, p.name, p.aliases, \
+    p.created_at, p.updated_at, p.author, p.last_used_at, p.use_count, p.deleted_at";
+
+/// The number of columns in `PROMPT_COLUMNS`.
+const PROMPT_COLUMN_COUNT: i32 = 15;
+
+fn format_timestamp(timestamp: Option<DateTime<Utc>>) -> Option<String> {
+    timestamp.map(|t| t.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
+fn parse_timestamp(timestamp: Option<String>) -> Result<Option<DateTime<Utc>>> {
+    Ok(match timestamp {
+        Some(timestamp) => Some(DateTime::parse_from_rfc3339(&timestamp)?.with_timezone(&Utc)),
+        None => None,
+    })
+}
+
+fn row_to_prompt(row: &libsql::Row) -> Result<Prompt> {
+    let hash: String = row.get(0)?;
+    let content: String = row.get(1)?;
+    let tags_str: Option<String> = row.get(2)?;
+    let categories_str: Option<String> = row.get(3)?;
+    let id: Option<String> = row.get(4)?;
+    let tests: Option<String> = row.get(5)?;
+    let messages: Option<String> = row.get(6)?;
+    let name: Option<String> = row.get(7)?;
+    let aliases: Option<String> = row.get(8)?;
+    let use_count: i64 = row.get(13)?;
+
+    let tags: Option<Vec<String>> = tags_str.map(|s| serde_json::from_str(&s)).transpose()?.flatten();
+    let categories: Option<Vec<String>> = categories_str.map(|s| serde_json::from_str(&s)).transpose()?.flatten();
+
+    Ok(Prompt {
+        id: id.filter(|id| !id.is_empty()).unwrap_or_else(|| hash.clone()),
+        name,
+        aliases: match aliases {
+            Some(aliases) => serde_json::from_str(&aliases)?,
+            None => Vec::new(),
+        },
+        hash,
+        content,
+        tags,
+        categories,
+        tests: match tests {
+            Some(tests) => serde_json::from_str(&tests)?,
+            None => Vec::new(),
+        },
+        messages: match messages {
+            Some(messages) => serde_json::from_str(&messages)?,
+            None => Vec::new(),
+        },
+        created_at: parse_timestamp(row.get(9)?)?,
+        updated_at: parse_timestamp(row.get(10)?)?,
+        author: row.get(11)?,
+        last_used_at: parse_timestamp(row.get(12)?)?,
+        use_count: use_count as u64,
+        deleted_at: parse_timestamp(row.get(14)?)?,
+    })
+}
+
 #[async_trait]
 impl Storage for LibSQLStorage {
     async fn save_prompt(&self, prompt: &mut Prompt) -> Result<()> {
-        let tags = serde_json::to_string(&prompt.tags.as_deref().unwrap_or_default())?;
-        let categories = serde_json::to_string(&prompt.categories.as_deref().unwrap_or_default())?;
+        self.check_writable()?;
+        check_names_available(prompt, &[])?;
+        for name in prompt.names() {
+            let mut rows = self.conn.query(
+                "SELECT hash FROM prompt_names WHERE name = ?1 AND hash != ?2",
+                libsql::params![name, prompt.hash.clone()],
+            ).await?;
+            if let Some(row) = rows.next().await? {
+                let hash: String = row.get(0)?;
+                return Err(name_taken(name, &hash));
+            }
+        }
 
-        self.conn.execute(
-            "INSERT INTO prompts (hash, content, tags, categories) VALUES (?1, ?2, ?3, ?4)",
-            libsql::params![prompt.hash.clone(), prompt.content.clone(), tags, categories],
-        ).await?;
+        // Saving a prompt again replaces any copy of it in the trash.
+        self.purge_trashed(&prompt.hash).await?;
+        self.insert_prompt(prompt, None).await?;
+        for name in prompt.names() {
+            self.conn.execute(
+                "INSERT INTO prompt_names (name, hash) VALUES (?1, ?2)",
+                libsql::params![name, prompt.hash.clone()],
+            ).await?;
+        }
 
-        Ok(())
+        self.after_write().await
     }
 
     async fn load_prompts(&self) -> Result<Vec<Prompt>> {
-        let mut rows = self.conn.query("SELECT hash, content, tags, categories FROM prompts", ()).await?;
+        let mut rows = self.conn.query(&format!("SELECT {} FROM prompts p WHERE p.deleted_at IS NULL", PROMPT_COLUMNS), ()).await?;
         let mut prompts = Vec::new();
 
         while let Some(row) = rows.next().await? {
-            let hash: String = row.get(0)?;
-            let content: String = row.get(1)?;
-            let tags_str: String = row.get(2)?;
-            let categories_str: String = row.get(3)?;
+            prompts.push(row_to_prompt(&row)?);
+        }
+
+        Ok(prompts)
+    }
 
-            let tags: Option<Vec<String>> = serde_json::from_str(&tags_str)?;
-            let categories: Option<Vec<String>> = serde_json::from_str(&categories_str)?;
+    async fn record_use(&self, hash: &str, at: DateTime<Utc>) -> Result<()> {
+        self.check_writable()?;
+        self.conn.execute(
+            "UPDATE prompts SET last_used_at = ?2, use_count = use_count + 1 WHERE hash = ?1 AND deleted_at IS NULL",
+            libsql::params![hash, format_timestamp(Some(at))],
+        ).await?;
+        self.after_write().await
+    }
 
-            prompts.push(Prompt {
-                hash,
-                content,
-                tags,
-                categories,
-            });
+    async fn find_by_hash_prefix(&self, prefix: &str) -> Result<Vec<Prompt>> {
+        // Prefixes are hex, so they hold no LIKE wildcards to escape.
+        let mut rows = self.conn.query(
+            &format!("SELECT {} FROM prompts p WHERE (p.hash LIKE ?1 || '%' OR p.id LIKE ?1 || '%') AND p.deleted_at IS NULL ORDER BY p.hash", PROMPT_COLUMNS),
+            libsql::params![prefix],
+        ).await?;
+        let mut prompts = Vec::new();
+        while let Some(row) = rows.next().await? {
+            prompts.push(row_to_prompt(&row)?);
         }
-
         Ok(prompts)
     }
 
+    async fn find_by_name(&self, name: &str) -> Result<Option<Prompt>> {
+        let mut rows = self.conn.query(
+            &format!(
+                "SELECT {} FROM prompts p JOIN prompt_names n ON n.hash = p.hash WHERE n.name = ?1 AND p.deleted_at IS NULL",
+                PROMPT_COLUMNS
+            ),
+            libsql::params![name],
+        ).await?;
+        match rows.next().await? {
+            Some(row) => Ok(Some(row_to_prompt(&row)?)),
+            None => Ok(None),
+        }
+    }
+
     async fn delete_prompt(&self, hash: &str) -> Result<()> {
+        self.check_writable()?;
+        self.conn.execute(
+            "DELETE FROM prompts WHERE hash = ?1 AND deleted_at IS NULL",
+            libsql::params![hash],
+        ).await?;
         self.conn.execute(
-            "DELETE FROM prompts WHERE hash = ?1",
+            "DELETE FROM prompt_names WHERE hash = ?1",
             libsql::params![hash],
         ).await?;
+        self.after_write().await
+    }
+
+    async fn trash_prompt(&self, hash: &str, at: DateTime<Utc>) -> Result<()> {
+        self.check_writable()?;
+        // Trashed prompts give up their names, so new prompts can take them.
+        self.conn.execute(
+            "UPDATE prompts SET deleted_at = ?2 WHERE hash = ?1 AND deleted_at IS NULL",
+            libsql::params![hash, format_timestamp(Some(at))],
+        ).await?;
+        self.conn.execute(
+            "DELETE FROM prompt_names WHERE hash = ?1",
+            libsql::params![hash],
+        ).await?;
+        self.after_write().await
+    }
+
+    async fn load_trash(&self) -> Result<Vec<Prompt>> {
+        let mut rows = self.conn.query(
+            &format!("SELECT {} FROM prompts p WHERE p.deleted_at IS NOT NULL", PROMPT_COLUMNS),
+            (),
+        ).await?;
+        let mut prompts = Vec::new();
+        while let Some(row) = rows.next().await? {
+            prompts.push(row_to_prompt(&row)?);
+        }
+        Ok(prompts)
+    }
+
+    async fn save_trashed(&self, prompt: &Prompt) -> Result<()> {
+        self.check_writable()?;
+        self.purge_trashed(&prompt.hash).await?;
+        self.insert_prompt(prompt, Some(prompt.deleted_at.unwrap_or_else(Utc::now))).await?;
+        self.after_write().await
+    }
+
+    async fn restore_prompt(&self, hash: &str) -> Result<()> {
+        let trashed = self.load_trash().await?.into_iter().find(|p| p.hash == hash);
+        if let Some(mut prompt) = trashed {
+            prompt.deleted_at = None;
+            self.save_prompt(&mut prompt).await?;
+        }
         Ok(())
     }
-}
\ No newline at end of file
+
+    async fn purge_prompt(&self, hash: &str) -> Result<()> {
+        self.check_writable()?;
+        self.purge_trashed(hash).await?;
+        self.after_write().await
+    }
+
+    async fn save_revision(&self, id: &str, revision: &Revision) -> Result<()> {
+        self.check_writable()?;
+        let messages = (!revision.messages.is_empty()).then(|| serde_json::to_string(&revision.messages)).transpose()?;
+        self.conn.execute(
+            "INSERT INTO revisions (prompt_id, number, hash, content, messages) VALUES (?1, ?2, ?3, ?4, ?5)",
+            libsql::params![id, revision.number as i64, revision.hash.clone(), revision.content.clone(), messages],
+        ).await?;
+        self.after_write().await
+    }
+
+    async fn load_revisions(&self, id: &str) -> Result<Vec<Revision>> {
+        let mut rows = self.conn.query(
+            "SELECT number, hash, content, messages FROM revisions WHERE prompt_id = ?1 ORDER BY number",
+            libsql::params![id],
+        ).await?;
+        let mut revisions = Vec::new();
+
+        while let Some(row) = rows.next().await? {
+            let number: i64 = row.get(0)?;
+            let messages: Option<String> = row.get(3)?;
+            revisions.push(Revision {
+                number: number as usize,
+                hash: row.get(1)?,
+                content: row.get(2)?,
+                messages: match messages {
+                    Some(messages) => serde_json::from_str(&messages)?,
+                    None => Vec::new(),
+                },
+            });
+        }
+
+        Ok(revisions)
+    }
+
+    async fn save_run(&self, run: &Run) -> Result<()> {
+        self.check_writable()?;
+        let usage = run.usage.as_ref();
+        self.conn.execute(
+            "INSERT INTO runs (id, prompt_id, prompt_hash, input, variables, provider, model, output,
+                latency_ms, prompt_tokens, completion_tokens, total_tokens, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            libsql::params![
+                run.id.clone(),
+                run.prompt_id.clone(),
+                run.prompt_hash.clone(),
+                run.input.clone(),
+                serde_json::to_string(&run.variables)?,
+                run.provider.clone(),
+                run.model.clone(),
+                run.output.clone(),
+                run.latency_ms as i64,
+                usage.map(|u| u.prompt_tokens as i64),
+                usage.map(|u| u.completion_tokens as i64),
+                usage.map(|u| u.total_tokens as i64),
+                run.created_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+            ],
+        ).await?;
+        self.after_write().await
+    }
+
+    async fn load_runs(&self, prompt_id: Option<&str>) -> Result<Vec<Run>> {
+        let mut rows = self.conn.query(
+            "SELECT id, prompt_id, prompt_hash, input, variables, provider, model, output, latency_ms,
+                prompt_tokens, completion_tokens, total_tokens, created_at
+             FROM runs WHERE ?1 IS NULL OR prompt_id = ?1 ORDER BY created_at, id",
+            libsql::params![prompt_id],
+        ).await?;
+        let mut runs = Vec::new();
+
+        while let Some(row) = rows.next().await? {
+            let variables: String = row.get(4)?;
+            let latency_ms: i64 = row.get(8)?;
+            let prompt_tokens: Option<i64> = row.get(9)?;
+            let completion_tokens: Option<i64> = row.get(10)?;
+            let total_tokens: Option<i64> = row.get(11)?;
+            let created_at: String = row.get(12)?;
+            runs.push(Run {
+                id: row.get(0)?,
+                prompt_id: row.get(1)?,
+                prompt_hash: row.get(2)?,
+                input: row.get(3)?,
+                variables: serde_json::from_str(&variables)?,
+                provider: row.get(5)?,
+                model: row.get(6)?,
+                output: row.get(7)?,
+                latency_ms: latency_ms as u64,
+                usage: total_tokens.map(|total_tokens| Usage {
+                    prompt_tokens: prompt_tokens.unwrap_or(0) as u64,
+                    completion_tokens: completion_tokens.unwrap_or(0) as u64,
+                    total_tokens: total_tokens as u64,
+                }),
+                created_at: DateTime::parse_from_rfc3339(&created_at)?.with_timezone(&Utc),
+            });
+        }
+
+        Ok(runs)
+    }
+
+    async fn query_prompts(&self, query: &StorageQuery) -> Result<Option<Vec<SearchResult>>> {
+        let mut params: Vec<libsql::Value> = Vec::new();
+        let rank_terms: Vec<String> = query.filter
+            .positive_content_terms()
+            .into_iter()
+            .filter_map(fts_match_expression)
+            .collect();
+
+        let mut sql = if rank_terms.is_empty() {
+            format!("SELECT {}, 0.0 FROM prompts p", PROMPT_COLUMNS)
+        } else {
+            params.push(rank_terms.join(" OR ").into());
+            format!(
+                "SELECT {}, coalesce(r.score, 0.0) FROM prompts p \
+                 LEFT JOIN (SELECT rowid, -bm25(prompts_fts) AS score FROM prompts_fts WHERE prompts_fts MATCH ?1) r \
+                 ON r.rowid = p.rowid",
+                PROMPT_COLUMNS
+            )
+        };
+        let condition = query_to_sql(&query.filter, &mut params);
+        sql.push_str(&format!(
+            " WHERE p.deleted_at IS NULL AND ({}) \
+             ORDER BY {} DESC, p.use_count DESC, coalesce(p.updated_at, p.created_at) DESC, length(p.content), p.hash",
+            condition,
+            PROMPT_COLUMN_COUNT + 1
+        ));
+        if let Some(limit) = query.limit {
+            params.push((limit as i64).into());
+            sql.push_str(&format!(" LIMIT ?{}", params.len()));
+        }
+
+        let mut rows = self.conn.query(&sql, params).await?;
+        let mut results = Vec::new();
+        while let Some(row) = rows.next().await? {
+            results.push(SearchResult {
+                prompt: row_to_prompt(&row)?,
+                score: row.get(PROMPT_COLUMN_COUNT)?,
+                indices: Vec::new(),
+            });
+        }
+
+        Ok(Some(results))
+    }
+
+    async fn sync(&self) -> Result<SyncReport> {
+        let Some(replica) = &self.replica else {
+            return Err(anyhow::anyhow!(
+                "This database is not a replica; set storage.type to `libsql-replica` and storage.sync_url to share it"
+            ));
+        };
+        let replicated = replica.db.sync().await.map_err(|e| {
+            anyhow::anyhow!("Cannot reach the sync server at {}: {}", replica.config.sync_url, e)
+        })?;
+        Ok(SyncReport { frames_synced: replicated.frames_synced(), frame_no: replicated.frame_no() })
+    }
+}
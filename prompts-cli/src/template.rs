@@ -0,0 +1,532 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use tera::ast::{Expr, ExprVal, LogicOperator, Node};
+
+use crate::chat;
+use crate::provider::Message;
+use crate::storage::Prompt;
+
+/// A variable used by a prompt template.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct TemplateVariable {
+    /// The top-level name of the variable, e.g. `user` for `{{ user.name }}`.
+    pub name: String,
+    /// Whether rendering fails without it. Variables only used with the
+    /// `default` filter, in `is defined` tests or as `if` conditions are optional.
+    pub required: bool,
+}
+
+/// How a prompt was pulled into another prompt's template.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InclusionKind {
+    /// `{% include "name" %}`
+    Include,
+    /// `{% import "name" as macros %}`
+    Import,
+    /// `{% extends "name" %}`
+    Extends,
+}
+
+impl std::fmt::Display for InclusionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            InclusionKind::Include => "include",
+            InclusionKind::Import => "import",
+            InclusionKind::Extends => "extends",
+        })
+    }
+}
+
+/// A prompt pulled into a rendered template from the prompt store.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct Inclusion {
+    /// The name the referencing template used for the prompt.
+    pub name: String,
+    /// The hash of the prompt the name resolved to.
+    pub hash: String,
+    /// The hash of the prompt containing the reference.
+    pub parent: String,
+    /// How the prompt was pulled in.
+    pub kind: InclusionKind,
+    /// How many references away from the rendered prompt this one is, starting at 1.
+    pub depth: usize,
+}
+
+/// A prompt template together with the stored prompts it includes, imports
+/// macros from or extends.
+pub struct ComposedTemplate {
+    tera: tera::Tera,
+    /// The templates to render, with the role of each message of a chat prompt.
+    parts: Vec<(Option<String>, String)>,
+    contents: Vec<(InclusionKind, String)>,
+    /// Every reference resolved while composing, in template order.
+    pub inclusions: Vec<Inclusion>,
+}
+
+impl ComposedTemplate {
+    /// Resolves the references in `root` against `prompts`, recursively.
+    ///
+    /// A reference names a prompt by its full hash, its stable id, its name or
+    /// an alias, or a unique hash prefix. Fails if a reference cannot be
+    /// resolved, unless it was marked `ignore missing`, or if prompts include
+    /// each other in a cycle.
+    ///
+    /// Each message of a chat prompt is a template of its own.
+    pub fn new(root: &Prompt, prompts: &[Prompt]) -> Result<Self> {
+        let parts: Vec<(Option<String>, String, String)> = if root.is_chat() {
+            root.messages
+                .iter()
+                .enumerate()
+                .map(|(i, m)| (Some(m.role.clone()), format!("{}#{}", root.hash, i), m.content.clone()))
+                .collect()
+        } else {
+            vec![(None, root.hash.clone(), root.content.clone())]
+        };
+
+        let mut composer = Composer {
+            prompts,
+            stack: vec![root.hash.clone()],
+            templates: parts.iter().map(|(_, name, content)| (name.clone(), content.clone())).collect(),
+            registered: HashSet::from([root.hash.clone()]),
+            contents: parts.iter().map(|(_, _, content)| (InclusionKind::Include, content.clone())).collect(),
+            inclusions: Vec::new(),
+        };
+        for (_, name, content) in &parts {
+            composer.visit(&root.hash, name, content)?;
+        }
+
+        let mut tera = tera::Tera::default();
+        tera.autoescape_on(Vec::new());
+        tera.add_raw_templates(composer.templates)
+            .map_err(|e| anyhow::anyhow!(error_chain(&e)))?;
+
+        Ok(Self {
+            tera,
+            parts: parts.into_iter().map(|(role, name, _)| (role, name)).collect(),
+            contents: composer.contents,
+            inclusions: composer.inclusions,
+        })
+    }
+
+    /// Returns the variables the template and the prompts it includes or
+    /// extends read from the context. Macro bodies cannot see the context, so
+    /// imported prompts do not contribute.
+    pub fn variables(&self) -> Result<Vec<TemplateVariable>> {
+        let mut variables: BTreeMap<String, bool> = BTreeMap::new();
+        for (kind, content) in &self.contents {
+            if *kind == InclusionKind::Import {
+                continue;
+            }
+            for variable in template_variables(content)? {
+                *variables.entry(variable.name).or_insert(false) |= variable.required;
+            }
+        }
+        Ok(variables
+            .into_iter()
+            .map(|(name, required)| TemplateVariable { name, required })
+            .collect())
+    }
+
+    /// Renders the template with the given context.
+    ///
+    /// A chat prompt renders to the transcript of its messages.
+    pub fn render(&self, context: &tera::Context) -> Result<String> {
+        match self.parts.as_slice() {
+            [(None, name)] => self
+                .tera
+                .render(name, context)
+                .map_err(|e| anyhow::anyhow!(error_chain(&e))),
+            _ => Ok(chat::transcript(&self.render_messages(context)?)),
+        }
+    }
+
+    /// Renders the template as the messages to send to a provider.
+    ///
+    /// A plain text prompt becomes a single user message.
+    pub fn render_messages(&self, context: &tera::Context) -> Result<Vec<Message>> {
+        self.parts
+            .iter()
+            .map(|(role, name)| {
+                let content = self
+                    .tera
+                    .render(name, context)
+                    .map_err(|e| anyhow::anyhow!(error_chain(&e)))?;
+                Ok(Message {
+                    role: role.clone().unwrap_or_else(|| "user".to_string()),
+                    content,
+                })
+            })
+            .collect()
+    }
+}
+
+struct Composer<'a> {
+    prompts: &'a [Prompt],
+    stack: Vec<String>,
+    templates: Vec<(String, String)>,
+    registered: HashSet<String>,
+    contents: Vec<(InclusionKind, String)>,
+    inclusions: Vec<Inclusion>,
+}
+
+impl Composer<'_> {
+    /// Resolves the references in `content`, the template `name` of the prompt with hash `hash`.
+    fn visit(&mut self, hash: &str, name: &str, content: &str) -> Result<()> {
+        let template = tera::Template::new(name, None, content)
+            .map_err(|e| anyhow::anyhow!(error_chain(&e)))?;
+        let mut references = Vec::new();
+        collect_references(&template.ast, &mut references);
+
+        for (kind, name, ignore_missing) in references {
+            let target = match resolve_reference(&name, self.prompts) {
+                Ok(target) => target,
+                Err(_) if ignore_missing => continue,
+                Err(e) => return Err(e),
+            };
+            if self.stack.contains(&target.hash) {
+                let mut cycle: Vec<&str> = self.stack.iter().map(|h| &h[..12.min(h.len())]).collect();
+                cycle.push(&target.hash[..12.min(target.hash.len())]);
+                return Err(anyhow::anyhow!("Prompts include each other in a cycle: {}", cycle.join(" -> ")));
+            }
+
+            self.inclusions.push(Inclusion {
+                name: name.clone(),
+                hash: target.hash.clone(),
+                parent: hash.to_string(),
+                kind,
+                depth: self.stack.len(),
+            });
+            self.contents.push((kind, target.content.clone()));
+            if self.registered.insert(name.clone()) {
+                self.templates.push((name, target.content.clone()));
+            }
+
+            self.stack.push(target.hash.clone());
+            self.visit(&target.hash, &target.hash, &target.content)?;
+            self.stack.pop();
+        }
+        Ok(())
+    }
+}
+
+/// Finds the prompt a template reference names: a full hash, a stable id,
+/// a prompt name or alias, or a unique hash prefix.
+fn resolve_reference<'a>(name: &str, prompts: &'a [Prompt]) -> Result<&'a Prompt> {
+    if let Some(prompt) = prompts.iter().find(|p| p.hash == name || p.id == name || p.names().any(|n| n == name)) {
+        return Ok(prompt);
+    }
+    let candidates: Vec<&Prompt> = prompts.iter().filter(|p| p.hash.starts_with(name)).collect();
+    match candidates.as_slice() {
+        [prompt] => Ok(prompt),
+        [] => Err(anyhow::anyhow!("Included prompt `{}` not found", name)),
+        _ => Err(anyhow::anyhow!("Included prompt `{}` is ambiguous, it matches {} prompts", name, candidates.len())),
+    }
+}
+
+fn collect_references(nodes: &[Node], references: &mut Vec<(InclusionKind, String, bool)>) {
+    for node in nodes {
+        match node {
+            Node::Include(_, names, ignore_missing) => {
+                for name in names {
+                    references.push((InclusionKind::Include, name.clone(), *ignore_missing));
+                }
+            }
+            Node::ImportMacro(_, name, _) => references.push((InclusionKind::Import, name.clone(), false)),
+            Node::Extends(_, name) => references.push((InclusionKind::Extends, name.clone(), false)),
+            Node::MacroDefinition(_, definition, _) => collect_references(&definition.body, references),
+            Node::FilterSection(_, section, _) => collect_references(&section.body, references),
+            Node::Block(_, block, _) => collect_references(&block.body, references),
+            Node::Forloop(_, forloop, _) => {
+                collect_references(&forloop.body, references);
+                if let Some(empty_body) = &forloop.empty_body {
+                    collect_references(empty_body, references);
+                }
+            }
+            Node::If(if_node, _) => {
+                for (_, _, body) in &if_node.conditions {
+                    collect_references(body, references);
+                }
+                if let Some((_, body)) = &if_node.otherwise {
+                    collect_references(body, references);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Joins an error with its causes, as Tera puts the useful detail in the source.
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+/// Returns the variables a template reads from its context, sorted by name.
+///
+/// Loop variables, `set` assignments and macro arguments are local to the
+/// template and are not reported.
+pub fn template_variables(template: &str) -> Result<Vec<TemplateVariable>> {
+    let template = tera::Template::new("prompt", None, template)
+        .map_err(|e| anyhow::anyhow!(error_chain(&e)))?;
+    let mut collector = Collector::default();
+    collector.scopes.push(HashSet::new());
+    collector.nodes(&template.ast);
+    Ok(collector
+        .variables
+        .into_iter()
+        .map(|(name, required)| TemplateVariable { name, required })
+        .collect())
+}
+
+/// Returns the names of the required variables missing from `context`.
+pub fn missing_variables(variables: &[TemplateVariable], context: &tera::Context) -> Vec<String> {
+    variables
+        .iter()
+        .filter(|v| v.required && !context.contains_key(&v.name))
+        .map(|v| v.name.clone())
+        .collect()
+}
+
+/// The places `generate` takes template variables from.
+///
+/// When a variable is set in more than one place the most specific source
+/// wins: variable files are applied first, in order, then environment
+/// variables, then file contents and finally explicit `key=value` pairs.
+#[derive(Debug, Default, Clone)]
+pub struct VariableSources {
+    /// JSON, TOML or YAML files holding a table of variables.
+    pub files: Vec<PathBuf>,
+    /// Prefix of the environment variables to read, e.g. `PROMPT_`.
+    pub env_prefix: Option<String>,
+    /// Variables set to the contents of a file.
+    pub file_contents: Vec<(String, PathBuf)>,
+    /// Variables given directly as strings.
+    pub values: Vec<(String, String)>,
+}
+
+impl VariableSources {
+    /// Builds the rendering context, reading the process environment for
+    /// `env_prefix`.
+    pub fn context(&self) -> Result<tera::Context> {
+        let mut context = tera::Context::new();
+        for path in &self.files {
+            context.extend(read_vars_file(path)?);
+        }
+        if let Some(prefix) = &self.env_prefix {
+            // Variables that are not valid Unicode cannot be rendered, so they are left out.
+            let vars = std::env::vars_os().filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)));
+            for (key, value) in env_variables(prefix, vars) {
+                context.insert(key, &value);
+            }
+        }
+        for (key, path) in &self.file_contents {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read `{}` for variable `{}`: {}", path.display(), key, e))?;
+            context.insert(key, &content);
+        }
+        for (key, value) in &self.values {
+            context.insert(key, value);
+        }
+        Ok(context)
+    }
+}
+
+/// Reads template variables from a JSON, TOML or YAML file, chosen by its
+/// extension. Nested tables and arrays are kept, so templates can loop over them.
+pub fn read_vars_file(path: &Path) -> Result<tera::Context> {
+    let value = read_data_file(path, "variables")?;
+    if !value.is_object() {
+        return Err(anyhow::anyhow!(
+            "Variables file `{}` must contain a table of variables at the top level",
+            path.display()
+        ));
+    }
+    Ok(tera::Context::from_value(value)?)
+}
+
+/// Reads a JSON, TOML or YAML file, chosen by its extension. `kind` names
+/// the file in error messages.
+pub(crate) fn read_data_file(path: &Path, kind: &str) -> Result<serde_json::Value> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {} file `{}`: {}", kind, path.display(), e))?;
+    let extension = path.extension().and_then(|e| e.to_str()).map(str::to_lowercase);
+    match extension.as_deref() {
+        Some("json") => serde_json::from_str(&text).map_err(|e| e.to_string()),
+        Some("toml") => toml::from_str(&text).map_err(|e| e.to_string()),
+        Some("yaml" | "yml") => serde_yaml::from_str(&text).map_err(|e| e.to_string()),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unsupported {} file `{}`, expected a .json, .toml, .yaml or .yml extension",
+                kind,
+                path.display()
+            ))
+        }
+    }
+    .map_err(|e| anyhow::anyhow!("Invalid {} file `{}`: {}", kind, path.display(), e))
+}
+
+/// Picks the environment variables starting with `prefix` and names them
+/// by the rest of the name in lowercase, so `PROMPT_USER_NAME` becomes
+/// `user_name` for the prefix `PROMPT_`.
+pub fn env_variables(prefix: &str, vars: impl IntoIterator<Item = (String, String)>) -> Vec<(String, String)> {
+    let mut variables: Vec<(String, String)> = vars
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let name = key.strip_prefix(prefix)?;
+            (!name.is_empty()).then(|| (name.to_lowercase(), value))
+        })
+        .collect();
+    variables.sort();
+    variables
+}
+
+#[derive(Default)]
+struct Collector {
+    variables: BTreeMap<String, bool>,
+    scopes: Vec<HashSet<String>>,
+}
+
+impl Collector {
+    fn nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        match node {
+            Node::VariableBlock(_, expr) => self.expr(expr, true),
+            Node::Set(_, set) => {
+                self.expr(&set.value, true);
+                let scope = if set.global { self.scopes.first_mut() } else { self.scopes.last_mut() };
+                if let Some(scope) = scope {
+                    scope.insert(set.key.clone());
+                }
+            }
+            Node::FilterSection(_, section, _) => {
+                self.args(section.filter.args.values());
+                self.nodes(&section.body);
+            }
+            Node::Block(_, block, _) => self.nodes(&block.body),
+            Node::Forloop(_, forloop, _) => {
+                self.expr(&forloop.container, true);
+                let mut scope: HashSet<String> = forloop.key.iter().cloned().collect();
+                scope.insert(forloop.value.clone());
+                scope.insert("loop".to_string());
+                self.scopes.push(scope);
+                self.nodes(&forloop.body);
+                self.scopes.pop();
+                if let Some(empty_body) = &forloop.empty_body {
+                    self.nodes(empty_body);
+                }
+            }
+            Node::If(if_node, _) => {
+                for (_, condition, body) in &if_node.conditions {
+                    self.condition(condition);
+                    self.nodes(body);
+                }
+                if let Some((_, body)) = &if_node.otherwise {
+                    self.nodes(body);
+                }
+            }
+            // Macros cannot see the template context, and the remaining nodes
+            // do not read variables.
+            _ => {}
+        }
+    }
+
+    /// Visits an expression evaluated as a boolean, where an undefined
+    /// variable counts as false instead of failing.
+    fn condition(&mut self, expr: &Expr) {
+        match &expr.val {
+            ExprVal::Ident(name) => self.ident(name, false),
+            ExprVal::Logic(logic) if matches!(logic.operator, LogicOperator::And | LogicOperator::Or) => {
+                self.condition(&logic.lhs);
+                self.condition(&logic.rhs);
+            }
+            _ => self.expr(expr, true),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, required: bool) {
+        let required = required && !expr.has_default_filter();
+        self.value(&expr.val, required);
+        for filter in &expr.filters {
+            self.args(filter.args.values());
+        }
+    }
+
+    fn value(&mut self, value: &ExprVal, required: bool) {
+        match value {
+            ExprVal::Ident(name) => self.ident(name, required),
+            ExprVal::Math(math) => {
+                self.expr(&math.lhs, required);
+                self.expr(&math.rhs, required);
+            }
+            ExprVal::Logic(logic) => {
+                self.expr(&logic.lhs, required);
+                self.expr(&logic.rhs, required);
+            }
+            ExprVal::Test(test) => {
+                let optional = test.name == "defined" || test.name == "undefined";
+                self.ident(&test.ident, required && !optional);
+                for arg in &test.args {
+                    self.expr(arg, true);
+                }
+            }
+            ExprVal::MacroCall(call) => self.args(call.args.values()),
+            ExprVal::FunctionCall(call) => self.args(call.args.values()),
+            ExprVal::Array(items) => {
+                for item in items {
+                    self.expr(item, required);
+                }
+            }
+            ExprVal::StringConcat(concat) => {
+                for value in &concat.values {
+                    self.value(value, required);
+                }
+            }
+            ExprVal::In(in_expr) => {
+                self.expr(&in_expr.lhs, required);
+                self.expr(&in_expr.rhs, required);
+            }
+            ExprVal::String(_) | ExprVal::Int(_) | ExprVal::Float(_) | ExprVal::Bool(_) => {}
+        }
+    }
+
+    fn args<'a>(&mut self, args: impl Iterator<Item = &'a Expr>) {
+        for arg in args {
+            self.expr(arg, true);
+        }
+    }
+
+    /// Records the variable an identifier such as `user.name` or
+    /// `items[index]` reads, including variables used as subscripts.
+    fn ident(&mut self, ident: &str, required: bool) {
+        let root_end = ident.find(['.', '[']).unwrap_or(ident.len());
+        let root = &ident[..root_end];
+        if !root.starts_with("__tera") && !self.scopes.iter().any(|scope| scope.contains(root)) {
+            *self.variables.entry(root.to_string()).or_insert(false) |= required;
+        }
+
+        let mut rest = &ident[root_end..];
+        while let Some(open) = rest.find('[') {
+            let Some(close) = rest[open..].find(']') else { break };
+            let subscript = rest[open + 1..open + close].trim();
+            let is_literal = subscript.starts_with(['"', '\'', '`']) || subscript.parse::<f64>().is_ok();
+            if !subscript.is_empty() && !is_literal {
+                self.ident(subscript, required);
+            }
+            rest = &rest[open + close + 1..];
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+
+/// A byte-pair encoding used to count tokens. The tables are embedded in
+/// the binary, so counting works offline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Encoding {
+    /// The encoding of GPT-3.5 and GPT-4.
+    #[serde(rename = "cl100k_base")]
+    Cl100kBase,
+    /// The encoding of GPT-4o and the o-series models.
+    #[serde(rename = "o200k_base")]
+    O200kBase,
+}
+
+impl Encoding {
+    /// Returns the encoding an OpenAI model uses, if the model is known.
+    pub fn for_model(model: &str) -> Option<Self> {
+        match get_tokenizer(model)? {
+            Tokenizer::O200kBase => Some(Encoding::O200kBase),
+            Tokenizer::Cl100kBase => Some(Encoding::Cl100kBase),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Encoding::Cl100kBase => "cl100k_base",
+            Encoding::O200kBase => "o200k_base",
+        })
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cl100k" | "cl100k_base" => Ok(Encoding::Cl100kBase),
+            "o200k" | "o200k_base" => Ok(Encoding::O200kBase),
+            _ => Err(format!("unknown encoding `{}`, expected cl100k_base or o200k_base", s)),
+        }
+    }
+}
+
+/// Counts the tokens in `text`.
+pub fn count_tokens(text: &str, encoding: Encoding) -> usize {
+    let bpe = match encoding {
+        Encoding::Cl100kBase => tiktoken_rs::cl100k_base_singleton(),
+        Encoding::O200kBase => tiktoken_rs::o200k_base_singleton(),
+    };
+    bpe.encode_ordinary(text).len()
+}
+
+/// Returns the context window of an OpenAI model, if the model is known.
+pub fn context_limit(model: &str) -> Option<usize> {
+    get_tokenizer(model).map(|_| tiktoken_rs::model::get_context_size(model))
+}
+
+/// The `[tokens]` section of the configuration file.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct TokensConfig {
+    /// The model prompts are budgeted for. Known OpenAI models set the
+    /// encoding and context limit.
+    pub model: Option<String>,
+    /// The encoding to count with, overriding the model's.
+    pub encoding: Option<Encoding>,
+    /// The context window in tokens, overriding the model's.
+    pub context_limit: Option<usize>,
+}
+
+impl TokensConfig {
+    /// Works out how to count tokens, with `model` and `encoding` given for
+    /// one command taking precedence over the configuration. A configured
+    /// `context_limit` applies whichever model is used.
+    ///
+    /// Falls back to `o200k_base` when no encoding is known.
+    pub fn budget(&self, model: Option<&str>, encoding: Option<Encoding>) -> TokenBudget {
+        let configured = self.model.as_deref();
+        let encoding = encoding
+            .or_else(|| model.and_then(Encoding::for_model))
+            .or(self.encoding)
+            .or_else(|| configured.and_then(Encoding::for_model))
+            .unwrap_or(Encoding::O200kBase);
+        let context_limit = self.context_limit.or_else(|| model.or(configured).and_then(context_limit));
+        TokenBudget {
+            encoding,
+            model: model.or(configured).map(str::to_string),
+            context_limit,
+        }
+    }
+}
+
+/// An encoding to count with and the context window to compare against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenBudget {
+    pub encoding: Encoding,
+    pub model: Option<String>,
+    pub context_limit: Option<usize>,
+}
+
+impl TokenBudget {
+    pub fn count(&self, text: &str) -> TokenCount {
+        TokenCount {
+            tokens: count_tokens(text, self.encoding),
+            encoding: self.encoding,
+            model: self.model.clone(),
+            context_limit: self.context_limit,
+        }
+    }
+}
+
+/// The size of a text in tokens, measured against a context window.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenCount {
+    pub tokens: usize,
+    pub encoding: Encoding,
+    pub model: Option<String>,
+    pub context_limit: Option<usize>,
+}
+
+impl TokenCount {
+    /// Whether the text does not fit in the context window.
+    pub fn exceeds_limit(&self) -> bool {
+        self.context_limit.is_some_and(|limit| self.tokens > limit)
+    }
+}
+
+impl fmt::Display for TokenCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} tokens ({})", self.tokens, self.encoding)?;
+        if let Some(limit) = self.context_limit {
+            let share = self.tokens as f64 / limit as f64 * 100.0;
+            match &self.model {
+                Some(model) => write!(f, ", {:.1}% of the {}-token context of {}", share, limit, model)?,
+                None => write!(f, ", {:.1}% of the {}-token context", share, limit)?,
+            }
+        }
+        Ok(())
+    }
+}
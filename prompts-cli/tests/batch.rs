@@ -0,0 +1,117 @@
+use prompts_cli::{
+    batch::{read_rows, render_batch},
+    storage::JsonStorage,
+    template::ComposedTemplate,
+    Prompt, Prompts,
+};
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_read_rows() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+
+    let path = dir.path().join("rows.csv");
+    fs::write(&path, "name,city\nAda,London\n\"Lovelace, A\",Paris\n")?;
+    let rows = read_rows(&path)?;
+    assert_eq!(rows.len(), 2);
+    let row = rows[1].as_ref().unwrap();
+    assert_eq!(row.get("name").and_then(|v| v.as_str()), Some("Lovelace, A"));
+    assert_eq!(row.get("city").and_then(|v| v.as_str()), Some("Paris"));
+
+    let path = dir.path().join("rows.jsonl");
+    fs::write(&path, "{\"items\": [1, 2]}\n\nnot json\n[1]\n")?;
+    let rows = read_rows(&path)?;
+    assert_eq!(rows.len(), 3);
+    assert!(rows[0].is_ok());
+    assert!(rows[1].as_ref().unwrap_err().starts_with("Invalid JSON on line 3"));
+    assert_eq!(rows[2].as_ref().unwrap_err(), "Line 4 is not a JSON object");
+
+    assert!(read_rows(&dir.path().join("rows.txt")).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_render_batch() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("rows.jsonl");
+    let mut lines: Vec<String> = (0..20).map(|i| format!("{{\"name\": \"user{}\"}}", i)).collect();
+    lines[7] = "{\"greeting\": \"Hey\"}".to_string();
+    fs::write(&path, lines.join("\n"))?;
+
+    let prompt = Prompt::new("{{ greeting }}, {{ name }}!", None, None);
+    let template = ComposedTemplate::new(&prompt, &[])?;
+    let mut base = tera::Context::new();
+    base.insert("greeting", "Hello");
+
+    let results = render_batch(&template, &prompt.hash, &base, read_rows(&path)?, 3)?;
+    assert_eq!(results.len(), 20);
+    assert!(results.iter().enumerate().all(|(i, r)| r.index == i && r.hash == prompt.hash));
+    assert_eq!(results[0].output.as_deref(), Some("Hello, user0!"));
+    assert_eq!(results[19].output.as_deref(), Some("Hello, user19!"));
+    assert_eq!(results[7].output, None);
+    assert_eq!(results[7].error.as_deref(), Some("Missing template variables: name"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_generate_batch() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage_path = dir.path().join("prompts");
+    fs::create_dir_all(&storage_path)?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(
+        &config_path,
+        format!("[storage]\npath = {:?}\n", storage_path.to_string_lossy()),
+    )?;
+
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(storage_path.clone()))?));
+    let mut prompt = Prompt::new("Summarise {{ title }} for {{ audience }}", None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    let rows = dir.path().join("rows.csv");
+    fs::write(&rows, "title\nThe Book\n\"Other, Book\"\n")?;
+    let output = dir.path().join("out.jsonl");
+
+    let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli")?;
+    cmd.arg("--config")
+        .arg(&config_path)
+        .arg("generate")
+        .arg("Summarise")
+        .arg("--batch")
+        .arg(&rows)
+        .arg("--batch-output")
+        .arg(&output)
+        .arg("-v")
+        .arg("audience=kids");
+    cmd.assert().success();
+
+    let results: Vec<serde_json::Value> = fs::read_to_string(&output)?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(
+        results,
+        vec![
+            serde_json::json!({ "index": 0, "hash": prompt.hash, "output": "Summarise The Book for kids" }),
+            serde_json::json!({ "index": 1, "hash": prompt.hash, "output": "Summarise Other, Book for kids" }),
+        ]
+    );
+
+    let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli")?;
+    cmd.arg("--config")
+        .arg(&config_path)
+        .arg("generate")
+        .arg("Summarise")
+        .arg("--batch")
+        .arg(&rows)
+        .arg("--jobs")
+        .arg("2");
+    cmd.assert()
+        .failure()
+        .stdout(predicates::str::contains(r#""index":1"#))
+        .stdout(predicates::str::contains("Missing template variables: audience"))
+        .stderr(predicates::str::contains("2 of 2 rows failed to render"));
+
+    Ok(())
+}
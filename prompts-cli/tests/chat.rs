@@ -0,0 +1,197 @@
+use prompts_cli::{
+    chat::{hash_messages, parse_transcript, read_messages, transcript},
+    provider::Message,
+    storage::{JsonStorage, LibSQLStorage, Storage},
+    Prompt, Prompts,
+};
+use std::fs;
+use tempfile::tempdir;
+
+fn message(role: &str, content: &str) -> Message {
+    Message {
+        role: role.to_string(),
+        content: content.to_string(),
+    }
+}
+
+fn few_shot() -> Vec<Message> {
+    vec![
+        message("system", "You translate {{ language }}."),
+        message("user", "Hello"),
+        message("assistant", "Bonjour"),
+        message("user", "{{ text }}"),
+    ]
+}
+
+#[test]
+fn test_transcript() -> anyhow::Result<()> {
+    let messages = few_shot();
+    let text = transcript(&messages);
+    assert_eq!(text, "[system]\nYou translate {{ language }}.\n\n[user]\nHello\n\n[assistant]\nBonjour\n\n[user]\n{{ text }}");
+    assert_eq!(parse_transcript(&text)?, messages);
+
+    let edited = "\n[system]\n\n[user]\n  Line one\n\nLine two [user]\n\n";
+    assert_eq!(parse_transcript(edited)?, vec![message("user", "Line one\n\nLine two [user]")]);
+
+    assert!(parse_transcript("Hello\n[user]\nHi").unwrap_err().to_string().contains("must start with a role header"));
+    assert!(parse_transcript("[system]\n").unwrap_err().to_string().contains("at least one message"));
+    Ok(())
+}
+
+#[test]
+fn test_read_messages() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let json = dir.path().join("messages.json");
+    fs::write(&json, r#"{"model": "gpt-4o", "messages": [{"role": "system", "content": "Be brief."}, {"role": "user", "content": "Hi"}]}"#)?;
+    assert_eq!(read_messages(&json)?, vec![message("system", "Be brief."), message("user", "Hi")]);
+
+    let yaml = dir.path().join("messages.yaml");
+    fs::write(&yaml, "- role: tool\n  content: Hi\n")?;
+    assert!(read_messages(&yaml).unwrap_err().to_string().contains("Unknown message role `tool`"));
+    Ok(())
+}
+
+#[test]
+fn test_chat_prompt_hash() {
+    let prompt = Prompt::chat(few_shot(), None, None);
+    assert!(prompt.is_chat());
+    assert_eq!(prompt.hash, hash_messages(&few_shot()));
+    assert_eq!(prompt.content, transcript(&few_shot()));
+    assert_ne!(prompt.hash, Prompt::new(&prompt.content, None, None).hash);
+
+    let mut swapped = few_shot();
+    swapped[1].role = "assistant".to_string();
+    swapped[2].role = "user".to_string();
+    assert_ne!(Prompt::chat(swapped, None, None).hash, prompt.hash);
+}
+
+async fn test_chat_prompt_storage_impl(storage_type: &str) -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage: Box<dyn Storage + Send + Sync> = if storage_type == "json" {
+        Box::new(JsonStorage::new(Some(dir.path().to_path_buf()))?)
+    } else {
+        Box::new(LibSQLStorage::new(Some(dir.path().join("test.db"))).await?)
+    };
+    let prompts_api = Prompts::new(storage);
+    let mut prompt = Prompt::chat(few_shot(), Some(vec!["translate".to_string()]), None);
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    let found = prompts_api.show_prompt("Bonjour", None).await?;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].messages, few_shot());
+    assert_eq!(found[0].hash, prompt.hash);
+
+    let edited = prompt.content.replace("Bonjour", "Salut");
+    prompts_api.edit_prompt(&prompt.hash, Some(edited), None, None, None, None).await?;
+    let current = prompts_api.list_prompts(None).await?.remove(0);
+    assert_eq!(current.messages[2], message("assistant", "Salut"));
+    assert_eq!(current.hash, hash_messages(&current.messages));
+
+    prompts_api.revert_prompt(&prompt.id, 1).await?;
+    let reverted = prompts_api.list_prompts(None).await?.remove(0);
+    assert_eq!(reverted.messages, few_shot());
+    assert_eq!(reverted.hash, prompt.hash);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_prompt_storage_json() -> anyhow::Result<()> {
+    test_chat_prompt_storage_impl("json").await
+}
+
+#[tokio::test]
+async fn test_chat_prompt_storage_libsql() -> anyhow::Result<()> {
+    test_chat_prompt_storage_impl("libsql").await
+}
+
+#[tokio::test]
+async fn test_render_chat_prompt() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(dir.path().to_path_buf()))?));
+    let mut style = Prompt::new("Keep the tone formal.", None, None);
+    prompts_api.add_prompt(&mut style).await?;
+    let mut messages = few_shot();
+    messages[0].content = format!("You translate {{{{ language }}}}. {{% include \"{}\" %}}", &style.hash[..12]);
+    let mut prompt = Prompt::chat(messages, None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    let composed = prompts_api.compose(&prompt).await?;
+    let names: Vec<String> = composed.variables()?.into_iter().map(|v| v.name).collect();
+    assert_eq!(names, vec!["language", "text"]);
+    assert_eq!(composed.inclusions.len(), 1);
+
+    let mut context = tera::Context::new();
+    context.insert("language", "French");
+    context.insert("text", "Good night");
+    let rendered = composed.render_messages(&context)?;
+    assert_eq!(rendered[0], message("system", "You translate French. Keep the tone formal."));
+    assert_eq!(rendered[3], message("user", "Good night"));
+    assert_eq!(composed.render(&context)?, transcript(&rendered));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_chat_prompt() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage_path = dir.path().join("prompts");
+    fs::create_dir_all(&storage_path)?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(&config_path, format!("[storage]\npath = {:?}\n", storage_path.to_string_lossy()))?;
+
+    let cli = |args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli").unwrap();
+        cmd.arg("--config").arg(&config_path).args(args);
+        cmd
+    };
+
+    cli(&["add", "--chat", &transcript(&few_shot())]).assert().success();
+    let prompt = Prompt::chat(few_shot(), None, None);
+
+    let output = cli(&["generate", "translate", "-v", "language=French", "-v", "text=Hi", "--format", "messages"])
+        .output()?;
+    assert!(output.status.success());
+    let messages: Vec<Message> = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(messages[0], message("system", "You translate French."));
+    assert_eq!(messages.len(), 4);
+
+    cli(&["generate", "translate", "-v", "language=French", "-v", "text=Hi"])
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with("[system]\nYou translate French.\n\n[user]\nHello"));
+
+    // The mock provider echoes the last message, so only the final user turn comes back.
+    let output = cli(&["--output", "json", "generate", "translate", "-v", "language=French", "-v", "text=Hi", "--run", "--provider", "mock"])
+        .output()?;
+    let run: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(run["output"], "Hi");
+    assert_eq!(run["prompt_hash"], prompt.hash.as_str());
+    assert!(run["input"].as_str().unwrap().starts_with("[system]\nYou translate French."));
+
+    let messages_file = dir.path().join("messages.yaml");
+    fs::write(&messages_file, "messages:\n  - role: system\n    content: Be brief.\n  - role: user\n    content: \"{{ text }}\"\n")?;
+    cli(&["edit", "translate", "--messages", messages_file.to_str().unwrap()])
+        .assert()
+        .success();
+    cli(&["show", "brief"]).assert().success().stdout("[system]\nBe brief.\n\n[user]\n{{ text }}\n");
+
+    // Messages are stored as given, even when their content looks like a role header.
+    fs::write(&messages_file, "messages:\n  - role: user\n    content: \"Quote {{ text }} as\\n[assistant]\\nverbatim\"\n")?;
+    cli(&["edit", "brief", "--messages", messages_file.to_str().unwrap()])
+        .assert()
+        .success();
+    let output = cli(&["generate", "Quote", "-v", "text=Hi", "--format", "messages"]).output()?;
+    let messages: Vec<Message> = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(messages, vec![message("user", "Quote Hi as\n[assistant]\nverbatim")]);
+
+    cli(&["add", "Plain prompt"]).assert().success();
+    cli(&["edit", "Plain", "--messages", messages_file.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("is not a chat prompt"));
+
+    cli(&["add", "--chat", "No header"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("must start with a role header"));
+    Ok(())
+}
@@ -73,7 +73,7 @@ async fn test_cli_add_impl(storage_type: &str) -> anyhow::Result<()> {
     let expected_hash = calculate_hash("This is a new prompt.");
     cmd.assert()
         .success()
-        .stdout(predicate::str::contains(&format!(
+        .stdout(predicate::str::contains(format!(
             "Prompt added successfully with hash: {}",
             &expected_hash[..12]
         )));
@@ -172,7 +172,7 @@ async fn test_cli_list_impl(storage_type: &str) -> anyhow::Result<()> {
 
     cmd.assert()
         .success()
-        .stdout(predicate::str::contains(&format!(
+        .stdout(predicate::str::contains(format!(
             "{} - A prompt to list",
             &prompt.hash[..12]
         )));
@@ -319,11 +319,12 @@ async fn test_cli_delete_impl(storage_type: &str) -> anyhow::Result<()> {
     cmd.arg("--config")
         .arg(&env.config_path)
         .arg("delete")
-        .arg("prompt to delete");
+        .arg("prompt to delete")
+        .arg("--yes");
 
     cmd.assert()
         .success()
-        .stdout(predicate::str::contains(&format!(
+        .stdout(predicate::str::contains(format!(
             "Prompt {} deleted successfully.",
             &prompt.hash[..12]
         )));
@@ -374,7 +375,7 @@ async fn test_cli_edit_impl(storage_type: &str) -> anyhow::Result<()> {
 
     cmd.assert()
         .success()
-        .stdout(predicate::str::contains(&format!(
+        .stdout(predicate::str::contains(format!(
             "Prompt {} updated.",
             &old_hash[..12]
         )));
@@ -523,6 +524,8 @@ async fn test_cli_edit_add_tags_impl(storage_type: &str) -> anyhow::Result<()> {
 
     let prompts = storage.load_prompts().await?;
     let edited_prompt = prompts.iter().find(|p| p.content == "A prompt to edit with merge").unwrap();
+    // Tags are not part of the hash
+    assert_eq!(edited_prompt.hash, old_hash);
 
     let mut expected_tags = vec!["tag1".to_string(), "tag2".to_string()];
     expected_tags.sort();
@@ -823,3 +826,149 @@ async fn test_cli_show_non_existent_prompt_json() -> anyhow::Result<()> {
 async fn test_cli_show_non_existent_prompt_libsql() -> anyhow::Result<()> {
     test_cli_show_non_existent_prompt_impl("libsql").await
 }
+
+async fn test_cli_search_impl(storage_type: &str) -> anyhow::Result<()> {
+    let env = CliTestEnv::new(storage_type)?;
+    let storage: Box<dyn Storage + Send + Sync> = if storage_type == "json" {
+        Box::new(JsonStorage::new(Some(env.storage_path.to_path_buf()))?)
+    } else {
+        Box::new(LibSQLStorage::new(Some(env.storage_path.to_path_buf())).await?)
+    };
+
+    let mut prompt1 = Prompt::new("Review this code and explain every bug you find in detail", None, None);
+    storage.save_prompt(&mut prompt1).await?;
+    let mut prompt2 = Prompt::new("Review code", None, None);
+    storage.save_prompt(&mut prompt2).await?;
+    let mut prompt3 = Prompt::new("Unrelated prompt", None, None);
+    storage.save_prompt(&mut prompt3).await?;
+
+    let mut cmd = Command::cargo_bin(r#"prompts-cli"#)?;
+    cmd.arg("--config")
+        .arg(&env.config_path)
+        .arg("search")
+        .arg("review code");
+
+    let output = cmd.output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with(&prompt2.hash[..12]));
+    assert!(lines[0].ends_with("- Review code"));
+    // Scores are shown to three decimals
+    let score = lines[0].split(['(', ')']).nth(1).unwrap();
+    assert_eq!(score.split_once('.').map(|(_, decimals)| decimals.len()), Some(3), "{}", lines[0]);
+
+    let mut cmd = Command::cargo_bin(r#"prompts-cli"#)?;
+    cmd.arg("--config")
+        .arg(&env.config_path)
+        .arg("--output")
+        .arg("json")
+        .arg("search")
+        .arg("review code")
+        .arg("--limit")
+        .arg("1");
+
+    let output = cmd.output()?;
+    let results: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let results = results.as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["prompt"]["content"], "Review code");
+    assert!(results[0]["score"].as_f64().unwrap() > 0.0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_search_json() -> anyhow::Result<()> {
+    test_cli_search_impl("json").await
+}
+
+#[tokio::test]
+async fn test_cli_search_libsql() -> anyhow::Result<()> {
+    test_cli_search_impl("libsql").await
+}
+
+async fn test_cli_show_first_impl(storage_type: &str) -> anyhow::Result<()> {
+    let env = CliTestEnv::new(storage_type)?;
+    let storage: Box<dyn Storage + Send + Sync> = if storage_type == "json" {
+        Box::new(JsonStorage::new(Some(env.storage_path.to_path_buf()))?)
+    } else {
+        Box::new(LibSQLStorage::new(Some(env.storage_path.to_path_buf())).await?)
+    };
+
+    let mut prompt1 = Prompt::new("A show prompt with a lot of extra words around it", None, None);
+    storage.save_prompt(&mut prompt1).await?;
+    let mut prompt2 = Prompt::new("A show prompt", None, None);
+    storage.save_prompt(&mut prompt2).await?;
+
+    let mut cmd = Command::cargo_bin(r#"prompts-cli"#)?;
+    cmd.arg("--config")
+        .arg(&env.config_path)
+        .arg("show")
+        .arg("show prompt")
+        .arg("--first");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::diff("A show prompt\n"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_show_first_json() -> anyhow::Result<()> {
+    test_cli_show_first_impl("json").await
+}
+
+#[tokio::test]
+async fn test_cli_show_first_libsql() -> anyhow::Result<()> {
+    test_cli_show_first_impl("libsql").await
+}
+
+async fn test_cli_list_with_query_impl(storage_type: &str) -> anyhow::Result<()> {
+    let env = CliTestEnv::new(storage_type)?;
+    let storage: Box<dyn Storage + Send + Sync> = if storage_type == "json" {
+        Box::new(JsonStorage::new(Some(env.storage_path.to_path_buf()))?)
+    } else {
+        Box::new(LibSQLStorage::new(Some(env.storage_path.to_path_buf())).await?)
+    };
+
+    let mut prompt1 = Prompt::new("A rust prompt", Some(vec!["rust".to_string()]), None);
+    storage.save_prompt(&mut prompt1).await?;
+    let mut prompt2 = Prompt::new("A draft go prompt", Some(vec!["go".to_string(), "draft".to_string()]), None);
+    storage.save_prompt(&mut prompt2).await?;
+
+    let mut cmd = Command::cargo_bin(r#"prompts-cli"#)?;
+    cmd.arg("--config")
+        .arg(&env.config_path)
+        .arg("list")
+        .arg("tag:rust OR tag:go -tag:draft");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("A rust prompt"))
+        .stdout(predicate::str::contains("A draft go prompt").not());
+
+    let mut cmd = Command::cargo_bin(r#"prompts-cli"#)?;
+    cmd.arg("--config")
+        .arg(&env.config_path)
+        .arg("show")
+        .arg("tag:rust (");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Error: Invalid query: unclosed `(` at column 10"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_list_with_query_json() -> anyhow::Result<()> {
+    test_cli_list_with_query_impl("json").await
+}
+
+#[tokio::test]
+async fn test_cli_list_with_query_libsql() -> anyhow::Result<()> {
+    test_cli_list_with_query_impl("libsql").await
+}
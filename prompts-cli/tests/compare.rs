@@ -0,0 +1,118 @@
+use prompts_cli::{
+    compare::{compare_prompts, CompareOptions},
+    eval::Assertion,
+    provider::{MockConfig, MockProvider},
+    storage::JsonStorage,
+    Prompt, Prompts,
+};
+use std::fs;
+use tempfile::tempdir;
+
+#[tokio::test]
+async fn test_compare_prompts() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(dir.path().to_path_buf()))?));
+    let mut short = Prompt::new("Summarise {{ topic }}", None, None);
+    let mut long = Prompt::new("Write a long, detailed summary of {{ topic }} for {{ audience }}", None, None);
+    prompts_api.add_prompt(&mut short).await?;
+    prompts_api.add_prompt(&mut long).await?;
+
+    let mut base = tera::Context::new();
+    base.insert("audience", "experts");
+    let mut rust = tera::Context::new();
+    rust.insert("topic", "Rust");
+    let rows = vec![Ok(rust), Err("Invalid JSON on line 2".to_string())];
+
+    let provider = MockProvider::new(MockConfig::default());
+    let assertions = vec![
+        Assertion::Contains { value: "Rust".to_string() },
+        Assertion::MaxLength { value: 20 },
+    ];
+    let options = CompareOptions {
+        provider_name: "mock",
+        provider: &provider,
+        model: None,
+        assertions: &assertions,
+    };
+    let comparisons = compare_prompts(&prompts_api, &[short.clone(), long.clone()], &base, rows, &options).await?;
+
+    assert_eq!(comparisons.len(), 4);
+    let keys: Vec<(usize, &str)> = comparisons.iter().map(|c| (c.row, c.prompt_hash.as_str())).collect();
+    assert_eq!(
+        keys,
+        vec![(0, short.hash.as_str()), (0, long.hash.as_str()), (1, short.hash.as_str()), (1, long.hash.as_str())]
+    );
+
+    assert_eq!(comparisons[0].output.as_deref(), Some("Summarise Rust"));
+    assert_eq!(comparisons[0].length, 14);
+    assert_eq!(comparisons[0].score, Some(1.0));
+    assert_eq!(comparisons[1].score, Some(0.5));
+    assert_eq!(comparisons[1].failures.len(), 1);
+    assert_eq!(comparisons[2].output, None);
+    assert_eq!(comparisons[2].score, Some(0.0));
+    assert_eq!(comparisons[2].failures, vec!["Invalid JSON on line 2".to_string()]);
+
+    let runs = prompts_api.runs(None).await?;
+    assert_eq!(runs.len(), 2);
+    assert!(runs.iter().all(|run| run.provider == "mock"));
+    assert!(runs.iter().any(|run| Some(&run.id) == comparisons[0].run_id.as_ref()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_compare() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage_path = dir.path().join("prompts");
+    fs::create_dir_all(&storage_path)?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(
+        &config_path,
+        format!("[storage]\npath = {:?}\n\n[providers.mock]\nresponse = \"{{\\\"ok\\\": true}}\"\n", storage_path.to_string_lossy()),
+    )?;
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(storage_path))?));
+    let mut first = Prompt::new("Reply with JSON about {{ topic }}", None, None);
+    let mut second = Prompt::new("Return a JSON object describing {{ topic }}", None, None);
+    prompts_api.add_prompt(&mut first).await?;
+    prompts_api.add_prompt(&mut second).await?;
+
+    let assertions = dir.path().join("assertions.yaml");
+    fs::write(&assertions, "- type: json_schema\n  schema: {required: [ok]}\n- type: contains\n  value: topic\n")?;
+
+    let cli = |args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli").unwrap();
+        cmd.arg("--config").arg(&config_path).args(args);
+        cmd
+    };
+    let common = [
+        "Reply with",
+        "Return a JSON",
+        "-v",
+        "topic=cats",
+        "--provider",
+        "mock",
+        "--assertions",
+        assertions.to_str().unwrap(),
+    ];
+
+    let mut args = vec!["compare"];
+    args.extend(common);
+    cli(&args)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Input 0\n  PROMPT         LENGTH    LATENCY   TOKENS  SCORE"))
+        .stdout(predicates::str::contains(format!("  {}       12", &first.hash[..12])))
+        .stdout(predicates::str::contains("1/2"))
+        .stdout(predicates::str::contains(format!("  --- {}\n  {{\"ok\": true}}\n  ! expected the output to contain `topic`", &second.hash[..12])));
+
+    let mut args = vec!["--output", "json", "compare"];
+    args.extend(common);
+    let output = cli(&args).output()?;
+    assert!(output.status.success());
+    let comparisons: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(comparisons.len(), 2);
+    assert_eq!(comparisons[1]["prompt_hash"], second.hash);
+    assert_eq!(comparisons[1]["score"], 0.5);
+
+    cli(&["compare", "Reply with"]).assert().failure();
+    Ok(())
+}
@@ -37,7 +37,7 @@ async fn test_cli_config_file() -> anyhow::Result<()> {
 
     cmd.assert()
         .success()
-        .stdout(predicate::str::contains(&format!("{} - Config test prompt content", &prompt.hash[..12])));
+        .stdout(predicate::str::contains(format!("{} - Config test prompt content", &prompt.hash[..12])));
 
     Ok(())
 }
@@ -84,7 +84,7 @@ async fn test_cli_default_config_location() -> anyhow::Result<()> {
     // 7. Assert that the CLI finds the prompt, proving it used our config.
     cmd.assert()
         .success()
-        .stdout(predicate::str::contains(&format!("{} - Default location config test", &prompt.hash[..12])));
+        .stdout(predicate::str::contains(format!("{} - Default location config test", &prompt.hash[..12])));
 
     Ok(())
 }
@@ -121,7 +121,7 @@ async fn test_cli_default_config_file() -> anyhow::Result<()> {
 
     cmd.assert()
         .success()
-        .stdout(predicate::str::contains(&format!("{} - Default config test prompt content", &prompt.hash[..12])));
+        .stdout(predicate::str::contains(format!("{} - Default config test prompt content", &prompt.hash[..12])));
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,218 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use prompts_cli::{editor::Document, Prompt, Prompts, provider::Message, storage::{JsonStorage, Storage}};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::{tempdir, TempDir};
+use toml::Value;
+
+#[test]
+fn test_document_round_trip() -> anyhow::Result<()> {
+    let document = Document {
+        content: "Line one\nLine two".to_string(),
+        tags: vec!["tag1".to_string(), "tag2".to_string()],
+        categories: vec![],
+    };
+    let text = document.render();
+    assert_eq!(text, "---\ntags: tag1, tag2\ncategories: \n---\nLine one\nLine two\n");
+    assert_eq!(Document::parse(&text)?, document);
+    Ok(())
+}
+
+#[test]
+fn test_document_parse() -> anyhow::Result<()> {
+    let document = Document::parse("Just a body\n")?;
+    assert_eq!(document.content, "Just a body");
+    assert!(document.tags.is_empty());
+
+    let document = Document::parse("---\ncategories: a,b ,\n---\n\n  Body  \n\n")?;
+    assert_eq!(document.content, "\n  Body  \n");
+    assert_eq!(document.categories, vec!["a".to_string(), "b".to_string()]);
+
+    assert!(Document::parse("---\nauthor: me\n---\nBody").is_err());
+    assert!(Document::parse("---\ntags: a\nBody").is_err());
+    Ok(())
+}
+
+struct EditorTestEnv {
+    dir: TempDir,
+    config_path: PathBuf,
+    storage_path: PathBuf,
+}
+
+impl EditorTestEnv {
+    fn new() -> anyhow::Result<Self> {
+        let dir = tempdir()?;
+        let storage_path = dir.path().join("prompts");
+        fs::create_dir_all(&storage_path)?;
+        let config_path = dir.path().join("config.toml");
+
+        let mut config = toml::map::Map::new();
+        let mut storage_config = toml::map::Map::new();
+        storage_config.insert(
+            "path".to_string(),
+            Value::String(storage_path.to_string_lossy().into_owned()),
+        );
+        config.insert("storage".to_string(), Value::Table(storage_config));
+        fs::write(&config_path, toml::to_string(&config)?)?;
+
+        Ok(Self { dir, config_path, storage_path })
+    }
+
+    /// Writes an editor script that replaces the edited file with `contents`.
+    fn editor(&self, contents: &str) -> anyhow::Result<PathBuf> {
+        let replacement = self.dir.path().join("replacement.md");
+        fs::write(&replacement, contents)?;
+        let script = self.dir.path().join("editor.sh");
+        fs::write(&script, format!("#!/bin/sh\ncp '{}' \"$1\"\n", replacement.display()))?;
+        make_executable(&script)?;
+        Ok(script)
+    }
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Wraps `cmd` in `script` so that it runs with a terminal, which the editor needs.
+fn in_terminal(cmd: &Command) -> Command {
+    let quote = |arg: &std::ffi::OsStr| format!("'{}'", arg.to_string_lossy().replace('\'', "'\\''"));
+    let line: Vec<String> = std::iter::once(cmd.get_program()).chain(cmd.get_args()).map(quote).collect();
+    let mut script = Command::new("script");
+    script.arg("-qec").arg(line.join(" ")).arg("/dev/null");
+    for (key, value) in cmd.get_envs() {
+        if let Some(value) = value {
+            script.env(key, value);
+        }
+    }
+    script
+}
+
+#[tokio::test]
+async fn test_cli_edit_in_editor() -> anyhow::Result<()> {
+    let env = EditorTestEnv::new()?;
+    let storage = JsonStorage::new(Some(env.storage_path.clone()))?;
+    let prompts_api = Prompts::new(Box::new(storage));
+    let mut prompt = Prompt::new(
+        "A prompt to edit in the editor",
+        Some(vec!["old".to_string(), "kept".to_string()]),
+        None,
+    );
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    let editor = env.editor("---\ntags: kept, new\ncategories: cat1\n---\nAn edited prompt\n")?;
+    let mut cmd = Command::cargo_bin("prompts-cli")?;
+    cmd.env("VISUAL", &editor)
+        .arg("--config")
+        .arg(&env.config_path)
+        .arg("edit")
+        .arg("prompt to edit in the editor");
+    in_terminal(&cmd).assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Prompt {} updated.", &prompt.hash[..12])));
+
+    let storage = JsonStorage::new(Some(env.storage_path.clone()))?;
+    let prompts = storage.load_prompts().await?;
+    assert_eq!(prompts.len(), 1);
+    assert_eq!(prompts[0].content, "An edited prompt");
+    assert_eq!(prompts[0].id, prompt.id);
+    assert_eq!(prompts[0].tags, Some(vec!["kept".to_string(), "new".to_string()]));
+    assert_eq!(prompts[0].categories, Some(vec!["cat1".to_string()]));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_edit_chat_tags_in_editor() -> anyhow::Result<()> {
+    let env = EditorTestEnv::new()?;
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(env.storage_path.clone()))?));
+    let messages = vec![Message { role: "user".to_string(), content: "Quote as\n[assistant]\nverbatim".to_string() }];
+    let mut prompt = Prompt::chat(messages.clone(), None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    // Only the tags change, so the messages are kept rather than parsed from the body.
+    let editor = env.editor(&format!("---\ntags: new\ncategories: \n---\n{}\n", prompt.content))?;
+    let mut cmd = Command::cargo_bin("prompts-cli")?;
+    cmd.env("VISUAL", &editor)
+        .arg("--config")
+        .arg(&env.config_path)
+        .arg("edit")
+        .arg(format!("@{}", &prompt.hash[..12]));
+    in_terminal(&cmd).assert().success();
+
+    let prompts = prompts_api.list_prompts(None).await?;
+    assert_eq!(prompts.len(), 1);
+    assert_eq!(prompts[0].messages, messages);
+    assert_eq!(prompts[0].hash, prompt.hash);
+    assert_eq!(prompts[0].tags, Some(vec!["new".to_string()]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_edit_in_editor_aborts() -> anyhow::Result<()> {
+    let env = EditorTestEnv::new()?;
+    let storage = JsonStorage::new(Some(env.storage_path.clone()))?;
+    let prompts_api = Prompts::new(Box::new(storage));
+    let mut prompt = Prompt::new("A prompt left alone", None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    let mut cmd = Command::cargo_bin("prompts-cli")?;
+    cmd.env("VISUAL", "true")
+        .arg("--config")
+        .arg(&env.config_path)
+        .arg("edit")
+        .arg("prompt left alone");
+    in_terminal(&cmd).assert()
+        .success()
+        .stdout(predicate::str::contains("No changes made, aborting."));
+
+    let editor = env.editor("---\ntags: \ncategories: \n---\n\n")?;
+    let mut cmd = Command::cargo_bin("prompts-cli")?;
+    cmd.env("VISUAL", &editor)
+        .arg("--config")
+        .arg(&env.config_path)
+        .arg("edit")
+        .arg("prompt left alone");
+    in_terminal(&cmd).assert()
+        .success()
+        .stdout(predicate::str::contains("Prompt is empty, aborting."));
+
+    let prompts = prompts_api.list_prompts(None).await?;
+    assert_eq!(prompts.len(), 1);
+    assert_eq!(prompts[0].content, "A prompt left alone");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_edit_reads_piped_text() -> anyhow::Result<()> {
+    let env = EditorTestEnv::new()?;
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(env.storage_path.clone()))?));
+    let mut prompt = Prompt::new("A prompt to replace", None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli")?;
+    cmd.env("VISUAL", "false")
+        .arg("--config")
+        .arg(&env.config_path)
+        .arg("edit")
+        .arg("prompt to replace");
+    cmd.write_stdin("A replaced prompt\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Prompt {} updated.", &prompt.hash[..12])));
+
+    let prompts = prompts_api.list_prompts(None).await?;
+    assert_eq!(prompts.len(), 1);
+    assert_eq!(prompts[0].content, "A replaced prompt");
+    Ok(())
+}
@@ -0,0 +1,192 @@
+use predicates::prelude::*;
+use prompts_cli::{
+    eval::{read_test_cases, run_test_cases, Assertion, TestCase},
+    provider::{MockConfig, MockProvider},
+    storage::{JsonStorage, LibSQLStorage, Storage},
+    template::ComposedTemplate,
+    Prompt, Prompts,
+};
+use std::fs;
+use tempfile::tempdir;
+
+fn contains(value: &str) -> Assertion {
+    Assertion::Contains { value: value.to_string() }
+}
+
+#[test]
+fn test_assertions() {
+    let json = r#"{"name": "Ada", "age": 36}"#;
+    let schema = serde_json::json!({
+        "type": "object",
+        "required": ["name"],
+        "properties": { "age": { "type": "integer" } },
+    });
+
+    assert!(contains("Ada").check(json).is_ok());
+    assert!(contains("Bob").check(json).is_err());
+    assert!(Assertion::NotContains { value: "Bob".to_string() }.check(json).is_ok());
+    assert!(Assertion::Regex { pattern: r#""age": \d+"#.to_string() }.check(json).is_ok());
+    assert!(Assertion::Regex { pattern: "^Ada".to_string() }.check(json).is_err());
+    assert!(Assertion::Regex { pattern: "(".to_string() }.check(json).unwrap_err().starts_with("invalid regex"));
+    assert!(Assertion::Equals { value: "Hello".to_string() }.check("  Hello\n").is_ok());
+    assert_eq!(
+        Assertion::MaxLength { value: 3 }.check("Hello"),
+        Err("expected at most 3 characters, got 5".to_string())
+    );
+    assert!(Assertion::JsonSchema { schema: schema.clone() }.check(json).is_ok());
+    assert!(Assertion::JsonSchema { schema: schema.clone() }.check(r#"{"age": "old"}"#).is_err());
+    assert!(Assertion::JsonSchema { schema }.check("not json").unwrap_err().starts_with("expected the output to be JSON"));
+}
+
+#[test]
+fn test_read_test_cases() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let yaml = dir.path().join("cases.yaml");
+    fs::write(
+        &yaml,
+        "- name: greets\n  variables:\n    name: Ada\n  assertions:\n    - type: contains\n      value: Ada\n    - type: max_length\n      value: 20\n",
+    )?;
+    let cases = read_test_cases(&yaml)?;
+    assert_eq!(cases.len(), 1);
+    assert_eq!(cases[0].variables["name"], "Ada");
+    assert_eq!(cases[0].assertions, vec![contains("Ada"), Assertion::MaxLength { value: 20 }]);
+
+    let toml = dir.path().join("cases.toml");
+    fs::write(
+        &toml,
+        "[[cases]]\nname = \"greets\"\nvariables = { name = \"Ada\" }\nassertions = [{ type = \"contains\", value = \"Ada\" }, { type = \"max_length\", value = 20 }]\n",
+    )?;
+    assert_eq!(read_test_cases(&toml)?, cases);
+
+    let duplicate = dir.path().join("duplicate.json");
+    fs::write(&duplicate, r#"[{"name": "a"}, {"name": "a"}]"#)?;
+    assert!(read_test_cases(&duplicate).unwrap_err().to_string().contains("more than once"));
+
+    let unknown = dir.path().join("unknown.json");
+    fs::write(&unknown, r#"[{"name": "a", "assertions": [{"type": "shorter_than"}]}]"#)?;
+    assert!(read_test_cases(&unknown).is_err());
+    Ok(())
+}
+
+fn case(name: &str, who: Option<&str>, assertions: Vec<Assertion>) -> TestCase {
+    let mut variables = serde_json::Map::new();
+    if let Some(who) = who {
+        variables.insert("name".to_string(), who.into());
+    }
+    TestCase { name: name.to_string(), variables, assertions }
+}
+
+#[tokio::test]
+async fn test_run_test_cases() -> anyhow::Result<()> {
+    let prompt = Prompt::new("Hello, {{ name }}!", None, None);
+    let template = ComposedTemplate::new(&prompt, &[])?;
+    let cases = vec![
+        case("passes", Some("Ada"), vec![contains("Ada")]),
+        case("fails", Some("Bob"), vec![contains("Ada"), Assertion::MaxLength { value: 5 }]),
+        case("missing", None, vec![]),
+    ];
+
+    let results = run_test_cases(&template, &cases, None).await?;
+    assert!(results[0].passed());
+    assert_eq!(results[0].output.as_deref(), Some("Hello, Ada!"));
+    assert_eq!(results[1].failures.len(), 2);
+    assert_eq!(results[2].failures, vec!["Missing template variables: name".to_string()]);
+
+    let provider = MockProvider::new(MockConfig { response: Some("{\"ok\": true}".to_string()) });
+    let cases = vec![case(
+        "json",
+        Some("Ada"),
+        vec![Assertion::JsonSchema { schema: serde_json::json!({ "required": ["ok"] }) }],
+    )];
+    let results = run_test_cases(&template, &cases, Some(&provider)).await?;
+    assert!(results[0].passed(), "{:?}", results[0].failures);
+    assert_eq!(results[0].output.as_deref(), Some("{\"ok\": true}"));
+    Ok(())
+}
+
+async fn test_store_test_cases_impl(storage_type: &str) -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage: Box<dyn Storage + Send + Sync> = if storage_type == "json" {
+        Box::new(JsonStorage::new(Some(dir.path().to_path_buf()))?)
+    } else {
+        Box::new(LibSQLStorage::new(Some(dir.path().join("test.db"))).await?)
+    };
+    let prompts_api = Prompts::new(storage);
+    let mut prompt = Prompt::new("Hello, {{ name }}!", None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+    assert!(prompts_api.list_prompts(None).await?[0].tests.is_empty());
+
+    let cases = vec![case("greets", Some("Ada"), vec![contains("Ada")])];
+    prompts_api.set_tests(&prompt.hash, cases.clone()).await?;
+    prompts_api
+        .edit_prompt(&prompt.hash, Some("Hi, {{ name }}!".to_string()), Some(vec!["t".to_string()]), None, None, None)
+        .await?;
+
+    let prompts = prompts_api.list_prompts(None).await?;
+    assert_eq!(prompts[0].tests, cases);
+    assert_eq!(prompts_api.show_prompt("Hi", None).await?[0].tests, cases);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_store_test_cases_json() -> anyhow::Result<()> {
+    test_store_test_cases_impl("json").await
+}
+
+#[tokio::test]
+async fn test_store_test_cases_libsql() -> anyhow::Result<()> {
+    test_store_test_cases_impl("libsql").await
+}
+
+#[tokio::test]
+async fn test_cli_test_command() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage_path = dir.path().join("prompts");
+    fs::create_dir_all(&storage_path)?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(&config_path, format!("[storage]\npath = {:?}\n", storage_path.to_string_lossy()))?;
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(storage_path))?));
+    let mut prompt = Prompt::new("Summarise {{ topic }} briefly", None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    let cli = |args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli").unwrap();
+        cmd.arg("--config").arg(&config_path).args(args);
+        cmd
+    };
+
+    cli(&["test", "run", "Summarise"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("has no test cases"));
+
+    let cases = dir.path().join("cases.yaml");
+    fs::write(
+        &cases,
+        "- name: mentions topic\n  variables: {topic: rust}\n  assertions:\n    - {type: contains, value: rust}\n- name: too strict\n  variables: {topic: go}\n  assertions:\n    - {type: max_length, value: 10}\n",
+    )?;
+    cli(&["test", "set", "Summarise", "--file", cases.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!("Saved 2 test cases for prompt {}.", &prompt.hash[..12])));
+
+    cli(&["test", "show", "Summarise"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("name: mentions topic"));
+
+    cli(&["test", "run", "Summarise", "--provider", "mock"])
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("PASS mentions topic"))
+        .stdout(predicates::str::contains("FAIL too strict\n  - expected at most 10 characters, got 20"))
+        .stdout(predicates::str::contains("2 tests, 1 passed, 1 failed"))
+        .stderr(predicates::str::contains("1 of 2 tests failed"));
+
+    cli(&["test", "run", "Summarise", "--case", "mentions topic"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("1 tests, 1 passed, 0 failed").and(predicates::str::contains("too strict").not()));
+
+    Ok(())
+}
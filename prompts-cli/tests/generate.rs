@@ -63,3 +63,164 @@ async fn test_generate_command() -> anyhow::Result<()> {
     Ok(())
 }
 
+
+#[tokio::test]
+async fn test_generate_reports_all_missing_variables() -> anyhow::Result<()> {
+    let env = CliTestEnv::new()?;
+    let storage = JsonStorage::new(Some(env.storage_path.to_path_buf()))?;
+    let prompts_api = Prompts::new(Box::new(storage));
+
+    let mut prompt = Prompt::new("Dear {{ title }} {{ name }}, {% for i in items %}{{ i }}{% endfor %}", None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    let mut cmd = assert_cmd::Command::cargo_bin(r#"prompts-cli"#)?;
+    cmd.arg("--config")
+        .arg(&env.config_path)
+        .arg("generate")
+        .arg("Dear")
+        .arg("--variables")
+        .arg("name=World");
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("Missing template variables: items, title"));
+
+    let mut cmd = assert_cmd::Command::cargo_bin(r#"prompts-cli"#)?;
+    cmd.arg("--config")
+        .arg(&env.config_path)
+        .arg("--output")
+        .arg("json")
+        .arg("generate")
+        .arg("Dear");
+    let output = cmd.output()?;
+    assert!(!output.status.success());
+    let error: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(error, serde_json::json!({ "MissingVariables": ["items", "name", "title"] }));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_vars_command() -> anyhow::Result<()> {
+    let env = CliTestEnv::new()?;
+    let storage = JsonStorage::new(Some(env.storage_path.to_path_buf()))?;
+    let prompts_api = Prompts::new(Box::new(storage));
+
+    let mut prompt = Prompt::new("Hi {{ name }}{% if formal %}, {{ title }}{% endif %}", None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    let mut cmd = assert_cmd::Command::cargo_bin(r#"prompts-cli"#)?;
+    cmd.arg("--config").arg(&env.config_path).arg("vars").arg("Hi");
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::diff("formal (optional)\nname\ntitle\n"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_generate_with_includes() -> anyhow::Result<()> {
+    let env = CliTestEnv::new()?;
+    let storage = JsonStorage::new(Some(env.storage_path.to_path_buf()))?;
+    let prompts_api = Prompts::new(Box::new(storage));
+
+    let mut signature = Prompt::new("Thanks, {{ sender }}", None, None);
+    prompts_api.add_prompt(&mut signature).await?;
+    let mut letter = Prompt::new(
+        &format!("Dear {{{{ name }}}},\n{{% include \"{}\" %}}", &signature.hash[..12]),
+        None,
+        None,
+    );
+    prompts_api.add_prompt(&mut letter).await?;
+
+    let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli")?;
+    cmd.arg("--config")
+        .arg(&env.config_path)
+        .arg("generate")
+        .arg("Dear")
+        .arg("--explain")
+        .arg("-v")
+        .arg("name=Ada")
+        .arg("-v")
+        .arg("sender=Bob");
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("Dear Ada,\nThanks, Bob"))
+        .stderr(predicates::str::contains(format!(
+            "  include \"{}\" -> {} - Thanks, {{{{ sender }}}}",
+            &signature.hash[..12],
+            &signature.hash[..12]
+        )));
+
+    let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli")?;
+    cmd.arg("--config")
+        .arg(&env.config_path)
+        .arg("--output")
+        .arg("json")
+        .arg("generate")
+        .arg("Dear")
+        .arg("-v")
+        .arg("name=Ada");
+    cmd.assert()
+        .failure()
+        .stdout(predicates::str::contains(r#"{"MissingVariables":["sender"]}"#));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_generate_with_variable_sources() -> anyhow::Result<()> {
+    let env = CliTestEnv::new()?;
+    let storage = JsonStorage::new(Some(env.storage_path.to_path_buf()))?;
+    let prompts_api = Prompts::new(Box::new(storage));
+
+    let mut prompt = Prompt::new(
+        "Review for {{ team.name }} ({{ tone }}):{% for rule in team.rules %} {{ rule }};{% endfor %}\n{{ code }}",
+        None,
+        None,
+    );
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    let vars_path = env.storage_path.join("vars.toml");
+    fs::write(&vars_path, "tone = \"from file\"\n[team]\nname = \"core\"\nrules = [\"tests\", \"docs\"]\n")?;
+    let code_path = env.storage_path.join("main.rs");
+    fs::write(&code_path, "fn main() {}")?;
+
+    let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli")?;
+    cmd.env("REVIEW_TONE", "from env")
+        .arg("--config")
+        .arg(&env.config_path)
+        .arg("generate")
+        .arg("Review")
+        .arg("--vars-file")
+        .arg(&vars_path)
+        .arg("--env-prefix")
+        .arg("REVIEW_")
+        .arg("--var-from-file")
+        .arg(format!("code={}", code_path.display()));
+    // Variables that are not valid Unicode are skipped rather than failing the render.
+    #[cfg(unix)]
+    cmd.env("REVIEW_RAW", <std::ffi::OsStr as std::os::unix::ffi::OsStrExt>::from_bytes(b"\xff"));
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("Review for core (from env): tests; docs;\nfn main() {}"));
+
+    let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli")?;
+    cmd.env("REVIEW_TONE", "from env")
+        .arg("--config")
+        .arg(&env.config_path)
+        .arg("generate")
+        .arg("Review")
+        .arg("--vars-file")
+        .arg(&vars_path)
+        .arg("--env-prefix")
+        .arg("REVIEW_")
+        .arg("-v")
+        .arg("tone=from flag")
+        .arg("-v")
+        .arg("code=inline");
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("(from flag)"));
+
+    Ok(())
+}
@@ -0,0 +1,231 @@
+use prompts_cli::{GitStorage, Prompt, Prompts, Run, Storage};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output().unwrap();
+    assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Opens a git storage in `dir` that shares prompts through `remote` as `origin`.
+async fn clone_of(remote: &Path, dir: &Path) -> anyhow::Result<GitStorage> {
+    let storage = GitStorage::new(Some(dir.to_path_buf())).await?;
+    git(dir, &["remote", "add", "origin", &remote.to_string_lossy()]);
+    Ok(storage)
+}
+
+fn bare_remote(dir: &Path) -> std::path::PathBuf {
+    let remote = dir.join("remote.git");
+    git(dir, &["init", "--quiet", "--bare", "remote.git"]);
+    remote
+}
+
+#[tokio::test]
+async fn test_git_commits_every_change() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let prompts_api = Prompts::new(Box::new(GitStorage::new(Some(dir.path().to_path_buf())).await?));
+    let mut prompt = Prompt::new("Review this code", None, None);
+    prompt.name = Some("review".to_string());
+    prompts_api.add_prompt(&mut prompt).await?;
+    prompts_api.edit_prompt(&prompt.hash, Some("Review this code carefully".to_string()), None, None, None, None).await?;
+    let edited = prompts_api.find_by_name("review").await?.unwrap();
+    prompts_api.delete_prompt(&edited.hash).await?;
+
+    let log = git(dir.path(), &["log", "--format=%s", "--reverse"]);
+    let subjects: Vec<&str> = log.lines().collect();
+    assert_eq!(
+        subjects,
+        vec![
+            "Keep recorded runs out of git",
+            "Save prompt `review`: Review this code",
+            "Save prompt `review`: Review this code carefully",
+            "Move prompt `review`: Review this code carefully to the trash",
+        ]
+    );
+    assert_eq!(git(dir.path(), &["status", "--porcelain"]), "");
+
+    // Each use is committed, and runs are never committed.
+    prompts_api.restore_prompt(&edited.hash).await?;
+    prompts_api.record_use(&edited.hash).await?;
+    let storage = GitStorage::new(Some(dir.path().to_path_buf())).await?;
+    let run = Run {
+        id: "run-1".to_string(),
+        prompt_id: edited.id.clone(),
+        prompt_hash: edited.hash.clone(),
+        input: edited.content.clone(),
+        variables: serde_json::json!({}),
+        provider: "mock".to_string(),
+        model: "echo".to_string(),
+        output: edited.content.clone(),
+        latency_ms: 0,
+        usage: None,
+        created_at: chrono::Utc::now(),
+    };
+    storage.save_run(&run).await?;
+    let log = git(dir.path(), &["log", "-2", "--format=%s", "--reverse"]);
+    assert_eq!(
+        log.lines().collect::<Vec<_>>(),
+        vec![
+            "Restore prompt `review`: Review this code carefully from the trash",
+            "Record a use of prompt `review`: Review this code carefully",
+        ]
+    );
+    assert_eq!(git(dir.path(), &["status", "--porcelain"]), "");
+
+    // Deleting a prompt for good outside of an edit is a change of its own.
+    storage.delete_prompt(&edited.hash).await?;
+    assert_eq!(git(dir.path(), &["log", "-1", "--format=%s"]).trim(), "Delete prompt `review`: Review this code carefully");
+    assert_eq!(git(dir.path(), &["status", "--porcelain"]), "");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_git_ignores_runs_alone() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    git(dir.path(), &["init", "--quiet"]);
+    git(dir.path(), &["config", "user.name", "test"]);
+    git(dir.path(), &["config", "user.email", "test@localhost"]);
+    fs::create_dir_all(dir.path().join("runs"))?;
+    fs::write(dir.path().join("runs").join("run-1.json"), "{}")?;
+    git(dir.path(), &["add", "--", "runs"]);
+    git(dir.path(), &["commit", "--quiet", "-m", "Track runs"]);
+    fs::write(dir.path().join("notes.txt"), "Not a prompt")?;
+
+    GitStorage::new(Some(dir.path().to_path_buf())).await?;
+    let changed = git(dir.path(), &["show", "--name-status", "--format=%s", "HEAD"]);
+    assert_eq!(changed, "Keep recorded runs out of git\n\nA\t.gitignore\nD\truns/run-1.json\n");
+    assert_eq!(git(dir.path(), &["status", "--porcelain"]), "?? notes.txt\n");
+    assert!(dir.path().join("runs").join("run-1.json").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_git_pull_and_push() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let remote = bare_remote(dir.path());
+    let ours = clone_of(&remote, &dir.path().join("ours")).await?;
+    let theirs = clone_of(&remote, &dir.path().join("theirs")).await?;
+    let branch = git(&dir.path().join("ours"), &["symbolic-ref", "--short", "HEAD"]);
+    let branch = Some(branch.trim());
+
+    let mut review = Prompt::new("Review this code", None, None);
+    review.name = Some("review".to_string());
+    let ours_api = Prompts::new(Box::new(ours));
+    ours_api.add_prompt(&mut review).await?;
+    let ours = GitStorage::new(Some(dir.path().join("ours"))).await?;
+    ours.push("origin", branch).await?;
+
+    let report = theirs.pull("origin", branch).await?;
+    assert!(report.updated);
+    assert!(report.kept_both.is_empty());
+    assert_eq!(theirs.load_prompts().await?.len(), 1);
+    assert!(!theirs.pull("origin", branch).await?.updated);
+
+    // Both sides change the same prompt, and add another one with the same name.
+    let theirs_api = Prompts::new(Box::new(theirs));
+    theirs_api.edit_prompt(&review.hash, Some("Review this code for bugs".to_string()), None, None, None, None).await?;
+    let mut summary = Prompt::new("Summarise this", None, None);
+    summary.name = Some("summary".to_string());
+    theirs_api.add_prompt(&mut summary).await?;
+    let theirs = GitStorage::new(Some(dir.path().join("theirs"))).await?;
+    theirs.push("origin", branch).await?;
+
+    ours_api.edit_prompt(&review.hash, Some("Review this code for style".to_string()), None, None, None, None).await?;
+    let mut other_summary = Prompt::new("Summarise this briefly", None, None);
+    other_summary.name = Some("summary".to_string());
+    ours_api.add_prompt(&mut other_summary).await?;
+    let err = ours.push("origin", branch).await.unwrap_err();
+    assert!(err.to_string().contains("run `prompts-cli pull` first"), "{}", err);
+
+    let report = ours.pull("origin", branch).await?;
+    assert!(report.updated);
+    assert_eq!(report.kept_both.len(), 2);
+    let ours_dir = dir.path().join("ours");
+    assert_eq!(git(&ours_dir, &["status", "--porcelain"]), "");
+    assert!(git(&ours_dir, &["log", "-1", "--format=%s"]).starts_with("Merge origin/"));
+
+    let prompts = ours.load_prompts().await?;
+    assert_eq!(prompts.len(), 4);
+    let mine = prompts.iter().find(|p| p.content == "Review this code for style").unwrap();
+    let remote_edit = prompts.iter().find(|p| p.content == "Review this code for bugs").unwrap();
+    assert_eq!((mine.id.as_str(), mine.name.as_deref()), (review.hash.as_str(), Some("review")));
+    assert_eq!(remote_edit.id, remote_edit.hash);
+    assert_eq!(remote_edit.name, Some(format!("review-{}", &remote_edit.hash[..8])));
+    let history = ours.load_revisions(&remote_edit.id).await?;
+    assert_eq!(history.last().unwrap().content, "Review this code for bugs");
+    assert_eq!(ours.load_revisions(&mine.id).await?.last().unwrap().content, "Review this code for style");
+    let summaries: Vec<_> = prompts.iter().filter_map(|p| p.name.as_deref()).filter(|n| n.starts_with("summary")).collect();
+    assert_eq!(summaries.len(), 2);
+
+    ours.push("origin", branch).await?;
+    assert_eq!(theirs.pull("origin", branch).await?.kept_both, Vec::<String>::new());
+    assert_eq!(theirs.load_prompts().await?.len(), 4);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_git_pull_combines_metadata() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let remote = bare_remote(dir.path());
+    let ours_api = Prompts::new(Box::new(clone_of(&remote, &dir.path().join("ours")).await?));
+    let theirs_api = Prompts::new(Box::new(clone_of(&remote, &dir.path().join("theirs")).await?));
+    let ours = GitStorage::new(Some(dir.path().join("ours"))).await?;
+    let theirs = GitStorage::new(Some(dir.path().join("theirs"))).await?;
+    let branch = git(&dir.path().join("ours"), &["symbolic-ref", "--short", "HEAD"]);
+    let branch = Some(branch.trim());
+
+    let mut review = Prompt::new("Review this code", Some(vec!["code".to_string()]), None);
+    ours_api.add_prompt(&mut review).await?;
+    ours.push("origin", branch).await?;
+    theirs.pull("origin", branch).await?;
+
+    // Both sides change the tags of the same prompt, which lives in one file, and use it.
+    theirs_api.edit_prompt(&review.hash, None, Some(vec!["remote".to_string()]), None, None, None).await?;
+    theirs_api.record_use(&review.hash).await?;
+    theirs.push("origin", branch).await?;
+    ours_api.record_use(&review.hash).await?;
+    ours_api.edit_prompt(&review.hash, None, Some(vec!["local".to_string()]), Some(vec!["code".to_string()]), None, None).await?;
+
+    let report = ours.pull("origin", branch).await?;
+    assert_eq!(report.kept_both, vec![review.hash.clone()]);
+    assert_eq!(git(&dir.path().join("ours"), &["status", "--porcelain"]), "");
+    let prompts = ours.load_prompts().await?;
+    assert_eq!(prompts.len(), 1);
+    let mut tags = prompts[0].tags.clone().unwrap_or_default();
+    tags.sort();
+    assert_eq!(tags, vec!["local", "remote"]);
+    assert_eq!(prompts[0].use_count, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_pull_and_push() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let remote = bare_remote(dir.path());
+    let storage_path = dir.path().join("prompts");
+    clone_of(&remote, &storage_path).await?;
+    let branch = git(&storage_path, &["symbolic-ref", "--short", "HEAD"]);
+    let config_path = dir.path().join("config.toml");
+    fs::write(
+        &config_path,
+        format!("[storage]\ntype = \"git\"\npath = {:?}\nbranch = {:?}\n", storage_path.to_string_lossy(), branch.trim()),
+    )?;
+
+    let cli = |args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli").unwrap();
+        cmd.arg("--config").arg(&config_path).args(args);
+        cmd
+    };
+
+    cli(&["add", "Summarise {{ text }}"]).assert().success();
+    cli(&["push"]).assert().success().stdout("Pushed to origin.\n");
+    cli(&["pull"]).assert().success().stdout("Already up to date.\n");
+    assert_eq!(git(&remote, &["log", "--format=%s", "-1", branch.trim()]).trim(), format!("Save prompt {}: Summarise {{{{ text }}}}", &Prompt::new("Summarise {{ text }}", None, None).hash[..12]));
+
+    fs::write(&config_path, format!("[storage]\npath = {:?}\n", storage_path.to_string_lossy()))?;
+    cli(&["pull"]).assert().failure().stderr(predicates::str::contains("need storage.type to be `git`"));
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use prompts_cli::{Prompt, Prompts, provider::Message, storage::{JsonStorage, LibSQLStorage, Storage}};
+use std::fs;
+use std::process::Command;
+use tempfile::tempdir;
+use toml::Value;
+
+async fn make_storage(storage_type: &str, dir: &std::path::Path) -> anyhow::Result<Box<dyn Storage + Send + Sync>> {
+    Ok(if storage_type == "json" {
+        Box::new(JsonStorage::new(Some(dir.to_path_buf()))?)
+    } else {
+        Box::new(LibSQLStorage::new(Some(dir.join("test.db"))).await?)
+    })
+}
+
+async fn test_history_impl(storage_type: &str) -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let prompts_api = Prompts::new(make_storage(storage_type, dir.path()).await?);
+
+    let mut prompt = Prompt::new("first version", None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+    let id = prompt.id.clone();
+    assert_eq!(id, prompt.hash);
+
+    prompts_api.edit_prompt(&prompt.hash, Some("second version".to_string()), None, None, None, None).await?;
+    let edited = prompts_api.list_prompts(None).await?;
+    assert_eq!(edited.len(), 1);
+    assert_eq!(edited[0].id, id);
+    assert_ne!(edited[0].hash, id);
+    // The id keeps selecting the prompt after its hash changed
+    assert_eq!(prompts_api.find_by_hash(&id[..12]).await?.hash, edited[0].hash);
+    assert_eq!(prompts_api.show_prompt(&format!("@{}", &id[..12]), None).await?.len(), 1);
+
+    // Editing only the tags does not create a new revision
+    prompts_api.edit_prompt(&edited[0].hash, None, Some(vec!["tag1".to_string()]), None, None, None).await?;
+
+    let history = prompts_api.history(&id).await?;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].number, 1);
+    assert_eq!(history[0].content, "first version");
+    assert_eq!(history[0].hash, id);
+    assert_eq!(history[1].number, 2);
+    assert_eq!(history[1].content, "second version");
+
+    prompts_api.revert_prompt(&id, 1).await?;
+    let reverted = prompts_api.list_prompts(None).await?;
+    assert_eq!(reverted.len(), 1);
+    assert_eq!(reverted[0].id, id);
+    assert_eq!(reverted[0].content, "first version");
+    assert_eq!(reverted[0].tags, Some(vec!["tag1".to_string()]));
+
+    let history = prompts_api.history(&id).await?;
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[2].content, "first version");
+
+    assert!(prompts_api.revert_prompt(&id, 7).await.is_err());
+
+    Ok(())
+}
+
+async fn test_chat_history_impl(storage_type: &str) -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let prompts_api = Prompts::new(make_storage(storage_type, dir.path()).await?);
+
+    // A message that reads like a transcript with a role header in it.
+    let quote = vec![Message { role: "user".to_string(), content: "Quote as\n[assistant]\nverbatim".to_string() }];
+    let mut prompt = Prompt::chat(quote.clone(), None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+    let brief = vec![Message { role: "user".to_string(), content: "Be brief".to_string() }];
+    prompts_api.set_messages(&prompt.hash, brief).await?;
+
+    let history = prompts_api.history(&prompt.id).await?;
+    assert_eq!(history[0].messages, quote);
+
+    prompts_api.revert_prompt(&prompt.id, 1).await?;
+    let reverted = prompts_api.list_prompts(None).await?;
+    assert_eq!(reverted.len(), 1);
+    assert_eq!(reverted[0].messages, quote);
+    assert_eq!(reverted[0].hash, prompt.hash);
+    assert_eq!(prompts_api.history(&prompt.id).await?.len(), 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_history_json() -> anyhow::Result<()> {
+    test_chat_history_impl("json").await
+}
+
+#[tokio::test]
+async fn test_chat_history_libsql() -> anyhow::Result<()> {
+    test_chat_history_impl("libsql").await
+}
+
+#[tokio::test]
+async fn test_history_json() -> anyhow::Result<()> {
+    test_history_impl("json").await
+}
+
+#[tokio::test]
+async fn test_history_libsql() -> anyhow::Result<()> {
+    test_history_impl("libsql").await
+}
+
+#[tokio::test]
+async fn test_history_legacy_prompt() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let legacy = r#"{"content": "legacy prompt", "tags": null, "categories": null, "hash": "abc123"}"#;
+    fs::write(dir.path().join("abc123.json"), legacy)?;
+
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(dir.path().to_path_buf()))?));
+    let prompts = prompts_api.list_prompts(None).await?;
+    assert_eq!(prompts[0].id, "abc123");
+
+    let history = prompts_api.history("abc123").await?;
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].content, "legacy prompt");
+
+    prompts_api.edit_prompt("abc123", Some("updated legacy prompt".to_string()), None, None, None, None).await?;
+    let history = prompts_api.history("abc123").await?;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].content, "legacy prompt");
+    assert_eq!(history[1].content, "updated legacy prompt");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_history_and_revert() -> anyhow::Result<()> {
+    let config_dir = tempdir()?;
+    let config_path = config_dir.path().join("config.toml");
+    let storage_dir = tempdir()?;
+
+    let mut config = toml::map::Map::new();
+    let mut storage_config = toml::map::Map::new();
+    storage_config.insert(
+        "path".to_string(),
+        Value::String(storage_dir.path().to_string_lossy().into_owned()),
+    );
+    config.insert("storage".to_string(), Value::Table(storage_config));
+    fs::write(&config_path, toml::to_string(&config)?)?;
+
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(storage_dir.path().to_path_buf()))?));
+    let mut prompt = Prompt::new("Summarize the text", None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+    prompts_api.edit_prompt(&prompt.hash, Some("Summarize the text briefly".to_string()), None, None, None, None).await?;
+
+    let mut cmd = Command::cargo_bin("prompts-cli")?;
+    cmd.arg("--config").arg(&config_path).arg("list");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(format!(" @{} - Summarize the text briefly", &prompt.id[..12])));
+
+    let mut cmd = Command::cargo_bin("prompts-cli")?;
+    cmd.arg("--config").arg(&config_path).arg("show").arg(format!("@{}", &prompt.id[..12]));
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::diff("Summarize the text briefly\n"));
+
+    let mut cmd = Command::cargo_bin("prompts-cli")?;
+    cmd.arg("--config").arg(&config_path).arg("history").arg("Summarize");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Prompt {}", &prompt.id[..12])))
+        .stdout(predicate::str::contains(format!("  1 {} - Summarize the text", &prompt.hash[..12])))
+        .stdout(predicate::str::contains("* 2"));
+
+    let mut cmd = Command::cargo_bin("prompts-cli")?;
+    cmd.arg("--config").arg(&config_path).arg("show").arg("Summarize").arg("--revision").arg("1");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::diff("Summarize the text\n"));
+
+    let mut cmd = Command::cargo_bin("prompts-cli")?;
+    cmd.arg("--config").arg(&config_path).arg("revert").arg("Summarize").arg("--revision").arg("1");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Prompt {} reverted to revision 1.", &prompt.id[..12])));
+
+    let prompts = prompts_api.list_prompts(None).await?;
+    assert_eq!(prompts.len(), 1);
+    assert_eq!(prompts[0].content, "Summarize the text");
+    assert_eq!(prompts[0].id, prompt.id);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_libsql_database_without_ids() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let db_path = dir.path().join("prompts.db");
+    {
+        // The schema before prompts had ids.
+        let conn = libsql::Builder::new_local(&db_path).build().await?.connect()?;
+        conn.execute(
+            "CREATE TABLE prompts (hash TEXT PRIMARY KEY, content TEXT NOT NULL, tags TEXT, categories TEXT)",
+            (),
+        ).await?;
+        conn.execute(
+            "INSERT INTO prompts (hash, content, tags, categories) VALUES ('abc123', 'An old prompt', '[]', '[]')",
+            (),
+        ).await?;
+    }
+
+    let prompts_api = Prompts::new(Box::new(LibSQLStorage::new(Some(db_path)).await?));
+    let prompts = prompts_api.list_prompts(None).await?;
+    assert_eq!(prompts.len(), 1);
+    assert_eq!(prompts[0].id, "abc123");
+
+    prompts_api.edit_prompt("abc123", Some("An old prompt, edited".to_string()), None, None, None, None).await?;
+    let history = prompts_api.history("abc123").await?;
+    assert_eq!(history.len(), 2);
+    assert_eq!(prompts_api.list_prompts(None).await?[0].id, "abc123");
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use chrono::{TimeZone, Utc};
+use predicates::prelude::*;
+use prompts_cli::{
+    core::{parse_date, sort_by_time},
+    storage::{JsonStorage, LibSQLStorage, Storage},
+    Prompt, PromptTime, Prompts,
+};
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_old_json_prompt_loads() -> anyhow::Result<()> {
+    let prompt: Prompt = serde_json::from_str(
+        r#"{"id": "abc", "content": "Hello", "tags": null, "categories": null, "hash": "abc"}"#,
+    )?;
+    assert_eq!(prompt.created_at, None);
+    assert_eq!(prompt.use_count, 0);
+    let json = serde_json::to_value(&prompt)?;
+    assert!(json.get("use_count").is_none());
+    assert!(json.get("author").is_none());
+    Ok(())
+}
+
+#[test]
+fn test_sort_and_parse_dates() {
+    let day = |d| Some(Utc.with_ymd_and_hms(2024, 5, d, 0, 0, 0).unwrap());
+    let mut prompts: Vec<Prompt> = ["a", "b", "c"].iter().map(|c| Prompt::new(c, None, None)).collect();
+    prompts[0].last_used_at = day(1);
+    prompts[2].last_used_at = day(3);
+    sort_by_time(&mut prompts, PromptTime::Used);
+    let contents: Vec<&str> = prompts.iter().map(|p| p.content.as_str()).collect();
+    assert_eq!(contents, vec!["c", "a", "b"]);
+
+    assert_eq!(parse_date("2024-05-02").ok(), day(2));
+    assert_eq!(parse_date("2024-05-02T02:00:00+02:00").ok(), day(2));
+    assert!(parse_date("yesterday").is_err());
+    assert!("used".parse::<PromptTime>().is_ok());
+    assert!("touched".parse::<PromptTime>().is_err());
+}
+
+async fn test_metadata_impl(storage_type: &str) -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage: Box<dyn Storage + Send + Sync> = if storage_type == "json" {
+        Box::new(JsonStorage::new(Some(dir.path().to_path_buf()))?)
+    } else {
+        Box::new(LibSQLStorage::new(Some(dir.path().join("test.db"))).await?)
+    };
+    let prompts_api = Prompts::new(storage);
+    let mut prompt = Prompt::new("Hello", None, None);
+    prompt.author = Some("Ada".to_string());
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    let added = prompts_api.list_prompts(None).await?.remove(0);
+    assert_eq!(added.author.as_deref(), Some("Ada"));
+    // LibSQL keeps timestamps to the millisecond.
+    assert_eq!(
+        added.created_at.map(|t| t.timestamp_millis()),
+        prompt.created_at.map(|t| t.timestamp_millis())
+    );
+    assert_eq!(added.updated_at, added.created_at);
+    assert_eq!((added.use_count, added.last_used_at), (0, None));
+
+    prompts_api.record_use(&prompt.hash).await?;
+    prompts_api.record_use(&prompt.hash).await?;
+    let used = prompts_api.list_prompts(None).await?.remove(0);
+    assert_eq!(used.use_count, 2);
+    assert!(used.last_used_at >= added.created_at);
+
+    prompts_api
+        .edit_prompt(&prompt.hash, Some("Hello there".to_string()), None, None, None, None)
+        .await?;
+    let edited = prompts_api.list_prompts(None).await?.remove(0);
+    assert_eq!(edited.created_at, added.created_at);
+    assert!(edited.updated_at >= used.last_used_at);
+    assert_eq!(edited.author.as_deref(), Some("Ada"));
+    assert_eq!(edited.use_count, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_metadata_json() -> anyhow::Result<()> {
+    test_metadata_impl("json").await
+}
+
+#[tokio::test]
+async fn test_metadata_libsql() -> anyhow::Result<()> {
+    test_metadata_impl("libsql").await
+}
+
+#[tokio::test]
+async fn test_cli_list_by_time() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage_path = dir.path().join("prompts");
+    fs::create_dir_all(&storage_path)?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(
+        &config_path,
+        format!("author = \"Ada\"\n\n[storage]\npath = {:?}\n", storage_path.to_string_lossy()),
+    )?;
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(storage_path.clone()))?));
+    let mut old = Prompt::new("An old prompt", None, None);
+    old.created_at = Some(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap());
+    prompts_api.add_prompt(&mut old).await?;
+    // Saved by a version that did not record when prompts were added.
+    JsonStorage::new(Some(storage_path.clone()))?.save_prompt(&mut Prompt::new("A legacy prompt", None, None)).await?;
+
+    let cli = |args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli").unwrap();
+        cmd.arg("--config").arg(&config_path).args(args);
+        cmd
+    };
+
+    cli(&["add", "A new prompt"]).assert().success();
+    cli(&["generate", "old prompt"]).assert().success();
+
+    let new = Prompt::new("A new prompt", None, None);
+    let stored: Prompt = serde_json::from_str(&fs::read_to_string(storage_path.join(format!("{}.json", new.hash)))?)?;
+    assert_eq!(stored.author.as_deref(), Some("Ada"));
+    assert!(stored.created_at.is_some());
+
+    cli(&["list", "--since", "2024-01-01"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("A new prompt").and(predicates::str::contains("An old prompt").not()));
+    cli(&["list", "--sort", "created", "--before", "2024-01-01"])
+        .assert()
+        .success()
+        .stdout(
+            predicates::str::contains("An old prompt")
+                .and(predicates::str::contains("A new prompt").not())
+                .and(predicates::str::contains("A legacy prompt").not()),
+        );
+    cli(&["list", "--sort", "used", "--before", "2020-01-01"])
+        .assert()
+        .success()
+        .stdout(
+            predicates::str::contains("A new prompt")
+                .and(predicates::str::contains("A legacy prompt"))
+                .and(predicates::str::contains("An old prompt").not()),
+        );
+    cli(&["list", "--sort", "used"])
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match("(?s)An old prompt.*A new prompt")?);
+    cli(&["list", "--sort", "created"])
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match("(?s)A new prompt.*An old prompt")?);
+    cli(&["list", "--since", "last week"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("invalid date"));
+    Ok(())
+}
@@ -0,0 +1,194 @@
+use chrono::Utc;
+use predicates::prelude::*;
+use prompts_cli::{
+    migrate::{migrate, StorageSpec},
+    provider::Message,
+    storage::{JsonStorage, LibSQLStorage, Storage},
+    Prompt, Prompts, Run,
+};
+use std::fs;
+use tempfile::tempdir;
+
+fn run(id: &str, prompt: &Prompt) -> Run {
+    Run {
+        id: id.to_string(),
+        prompt_id: prompt.id.clone(),
+        prompt_hash: prompt.hash.clone(),
+        input: prompt.content.clone(),
+        variables: serde_json::json!({}),
+        provider: "mock".to_string(),
+        model: "mock".to_string(),
+        output: "Done".to_string(),
+        latency_ms: 5,
+        usage: None,
+        created_at: Utc::now(),
+    }
+}
+
+#[test]
+fn test_storage_spec() {
+    let spec: StorageSpec = "libsql:/tmp/prompts.db".parse().unwrap();
+    assert_eq!(spec.kind, "libsql");
+    assert_eq!(spec.to_string(), "libsql:/tmp/prompts.db");
+    assert!("json:".parse::<StorageSpec>().is_err());
+    assert!("yaml:/tmp/prompts".parse::<StorageSpec>().unwrap_err().contains("unknown storage type"));
+}
+
+#[tokio::test]
+async fn test_migrate_between_backends() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    fs::create_dir_all(dir.path().join("json"))?;
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(dir.path().join("json")))?));
+    let mut review = Prompt::new("Review this {{ language }} code", Some(vec!["code".to_string()]), None);
+    review.name = Some("review".to_string());
+    review.author = Some("Ada".to_string());
+    let mut chat = Prompt::chat(
+        vec![Message { role: "system".to_string(), content: "Be brief.".to_string() }],
+        None,
+        None,
+    );
+    let mut old = Prompt::new("An old prompt", None, None);
+    for prompt in [&mut review, &mut chat, &mut old] {
+        prompts_api.add_prompt(prompt).await?;
+    }
+    prompts_api.edit_prompt(&review.hash, Some("Review this {{ language }} code carefully".to_string()), None, None, None, None).await?;
+    prompts_api.record_use(&old.hash).await?;
+    prompts_api.delete_prompt(&old.hash).await?;
+
+    let source = JsonStorage::new(Some(dir.path().join("json")))?;
+    source.save_run(&run("run-1", &chat)).await?;
+    let target = LibSQLStorage::new(Some(dir.path().join("prompts.db"))).await?;
+
+    let report = migrate(&source, &target).await?;
+    assert_eq!((report.prompts_copied, report.prompts_skipped, report.trash_copied), (2, 0, 1));
+    assert_eq!((report.revisions_copied, report.runs_copied, report.target_prompts), (4, 1, 2));
+
+    let migrated = Prompts::new(Box::new(target));
+    let review = migrated.find_by_name("review").await?.unwrap();
+    assert_eq!(review.content, "Review this {{ language }} code carefully");
+    assert_eq!(review.author.as_deref(), Some("Ada"));
+    assert_eq!(review.tags, Some(vec!["code".to_string()]));
+    assert_eq!(migrated.history(&review.id).await?.len(), 2);
+    let trash = migrated.list_trash().await?;
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0].use_count, 1);
+    assert_eq!(migrated.runs(None).await?.len(), 1);
+
+    // A second run finds everything in place.
+    let target = LibSQLStorage::new(Some(dir.path().join("prompts.db"))).await?;
+    let again = migrate(&source, &target).await?;
+    assert_eq!((again.prompts_copied, again.prompts_skipped, again.trash_copied), (0, 2, 0));
+    assert_eq!((again.revisions_copied, again.runs_copied), (0, 0));
+
+    // And back again into an empty JSON store.
+    fs::create_dir_all(dir.path().join("back"))?;
+    let back = JsonStorage::new(Some(dir.path().join("back")))?;
+    assert_eq!(migrate(&target, &back).await?.prompts_copied, 2);
+    assert_eq!(back.load_prompts().await?.iter().filter(|p| p.is_chat()).count(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_cli_migrate() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage_path = dir.path().join("prompts");
+    fs::create_dir_all(&storage_path)?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(
+        &config_path,
+        format!("# my settings\nauthor = \"Ada\"\n\n[storage]\npath = {:?}\n", storage_path.to_string_lossy()),
+    )?;
+    let db_path = dir.path().join("db").join("prompts.db");
+
+    let cli = |args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli").unwrap();
+        cmd.arg("--config").arg(&config_path).args(args);
+        cmd
+    };
+
+    cli(&["add", "Summarise {{ text }}", "--name", "summary"]).assert().success();
+    let from = format!("json:{}", storage_path.display());
+    let to = format!("libsql:{}", db_path.display());
+    cli(&["migrate", "--from", &from, "--to", &to, "--switch-config"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Copied 1 prompts (0 already present)").and(predicates::str::contains("Verified 1 prompts")));
+
+    let config = fs::read_to_string(&config_path)?;
+    assert!(config.starts_with("# my settings\nauthor = \"Ada\""), "{}", config);
+    assert!(config.contains("type = \"libsql\""), "{}", config);
+    fs::remove_dir_all(&storage_path)?;
+    cli(&["show", "summary"]).assert().success().stdout("Summarise {{ text }}\n");
+
+    cli(&["migrate", "--from", &to, "--to", &to])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("are the same"));
+    cli(&["migrate", "--from", "csv:x", "--to", &to])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("unknown storage type"));
+
+    // The configured storage is not opened, so it can be one that no longer works.
+    fs::write(&config_path, "[storage]\ntype = \"libsql-replica\"\n")?;
+    let back = format!("json:{}", dir.path().join("back").display());
+    cli(&["migrate", "--from", &to, "--to", &back])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Copied 1 prompts"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_migrate_verifies_metadata() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let source = LibSQLStorage::new(Some(dir.path().join("source.db"))).await?;
+    let mut prompt = Prompt::new("Summarise {{ text }}", None, None);
+    prompt.name = Some("summary".to_string());
+    Prompts::new(Box::new(LibSQLStorage::new(Some(dir.path().join("source.db"))).await?)).add_prompt(&mut prompt).await?;
+
+    // A target holding the same content under another name is not a faithful copy.
+    fs::create_dir_all(dir.path().join("json"))?;
+    let target = JsonStorage::new(Some(dir.path().join("json")))?;
+    target.save_prompt(&mut Prompt { name: Some("other".to_string()), ..prompt.clone() }).await?;
+    let err = migrate(&source, &target).await.unwrap_err();
+    assert!(err.to_string().contains("differs between the source and the target"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_migrate_trash_whose_name_is_taken() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    for (from, to) in [("json", "libsql"), ("libsql", "json")] {
+        let storage = |kind: &str| -> anyhow::Result<StorageSpec> {
+            format!("{}:{}", kind, dir.path().join(from).join(kind).display()).parse().map_err(anyhow::Error::msg)
+        };
+        let source = storage(from)?.open().await?;
+        let target = storage(to)?.open().await?;
+        let prompts_api = Prompts::new(storage(from)?.open().await?);
+
+        // The trashed prompt gave up its name, which a live prompt then took.
+        let mut old = Prompt::new("Summarise {{ text }}", None, None);
+        old.name = Some("summary".to_string());
+        prompts_api.add_prompt(&mut old).await?;
+        prompts_api.delete_prompt(&old.hash).await?;
+        let mut new = Prompt::new("Summarise {{ text }} briefly", None, None);
+        new.name = Some("summary".to_string());
+        prompts_api.add_prompt(&mut new).await?;
+
+        let report = migrate(source.as_ref(), target.as_ref()).await?;
+        assert_eq!((report.prompts_copied, report.trash_copied), (1, 1), "{} to {}", from, to);
+        let trash = target.load_trash().await?;
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].hash, old.hash);
+        assert_eq!(trash[0].name.as_deref(), Some("summary"));
+        assert_eq!(target.find_by_name("summary").await?.map(|p| p.hash), Some(new.hash.clone()));
+
+        // Changing a trashed copy after the fact is caught.
+        target.purge_prompt(&old.hash).await?;
+        target.save_trashed(&Prompt { name: None, ..trash[0].clone() }).await?;
+        let err = migrate(source.as_ref(), target.as_ref()).await.unwrap_err();
+        assert!(err.to_string().contains("differs between the source and the target"), "{}", err);
+    }
+    Ok(())
+}
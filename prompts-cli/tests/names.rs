@@ -0,0 +1,125 @@
+use predicates::prelude::*;
+use prompts_cli::{
+    storage::{check_name, JsonStorage, LibSQLStorage, Storage},
+    Prompt, Prompts,
+};
+use std::fs;
+use tempfile::tempdir;
+
+fn named(content: &str, name: &str, aliases: &[&str]) -> Prompt {
+    let mut prompt = Prompt::new(content, None, None);
+    prompt.name = Some(name.to_string());
+    prompt.aliases = aliases.iter().map(|a| a.to_string()).collect();
+    prompt
+}
+
+#[test]
+fn test_check_name() {
+    assert!(check_name("code-review").is_ok());
+    assert!(check_name("Greeting_v2.1").is_ok());
+    assert!(check_name("").is_err());
+    assert!(check_name("-draft").is_err());
+    assert!(check_name("two words").is_err());
+    assert!(check_name("tag:rust").is_err());
+}
+
+async fn test_unique_names_impl(storage_type: &str) -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage: Box<dyn Storage + Send + Sync> = if storage_type == "json" {
+        Box::new(JsonStorage::new(Some(dir.path().to_path_buf()))?)
+    } else {
+        Box::new(LibSQLStorage::new(Some(dir.path().join("test.db"))).await?)
+    };
+    let prompts_api = Prompts::new(storage);
+    let mut greeting = named("Hello, how are you?", "greeting", &["hello"]);
+    prompts_api.add_prompt(&mut greeting).await?;
+
+    let err = prompts_api.add_prompt(&mut named("Hi there", "hello", &[])).await.unwrap_err();
+    assert!(err.to_string().contains("Name `hello` is already used by prompt"), "{}", err);
+    let err = prompts_api.add_prompt(&mut named("Hi there", "hi", &["greeting"])).await.unwrap_err();
+    assert!(err.to_string().contains("Name `greeting` is already used"), "{}", err);
+    assert!(prompts_api.add_prompt(&mut named("Hi there", "hi", &["hi"])).await.is_err());
+    assert!(prompts_api.add_prompt(&mut named("Hi there", "not valid", &[])).await.is_err());
+
+    assert_eq!(prompts_api.find_by_name("hello").await?.map(|p| p.hash), Some(greeting.hash.clone()));
+    assert_eq!(prompts_api.find_by_name("hi").await?, None);
+
+    // An exact name wins over fuzzy matches of the same word.
+    let mut farewell = Prompt::new("Goodbye greeting, see you soon!", None, None);
+    prompts_api.add_prompt(&mut farewell).await?;
+    let found = prompts_api.show_prompt("greeting", None).await?;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].hash, greeting.hash);
+    assert_eq!(prompts_api.show_prompt("greeting", Some(vec!["other".to_string()])).await?.len(), 0);
+
+    let err = prompts_api.set_names(&farewell.hash, Some("greeting".to_string()), Vec::new()).await.unwrap_err();
+    assert!(err.to_string().contains("already used"));
+    assert_eq!(prompts_api.list_prompts(None).await?.len(), 2);
+    prompts_api.set_names(&farewell.hash, Some("farewell".to_string()), vec!["bye".to_string()]).await?;
+    assert_eq!(prompts_api.find_by_name("bye").await?.map(|p| p.hash), Some(farewell.hash.clone()));
+
+    prompts_api
+        .edit_prompt(&greeting.hash, Some("Hello!".to_string()), None, None, None, None)
+        .await?;
+    let edited = prompts_api.find_by_name("greeting").await?.unwrap();
+    assert_eq!(edited.content, "Hello!");
+    assert_eq!(edited.aliases, vec!["hello".to_string()]);
+
+    prompts_api.delete_prompt(&edited.hash).await?;
+    assert_eq!(prompts_api.find_by_name("greeting").await?, None);
+    prompts_api.add_prompt(&mut named("Hey", "greeting", &[])).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unique_names_json() -> anyhow::Result<()> {
+    test_unique_names_impl("json").await
+}
+
+#[tokio::test]
+async fn test_unique_names_libsql() -> anyhow::Result<()> {
+    test_unique_names_impl("libsql").await
+}
+
+#[test]
+fn test_cli_names() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage_path = dir.path().join("prompts");
+    fs::create_dir_all(&storage_path)?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(&config_path, format!("[storage]\npath = {:?}\n", storage_path.to_string_lossy()))?;
+
+    let cli = |args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli").unwrap();
+        cmd.arg("--config").arg(&config_path).args(args);
+        cmd
+    };
+
+    cli(&["add", "Review this {{ language }} code", "--name", "review", "--aliases", "cr"]).assert().success();
+    cli(&["add", "Review the review process", "--tags", "meta"]).assert().success();
+    cli(&["add", "Another", "--name", "review"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Name `review` is already used"));
+
+    cli(&["show", "review"]).assert().success().stdout("Review this {{ language }} code\n");
+    cli(&["generate", "cr", "-v", "language=Rust"]).assert().success().stdout("Review this Rust code\n");
+    cli(&["add", "Please {% include \"review\" %}", "--name", "wrapper"]).assert().success();
+    cli(&["generate", "wrapper", "-v", "language=Go"]).assert().success().stdout("Please Review this Go code\n");
+
+    let prompt = Prompt::new("Review this {{ language }} code", None, None);
+    cli(&["list"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!("{} review - Review this", &prompt.hash[..12])));
+
+    cli(&["edit", "review", "--name", "code-review", "--add-aliases", "rv", "--remove-aliases", "cr"])
+        .assert()
+        .success();
+    cli(&["show", "rv"]).assert().success().stdout("Review this {{ language }} code\n");
+    cli(&["show", "cr", "--first"]).assert().success().stdout(predicates::str::contains("Review this {{ language }} code").not());
+
+    cli(&["delete", "code-review", "--yes"]).assert().success();
+    cli(&["add", "Fresh", "--name", "code-review"]).assert().success();
+    Ok(())
+}
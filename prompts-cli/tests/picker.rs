@@ -0,0 +1,54 @@
+use prompts_cli::{picker::Picker, Prompt};
+
+fn candidates() -> Vec<Prompt> {
+    vec![
+        Prompt::new("Review this Rust code for bugs", Some(vec!["rust".to_string()]), None),
+        Prompt::new("Summarize the meeting notes", None, None),
+        Prompt::new("Review this Go code for style", Some(vec!["go".to_string()]), None),
+    ]
+}
+
+#[test]
+fn test_picker_navigation() {
+    let mut picker = Picker::new(candidates());
+    assert_eq!(picker.matches().len(), 3);
+    assert_eq!(picker.selected().unwrap().content, "Review this Rust code for bugs");
+
+    picker.move_up();
+    assert_eq!(picker.selected_index(), 0);
+
+    picker.move_down();
+    picker.move_down();
+    picker.move_down();
+    assert_eq!(picker.selected_index(), 2);
+    assert_eq!(picker.selected().unwrap().content, "Review this Go code for style");
+}
+
+#[test]
+fn test_picker_live_filtering() {
+    let mut picker = Picker::new(candidates());
+    picker.move_down();
+
+    for c in "review".chars() {
+        picker.push_char(c);
+    }
+    assert_eq!(picker.query(), "review");
+    assert_eq!(picker.matches().len(), 2);
+    assert_eq!(picker.selected_index(), 0);
+
+    for c in " go".chars() {
+        picker.push_char(c);
+    }
+    assert_eq!(picker.matches().len(), 1);
+    assert_eq!(picker.selected().unwrap().content, "Review this Go code for style");
+
+    picker.push_char('z');
+    assert!(picker.matches().is_empty());
+    assert!(picker.selected().is_none());
+
+    for _ in 0.." goz".len() {
+        picker.pop_char();
+    }
+    assert_eq!(picker.query(), "review");
+    assert_eq!(picker.matches().len(), 2);
+}
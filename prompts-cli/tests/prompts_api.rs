@@ -1,6 +1,6 @@
 use prompts_cli::{
     Prompt,
-    storage::JsonStorage,
+    storage::{JsonStorage, LibSQLStorage},
     Prompts
 };
 use tempfile::tempdir;
@@ -78,3 +78,27 @@ async fn test_add_duplicate_prompt() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_search_falls_back_to_fuzzy_matching() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage = LibSQLStorage::new(Some(dir.path().join("test.db"))).await?;
+    let prompts_api = Prompts::new(Box::new(storage));
+
+    let mut prompt = Prompt::new("This is the first prompt.", None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+    let mut other = Prompt::new("Another one", None, None);
+    prompts_api.add_prompt(&mut other).await?;
+
+    // Full-text search finds whole words.
+    let results = prompts_api.search("first", None, None).await?;
+    assert_eq!(results.len(), 1);
+    assert!(!results[0].indices.is_empty());
+
+    // Abbreviations are not indexed words, so the fuzzy search takes over.
+    let results = prompts_api.search("frst prmpt", None, None).await?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].prompt.hash, prompt.hash);
+
+    Ok(())
+}
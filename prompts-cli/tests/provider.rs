@@ -0,0 +1,332 @@
+use prompts_cli::{
+    provider::{
+        CompletionRequest, Message, OllamaApi, OllamaConfig, OllamaProvider, OpenAIConfig, OpenAIProvider,
+        Provider, ProvidersConfig, Usage,
+    },
+    storage::JsonStorage,
+    Prompt, Prompts,
+};
+use std::fs;
+use tempfile::tempdir;
+use wiremock::matchers::{body_partial_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const STREAM: &str = concat!(
+    "data: {\"model\":\"test-model-1\",\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+    "data: {\"model\":\"test-model-1\",\"choices\":[{\"delta\":{\"content\":\"Hello\"}}]}\n\n",
+    "data: {\"model\":\"test-model-1\",\"choices\":[{\"delta\":{\"content\":\", world\"}}]}\n\n",
+    "data: {\"model\":\"test-model-1\",\"choices\":[{\"delta\":{\"content\":null}}]}\n\n",
+    "data: {\"model\":\"test-model-1\",\"choices\":[],\"usage\":{\"prompt_tokens\":5,\"completion_tokens\":3,\"total_tokens\":8}}\n\n",
+    "data: [DONE]\n\n",
+);
+
+fn config(server: &MockServer, api_key_env: &str) -> OpenAIConfig {
+    OpenAIConfig {
+        base_url: format!("{}/v1", server.uri()),
+        model: "test-model".to_string(),
+        api_key_env: api_key_env.to_string(),
+    }
+}
+
+async fn mock_stream(server: &MockServer) {
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(serde_json::json!({ "stream": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(STREAM, "text/event-stream"))
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn test_openai_completion() -> anyhow::Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(header("authorization", "Bearer secret"))
+        .and(body_partial_json(serde_json::json!({
+            "model": "other-model",
+            "stream": false,
+            "messages": [{ "role": "user", "content": "Say hello" }],
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "model": "other-model-1",
+            "choices": [{ "message": { "role": "assistant", "content": "Hello!" } }],
+            "usage": { "prompt_tokens": 2, "completion_tokens": 1, "total_tokens": 3 },
+        })))
+        .mount(&server)
+        .await;
+
+    std::env::set_var("PROMPTS_CLI_TEST_OPENAI_KEY", "secret");
+    let provider = OpenAIProvider::new(config(&server, "PROMPTS_CLI_TEST_OPENAI_KEY"));
+    let request = CompletionRequest {
+        messages: vec![Message::user("Say hello")],
+        model: Some("other-model".to_string()),
+    };
+    let completion = provider.complete(&request, None).await?;
+    assert_eq!(completion.model, "other-model-1");
+    assert_eq!(completion.content, "Hello!");
+    assert_eq!(completion.usage.map(|u| u.total_tokens), Some(3));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_openai_streaming() -> anyhow::Result<()> {
+    let server = MockServer::start().await;
+    mock_stream(&server).await;
+
+    let provider = OpenAIProvider::new(config(&server, "PROMPTS_CLI_TEST_UNSET_KEY"));
+    let request = CompletionRequest {
+        messages: vec![Message::user("Say hello")],
+        ..Default::default()
+    };
+    let mut tokens = Vec::new();
+    let mut on_token = |token: &str| tokens.push(token.to_string());
+    let completion = provider.complete(&request, Some(&mut on_token)).await?;
+
+    assert_eq!(tokens, vec!["Hello", ", world"]);
+    assert_eq!(completion.content, "Hello, world");
+    assert_eq!(completion.model, "test-model-1");
+    assert_eq!(
+        completion.usage,
+        Some(Usage { prompt_tokens: 5, completion_tokens: 3, total_tokens: 8 })
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_openai_null_content() -> anyhow::Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": null, "refusal": "No." } }],
+        })))
+        .mount(&server)
+        .await;
+
+    let provider = OpenAIProvider::new(config(&server, "PROMPTS_CLI_TEST_UNSET_KEY"));
+    let completion = provider.complete(&CompletionRequest::default(), None).await?;
+    assert_eq!(completion.content, "");
+    assert_eq!(completion.model, "test-model");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_openai_errors() -> anyhow::Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(401).set_body_string("{\"error\":\"bad key\"}"))
+        .mount(&server)
+        .await;
+
+    let provider = OpenAIProvider::new(config(&server, "PROMPTS_CLI_TEST_UNSET_KEY"));
+    let err = provider.complete(&CompletionRequest::default(), None).await.unwrap_err();
+    assert_eq!(err.to_string(), "Provider returned 401 Unauthorized: {\"error\":\"bad key\"}");
+
+    let err = ProvidersConfig::default().provider("openai").err().unwrap();
+    assert!(err.to_string().contains("[providers.openai]"));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cli_generate_run() -> anyhow::Result<()> {
+    let server = MockServer::start().await;
+    mock_stream(&server).await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "stream": false })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "model": "test-model-1",
+            "choices": [{ "message": { "role": "assistant", "content": "Hello!" } }],
+        })))
+        .mount(&server)
+        .await;
+
+    let dir = tempdir()?;
+    let storage_path = dir.path().join("prompts");
+    fs::create_dir_all(&storage_path)?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "[storage]\npath = {:?}\n\n[providers.openai]\nbase_url = \"{}/v1\"\nmodel = \"test-model\"\n",
+            storage_path.to_string_lossy(),
+            server.uri()
+        ),
+    )?;
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(storage_path))?));
+    let mut prompt = Prompt::new("Greet {{ name }}", None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli")?;
+    cmd.arg("--config")
+        .arg(&config_path)
+        .arg("generate")
+        .arg("Greet")
+        .arg("-v")
+        .arg("name=Ada")
+        .arg("--run");
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::diff("Hello, world\n"))
+        .stderr(predicates::str::contains("test-model-1: 5 prompt + 3 completion = 8 tokens"));
+
+    let requests = server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body)?;
+    assert_eq!(body["messages"], serde_json::json!([{ "role": "user", "content": "Greet Ada" }]));
+
+    let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli")?;
+    cmd.arg("--config")
+        .arg(&config_path)
+        .arg("--output")
+        .arg("json")
+        .arg("generate")
+        .arg("Greet")
+        .arg("-v")
+        .arg("name=Ada")
+        .arg("--run");
+    let output = cmd.output()?;
+    assert!(output.status.success());
+    let run: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(run["output"], "Hello!");
+    assert_eq!(run["model"], "test-model-1");
+    assert_eq!(run["usage"], serde_json::Value::Null);
+
+    Ok(())
+}
+
+const OLLAMA_STREAM: &str = concat!(
+    "{\"model\":\"llama3\",\"message\":{\"role\":\"assistant\",\"content\":\"Hi\"},\"done\":false}\n",
+    "{\"model\":\"llama3\",\"message\":{\"role\":\"assistant\",\"content\":\" there\"},\"done\":false}\n",
+    "{\"model\":\"llama3\",\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"prompt_eval_count\":4,\"eval_count\":2}\n",
+);
+
+fn ollama_config(server: &MockServer, api: OllamaApi) -> OllamaConfig {
+    OllamaConfig {
+        base_url: server.uri(),
+        model: "llama3".to_string(),
+        api,
+    }
+}
+
+#[tokio::test]
+async fn test_ollama_chat_streaming() -> anyhow::Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(body_partial_json(serde_json::json!({
+            "model": "llama3",
+            "stream": true,
+            "messages": [{ "role": "user", "content": "Say hi" }],
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(OLLAMA_STREAM, "application/x-ndjson"))
+        .mount(&server)
+        .await;
+
+    let provider = OllamaProvider::new(ollama_config(&server, OllamaApi::Chat));
+    let request = CompletionRequest {
+        messages: vec![Message::user("Say hi")],
+        ..Default::default()
+    };
+    let mut tokens = Vec::new();
+    let mut on_token = |token: &str| tokens.push(token.to_string());
+    let completion = provider.complete(&request, Some(&mut on_token)).await?;
+
+    assert_eq!(tokens, vec!["Hi", " there"]);
+    assert_eq!(completion.content, "Hi there");
+    assert_eq!(
+        completion.usage,
+        Some(Usage { prompt_tokens: 4, completion_tokens: 2, total_tokens: 6 })
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ollama_generate() -> anyhow::Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/generate"))
+        .and(body_partial_json(serde_json::json!({
+            "model": "phi3",
+            "stream": false,
+            "system": "Be brief.",
+            "prompt": "Say hi",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "model": "phi3",
+            "response": "Hi.",
+            "done": true,
+        })))
+        .mount(&server)
+        .await;
+
+    let provider = OllamaProvider::new(ollama_config(&server, OllamaApi::Generate));
+    let request = CompletionRequest {
+        messages: vec![
+            Message { role: "system".to_string(), content: "Be brief.".to_string() },
+            Message::user("Say hi"),
+        ],
+        model: Some("phi3".to_string()),
+    };
+    let completion = provider.complete(&request, None).await?;
+    assert_eq!(completion.content, "Hi.");
+    assert_eq!(completion.model, "phi3");
+    assert_eq!(completion.usage, None);
+    Ok(())
+}
+
+#[test]
+fn test_default_provider() -> anyhow::Result<()> {
+    let config: ProvidersConfig = toml::from_str("[ollama]\nmodel = \"llama3\"\n")?;
+    assert_eq!(config.default_name()?, "ollama");
+    assert_eq!(config.ollama.as_ref().unwrap().base_url, "http://localhost:11434");
+    assert_eq!(config.ollama.as_ref().unwrap().api, OllamaApi::Chat);
+    assert!(config.provider("openai").is_err());
+    assert!(config.provider("ollama").is_ok());
+
+    let config: ProvidersConfig =
+        toml::from_str("default = \"ollama\"\n[openai]\nmodel = \"gpt\"\n[ollama]\nmodel = \"llama3\"\napi = \"generate\"\n")?;
+    assert_eq!(config.default_name()?, "ollama");
+
+    assert!(ProvidersConfig::default().default_name().is_err());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cli_generate_run_ollama() -> anyhow::Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(OLLAMA_STREAM, "application/x-ndjson"))
+        .mount(&server)
+        .await;
+
+    let dir = tempdir()?;
+    let storage_path = dir.path().join("prompts");
+    fs::create_dir_all(&storage_path)?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "[storage]\npath = {:?}\n\n[providers.openai]\nmodel = \"unused\"\n\n[providers.ollama]\nbase_url = \"{}\"\nmodel = \"llama3\"\n",
+            storage_path.to_string_lossy(),
+            server.uri()
+        ),
+    )?;
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(storage_path))?));
+    let mut prompt = Prompt::new("Say hi", None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli")?;
+    cmd.arg("--config")
+        .arg(&config_path)
+        .arg("generate")
+        .arg("Say hi")
+        .arg("--run")
+        .arg("--provider")
+        .arg("ollama");
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::diff("Hi there\n"))
+        .stderr(predicates::str::contains("llama3: 4 prompt + 2 completion = 6 tokens"));
+
+    Ok(())
+}
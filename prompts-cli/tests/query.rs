@@ -0,0 +1,172 @@
+use prompts_cli::{
+    Prompt, Prompts, Query,
+    storage::{JsonStorage, LibSQLStorage, Storage},
+};
+use tempfile::tempdir;
+
+fn tag(s: &str) -> Query {
+    Query::Tag(s.to_string())
+}
+
+#[test]
+fn test_parse_query() {
+    assert_eq!(Query::parse("").unwrap(), Query::all());
+    assert_eq!(Query::parse("  ").unwrap(), Query::all());
+    assert_eq!(Query::parse("review").unwrap(), Query::Fuzzy("review".to_string()));
+
+    let query = Query::parse(r#"tag:rust OR tag:go -tag:draft category:review "exact phrase" content~fuzzy"#).unwrap();
+    assert_eq!(
+        query,
+        Query::And(vec![
+            Query::Or(vec![tag("rust"), tag("go")]),
+            Query::Not(Box::new(tag("draft"))),
+            Query::Category("review".to_string()),
+            Query::Phrase("exact phrase".to_string()),
+            Query::Fuzzy("fuzzy".to_string()),
+        ])
+    );
+
+    assert_eq!(
+        Query::parse(r#"(tag:a AND tag:b) OR NOT content:"two words""#).unwrap(),
+        Query::Or(vec![
+            Query::And(vec![tag("a"), tag("b")]),
+            Query::Not(Box::new(Query::Phrase("two words".to_string()))),
+        ])
+    );
+    assert_eq!(Query::parse("step-by-step").unwrap(), Query::Fuzzy("step-by-step".to_string()));
+}
+
+#[test]
+fn test_parse_query_errors() {
+    let cases = [
+        ("tag:rust )", "unexpected `)`", 9, 1),
+        ("tags:rust", "unknown field `tags`", 0, 4),
+        ("tag:", "expected a value after `tag:`", 0, 4),
+        ("review \"open", "unterminated quote", 7, 1),
+        ("(tag:a", "unclosed `(`", 0, 1),
+        ("tag:a OR", "expected a term after `OR`", 6, 2),
+        ("OR tag:a", "unexpected `OR`", 0, 2),
+        ("tag:a -", "expected a term after `-`", 6, 1),
+        ("tag~rust", "`tag` does not support fuzzy matching", 0, 8),
+    ];
+    for (input, message, position, len) in cases {
+        let err = Query::parse(input).unwrap_err();
+        assert!(err.message.starts_with(message), "{}: {}", input, err.message);
+        assert_eq!((err.position, err.len), (position, len), "{}", input);
+    }
+
+    let err = Query::parse("tag:rust )").unwrap_err();
+    assert_eq!(err.to_string(), "unexpected `)` at column 10\n  tag:rust )\n           ^");
+}
+
+fn sample_prompts() -> Vec<Prompt> {
+    vec![
+        Prompt::new("Review this Rust code", Some(vec!["rust".to_string()]), Some(vec!["review".to_string()])),
+        Prompt::new("Review this Go code", Some(vec!["go".to_string(), "draft".to_string()]), Some(vec!["review".to_string()])),
+        Prompt::new("Explain this Go error", Some(vec!["go".to_string()]), None),
+        Prompt::new("Write a Python script", Some(vec!["python".to_string()]), None),
+    ]
+}
+
+fn contents(results: &[Prompt]) -> Vec<&str> {
+    let mut contents: Vec<&str> = results.iter().map(|p| p.content.as_str()).collect();
+    contents.sort();
+    contents
+}
+
+#[test]
+fn test_query_evaluation() {
+    let prompts = sample_prompts();
+    let search = |q: &str| prompts_cli::search_prompts(&prompts, q, &[], &[]);
+
+    assert_eq!(contents(&search("tag:rust OR tag:go -tag:draft")), vec!["Explain this Go error", "Review this Rust code"]);
+    assert_eq!(contents(&search("category:review -tag:rust")), vec!["Review this Go code"]);
+    assert_eq!(contents(&search("\"this go\"")), vec!["Explain this Go error", "Review this Go code"]);
+    assert_eq!(contents(&search("content~rvw tag:go")), vec!["Review this Go code"]);
+    assert_eq!(contents(&search("NOT (tag:go OR tag:rust)")), vec!["Write a Python script"]);
+
+    // A negated bare word excludes whole words only, not fuzzy matches.
+    let mut prompts = prompts;
+    prompts.push(Prompt::new("Review this algorithm", None, None));
+    let search = |q: &str| prompts_cli::search_prompts(&prompts, q, &[], &[]);
+    assert_eq!(contents(&search("review -go")), vec!["Review this Rust code", "Review this algorithm"]);
+    assert_eq!(contents(&search("review -\"go code\" -RUST")), vec!["Review this algorithm"]);
+    assert_eq!(contents(&search("review NOT (rust OR algorithm)")), vec!["Review this Go code"]);
+
+    let query = Query::parse("\"go code\"").unwrap();
+    let query_match = query.evaluate(&prompts[1]).unwrap();
+    assert_eq!(query_match.indices, (12..19).collect::<Vec<_>>());
+}
+
+async fn test_query_language_impl(storage_type: &str) -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage: Box<dyn Storage + Send + Sync> = if storage_type == "json" {
+        Box::new(JsonStorage::new(Some(dir.path().to_path_buf()))?)
+    } else {
+        Box::new(LibSQLStorage::new(Some(dir.path().join("test.db"))).await?)
+    };
+    let prompts_api = Prompts::new(storage);
+    for mut prompt in sample_prompts() {
+        prompts_api.add_prompt(&mut prompt).await?;
+    }
+
+    let results = prompts_api.show_prompt("tag:rust OR tag:go -tag:draft", None).await?;
+    assert_eq!(contents(&results), vec!["Explain this Go error", "Review this Rust code"]);
+
+    let results = prompts_api.show_prompt("review -\"rust code\"", None).await?;
+    assert_eq!(contents(&results), vec!["Review this Go code"]);
+
+    let results = prompts_api.show_prompt("NOT category:review", Some(vec!["go".to_string()])).await?;
+    assert_eq!(contents(&results), vec!["Explain this Go error"]);
+
+    let results = prompts_api.search("code OR script", None, None).await?;
+    assert_eq!(results.len(), 3);
+
+    assert!(prompts_api.show_prompt("tag:rust OR", None).await.is_err());
+
+    // Text that is not a valid query is searched for as written.
+    prompts_api.add_prompt(&mut Prompt::new("Note: summarize the meeting", None, None)).await?;
+    let results = prompts_api.show_prompt("Note: summarize", None).await?;
+    assert_eq!(contents(&results), vec!["Note: summarize the meeting"]);
+
+    // Words the full-text index cannot hold do not match every prompt.
+    let results = prompts_api.search("!!", None, None).await?;
+    assert!(results.len() < 5, "{:?}", results);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_language_json() -> anyhow::Result<()> {
+    test_query_language_impl("json").await
+}
+
+#[tokio::test]
+async fn test_query_language_libsql() -> anyhow::Result<()> {
+    test_query_language_impl("libsql").await
+}
+
+#[tokio::test]
+async fn test_query_language_backends_agree() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let json_dir = tempdir()?;
+    let json = Prompts::new(Box::new(JsonStorage::new(Some(json_dir.path().to_path_buf()))?));
+    let libsql = Prompts::new(Box::new(LibSQLStorage::new(Some(dir.path().join("test.db"))).await?));
+    for prompts_api in [&json, &libsql] {
+        for mut prompt in sample_prompts() {
+            prompts_api.add_prompt(&mut prompt).await?;
+        }
+        prompts_api.add_prompt(&mut Prompt::new("Take notes on the meeting", None, None)).await?;
+    }
+
+    // Fuzzy words such as `cde` match prompts no full-text index would find.
+    let queries = ["notes OR cde", "NOT cde", "-!!", "review -go", "tag:go cde", "\"this go\" OR rvw", "tag:go -\"go code\""];
+    for query in queries {
+        let from_json: Vec<_> = json.search(query, None, None).await?.into_iter().map(|r| r.prompt).collect();
+        let from_libsql: Vec<_> = libsql.search(query, None, None).await?.into_iter().map(|r| r.prompt).collect();
+        assert_eq!(contents(&from_json), contents(&from_libsql), "{}", query);
+    }
+    assert_eq!(json.search("notes OR cde", None, None).await?.len(), 3);
+
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use prompts_cli::{
+    storage::{JsonStorage, LibSQLStorage, ReplicaConfig, Storage},
+    Prompt, Prompts,
+};
+use std::fs;
+use tempfile::tempdir;
+
+/// Nothing listens on the discard port, so connecting fails straight away.
+const UNREACHABLE: &str = "http://127.0.0.1:9";
+
+fn replica_config(sync_url: &str) -> ReplicaConfig {
+    ReplicaConfig { sync_url: sync_url.to_string(), ..Default::default() }
+}
+
+#[tokio::test]
+async fn test_replica_offline() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let db_path = dir.path().join("replica.db");
+
+    // A replica that has never been synced has nothing to read.
+    let err = LibSQLStorage::new_replica(Some(dir.path().join("new.db")), replica_config(UNREACHABLE)).await.err().unwrap();
+    assert!(err.to_string().contains("Cannot reach the sync server at http://127.0.0.1:9"), "{}", err);
+
+    // Stand in for a replica synced earlier by filling in its file directly.
+    libsql::Builder::new_remote_replica(&db_path, UNREACHABLE.to_string(), String::new())
+        .sync_protocol(libsql::SyncProtocol::V1)
+        .build()
+        .await?;
+    {
+        let local = LibSQLStorage::new(Some(db_path.clone())).await?;
+        local.save_prompt(&mut Prompt::new("Hello, how are you?", None, None)).await?;
+    }
+    let replica = LibSQLStorage::new_replica(Some(db_path), replica_config(UNREACHABLE)).await?;
+    assert!(replica.offline().is_some());
+    let prompts_api = Prompts::new(Box::new(replica));
+    assert_eq!(prompts_api.list_prompts(None).await?.len(), 1);
+    assert_eq!(prompts_api.show_prompt("Hello", None).await?.len(), 1);
+
+    let err = prompts_api.add_prompt(&mut Prompt::new("Goodbye", None, None)).await.unwrap_err();
+    assert!(err.to_string().starts_with("Cannot change the prompt library while the sync server"), "{}", err);
+    let err = prompts_api.sync().await.unwrap_err();
+    assert!(err.to_string().starts_with("Cannot reach the sync server"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_needs_a_replica() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let json = Prompts::new(Box::new(JsonStorage::new(Some(dir.path().to_path_buf()))?));
+    assert!(json.sync().await.unwrap_err().to_string().contains("no server to sync with"));
+    let local = Prompts::new(Box::new(LibSQLStorage::new(Some(dir.path().join("prompts.db"))).await?));
+    assert!(local.sync().await.unwrap_err().to_string().contains("not a replica"));
+    Ok(())
+}
+
+/// Shares a library between two replicas through the sqld server at
+/// `PROMPTS_CLI_TEST_SQLD_URL`, e.g. one started with `sqld --grpc-listen-addr 127.0.0.1:5001`.
+/// Run it with `cargo test -- --ignored`.
+#[tokio::test]
+#[ignore = "needs a sqld server at PROMPTS_CLI_TEST_SQLD_URL"]
+async fn test_replicas_share_a_library() -> anyhow::Result<()> {
+    let sync_url = std::env::var("PROMPTS_CLI_TEST_SQLD_URL").expect("PROMPTS_CLI_TEST_SQLD_URL must be set to a sqld server");
+    let dir = tempdir()?;
+    let ours = LibSQLStorage::new_replica(Some(dir.path().join("ours.db")), replica_config(&sync_url)).await?;
+    let theirs = LibSQLStorage::new_replica(
+        Some(dir.path().join("theirs.db")),
+        ReplicaConfig { sync_on_write: true, ..replica_config(&sync_url) },
+    )
+    .await?;
+    assert!(ours.offline().is_none());
+
+    let mut prompt = Prompt::new(&format!("Shared prompt {}", chrono::Utc::now().timestamp_nanos_opt().unwrap()), None, None);
+    let ours = Prompts::new(Box::new(ours));
+    let theirs = Prompts::new(Box::new(theirs));
+    ours.add_prompt(&mut prompt).await?;
+    assert!(theirs.find_by_hash(&prompt.hash).await.is_err());
+    theirs.sync().await?;
+    assert_eq!(theirs.find_by_hash(&prompt.hash).await?.content, prompt.content);
+
+    // With sync_on_write, a change also brings in everyone else's.
+    ours.delete_prompt(&prompt.hash).await?;
+    theirs.add_prompt(&mut Prompt::new(&format!("{} again", prompt.content), None, None)).await?;
+    assert!(theirs.find_by_hash(&prompt.hash).await.is_err());
+    theirs.restore_prompt(&prompt.hash).await?;
+    Ok(())
+}
+
+#[test]
+fn test_cli_sync() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage_path = dir.path().join("prompts");
+    fs::create_dir_all(&storage_path)?;
+    let config_path = dir.path().join("config.toml");
+    let db_path = dir.path().join("replica.db");
+
+    let cli = |args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli").unwrap();
+        cmd.arg("--config").arg(&config_path).args(args);
+        cmd
+    };
+
+    fs::write(&config_path, format!("[storage]\npath = {:?}\n", storage_path.to_string_lossy()))?;
+    cli(&["sync"]).assert().failure().stderr(predicates::str::contains("no server to sync with"));
+
+    fs::write(&config_path, format!("[storage]\ntype = \"libsql-replica\"\npath = {:?}\n", db_path.to_string_lossy()))?;
+    cli(&["list"]).assert().failure().stderr(predicates::str::contains("needs storage.sync_url"));
+
+    fs::write(
+        &config_path,
+        format!("[storage]\ntype = \"libsql-replica\"\npath = {:?}\nsync_url = {:?}\n", db_path.to_string_lossy(), UNREACHABLE),
+    )?;
+    cli(&["list"]).assert().failure().stderr(predicates::str::contains("Cannot reach the sync server"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_generate_offline() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let db_path = dir.path().join("replica.db");
+    libsql::Builder::new_remote_replica(&db_path, UNREACHABLE.to_string(), String::new())
+        .sync_protocol(libsql::SyncProtocol::V1)
+        .build()
+        .await?;
+    LibSQLStorage::new(Some(db_path.clone())).await?.save_prompt(&mut Prompt::new("Hello, {{ name }}", None, None)).await?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(
+        &config_path,
+        format!("[storage]\ntype = \"libsql-replica\"\npath = {:?}\nsync_url = {:?}\n", db_path.to_string_lossy(), UNREACHABLE),
+    )?;
+
+    // Using a prompt cannot be recorded offline, but that does not stop it being rendered.
+    let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli")?;
+    cmd.arg("--config").arg(&config_path).args(["generate", "Hello", "-v", "name=Ada"]);
+    cmd.assert()
+        .success()
+        .stdout("Hello, Ada\n")
+        .stderr(predicates::str::contains("Warning: could not record the use of prompt"));
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use chrono::{TimeZone, Utc};
+use prompts_cli::{
+    provider::Usage,
+    storage::{JsonStorage, LibSQLStorage, Storage},
+    Prompt, Prompts, Run,
+};
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+use wiremock::matchers::{body_string_contains, method};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn run(id: &str, prompt_id: &str, minute: u32, usage: Option<Usage>) -> Run {
+    Run {
+        id: id.to_string(),
+        prompt_id: prompt_id.to_string(),
+        prompt_hash: format!("{}-hash", prompt_id),
+        input: "Say hi to Ada".to_string(),
+        variables: serde_json::json!({ "name": "Ada", "tags": ["a", "b"] }),
+        provider: "openai".to_string(),
+        model: "test-model".to_string(),
+        output: format!("Hi from run {}", id),
+        latency_ms: 120,
+        usage,
+        created_at: Utc.with_ymd_and_hms(2026, 1, 2, 3, minute, 5).unwrap(),
+    }
+}
+
+async fn test_run_log_impl(storage_type: &str) -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage: Box<dyn Storage + Send + Sync> = if storage_type == "json" {
+        Box::new(JsonStorage::new(Some(dir.path().to_path_buf()))?)
+    } else {
+        Box::new(LibSQLStorage::new(Some(dir.path().join("test.db"))).await?)
+    };
+
+    assert!(storage.load_runs(None).await?.is_empty());
+
+    let usage = Usage { prompt_tokens: 3, completion_tokens: 4, total_tokens: 7 };
+    let runs = vec![
+        run("run-b", "prompt-1", 30, Some(usage)),
+        run("run-a", "prompt-1", 10, None),
+        run("run-c", "prompt-2", 20, None),
+    ];
+    for run in &runs {
+        storage.save_run(run).await?;
+    }
+
+    let loaded = storage.load_runs(None).await?;
+    assert_eq!(loaded, vec![runs[1].clone(), runs[2].clone(), runs[0].clone()]);
+    let loaded = storage.load_runs(Some("prompt-1")).await?;
+    assert_eq!(loaded, vec![runs[1].clone(), runs[0].clone()]);
+
+    let prompts_api = Prompts::new(storage);
+    assert_eq!(prompts_api.find_run("run-c").await?, runs[2]);
+    assert!(prompts_api.find_run("run-").await.is_err());
+    assert!(prompts_api.find_run("nope").await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_run_log_json() -> anyhow::Result<()> {
+    test_run_log_impl("json").await
+}
+
+#[tokio::test]
+async fn test_run_log_libsql() -> anyhow::Result<()> {
+    test_run_log_impl("libsql").await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cli_runs() -> anyhow::Result<()> {
+    let server = MockServer::start().await;
+    for (name, reply) in [("Ada", "Hello Ada!"), ("Bob", "Hi Bob.")] {
+        Mock::given(method("POST"))
+            .and(body_string_contains(format!("Greet {}", name)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "model": "test-model-1",
+                "choices": [{ "message": { "role": "assistant", "content": reply } }],
+                "usage": { "prompt_tokens": 2, "completion_tokens": 3, "total_tokens": 5 },
+            })))
+            .mount(&server)
+            .await;
+    }
+
+    let dir = tempdir()?;
+    let storage_path = dir.path().join("prompts");
+    fs::create_dir_all(&storage_path)?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "[storage]\npath = {:?}\n\n[providers.openai]\nbase_url = \"{}/v1\"\nmodel = \"test-model\"\n",
+            storage_path.to_string_lossy(),
+            server.uri()
+        ),
+    )?;
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(storage_path.clone()))?));
+    let mut prompt = Prompt::new("Greet {{ name }}", None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    let cli = |args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli").unwrap();
+        cmd.arg("--config").arg(&config_path).args(args);
+        cmd
+    };
+    let mut ids = Vec::new();
+    for name in ["Ada", "Bob"] {
+        let output = cli(&["--output", "json", "generate", "Greet", "-v", &format!("name={}", name), "--run"]).output()?;
+        assert!(output.status.success());
+        let run: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+        ids.push(run["id"].as_str().unwrap().to_string());
+    }
+
+    let runs = prompts_api.runs(Some(&prompt.id)).await?;
+    assert_eq!(runs.len(), 2);
+    assert_eq!(runs[0].variables, serde_json::json!({ "name": "Ada" }));
+    assert_eq!(runs[0].input, "Greet Ada");
+    assert_eq!(runs[1].output, "Hi Bob.");
+    assert_eq!(runs[1].usage.as_ref().map(|u| u.total_tokens), Some(5));
+
+    let output = cli(&["runs", "list", "Greet"]).output()?;
+    assert!(output.status.success());
+    let listing = String::from_utf8(output.stdout)?;
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with(&ids[1][..12]));
+    assert!(lines[0].contains("openai/test-model-1"));
+    assert!(lines[0].ends_with("5 tokens - Hi Bob."), "{}", lines[0]);
+
+    cli(&["runs", "list", "--limit", "1"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Hi Bob.").and(predicates::str::contains("Hello Ada!").not()));
+
+    cli(&["runs", "show", &ids[0][..8]])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!("Run {}", ids[0])))
+        .stdout(predicates::str::contains("Tokens:    2 prompt + 3 completion = 5"))
+        .stdout(predicates::str::contains("--- Input\nGreet Ada\n\n--- Output\nHello Ada!"));
+
+    cli(&["runs", "diff", &ids[0][..8], &ids[1][..8]])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("-  \"name\": \"Ada\"\n+  \"name\": \"Bob\""))
+        .stdout(predicates::str::contains("-Hello Ada!\n+Hi Bob."));
+
+    cli(&["runs", "show", "0000"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("No run found"));
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use predicates::prelude::*;
+use prompts_cli::{
+    storage::{JsonStorage, LibSQLStorage, Storage, JSON_FORMAT_VERSION, SCHEMA_VERSION},
+    Prompt,
+};
+use std::fs;
+use tempfile::tempdir;
+
+async fn schema_versions(db_path: &std::path::Path) -> anyhow::Result<Vec<i64>> {
+    let conn = libsql::Builder::new_local(db_path).build().await?.connect()?;
+    let mut rows = conn.query("SELECT version FROM schema_version ORDER BY version", ()).await?;
+    let mut versions = Vec::new();
+    while let Some(row) = rows.next().await? {
+        versions.push(row.get(0)?);
+    }
+    Ok(versions)
+}
+
+#[tokio::test]
+async fn test_libsql_migrates_legacy_database() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let db_path = dir.path().join("prompts.db");
+    {
+        // A database from before prompts had ids.
+        let conn = libsql::Builder::new_local(&db_path).build().await?.connect()?;
+        conn.execute_batch(
+            "CREATE TABLE prompts (hash TEXT PRIMARY KEY, content TEXT NOT NULL, tags TEXT, categories TEXT);
+            INSERT INTO prompts (hash, content, tags, categories) VALUES ('abc123', 'An old prompt', '[\"old\"]', NULL);",
+        )
+        .await?;
+    }
+
+    let pending = LibSQLStorage::pending_migrations(Some(db_path.clone())).await?;
+    assert_eq!(pending.len(), SCHEMA_VERSION as usize);
+    assert!(pending.iter().any(|m| m.destructive));
+
+    let storage = LibSQLStorage::new(Some(db_path.clone())).await?;
+    let prompts = storage.load_prompts().await?;
+    assert_eq!(prompts.len(), 1);
+    assert_eq!(prompts[0].id, "abc123");
+    assert_eq!(prompts[0].tags, Some(vec!["old".to_string()]));
+    assert!(dir.path().join("prompts.db.v0.bak").exists());
+    assert_eq!(schema_versions(&db_path).await?, (1..=SCHEMA_VERSION as i64).collect::<Vec<_>>());
+    assert!(LibSQLStorage::pending_migrations(Some(db_path.clone())).await?.is_empty());
+
+    // Opening it again changes nothing.
+    drop(storage);
+    LibSQLStorage::new(Some(db_path.clone())).await?;
+    assert_eq!(schema_versions(&db_path).await?.len(), SCHEMA_VERSION as usize);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_libsql_new_database() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let db_path = dir.path().join("prompts.db");
+    assert_eq!(LibSQLStorage::pending_migrations(Some(db_path.clone())).await?.len(), SCHEMA_VERSION as usize);
+    let storage = LibSQLStorage::new(Some(db_path.clone())).await?;
+    storage.save_prompt(&mut Prompt::new("Hello", None, None)).await?;
+    assert!(LibSQLStorage::pending_migrations(Some(db_path.clone())).await?.is_empty());
+    // There was nothing to lose, so nothing was backed up.
+    assert_eq!(fs::read_dir(dir.path())?.filter(|e| e.as_ref().unwrap().path().extension().is_some_and(|x| x == "bak")).count(), 0);
+
+    let conn = libsql::Builder::new_local(&db_path).build().await?.connect()?;
+    conn.execute("INSERT INTO schema_version (version, description, applied_at) VALUES (99, 'From the future', '')", ()).await?;
+    let err = LibSQLStorage::new(Some(db_path)).await.err().unwrap();
+    assert!(err.to_string().contains("schema version 99"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_json_upgrades_old_files() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let old = dir.path().join("abc123.json");
+    fs::write(&old, r#"{"hash": "abc123", "content": "An old prompt", "tags": null, "categories": null}"#)?;
+    let storage = JsonStorage::new(Some(dir.path().to_path_buf()))?;
+    let mut current = Prompt::new("A new prompt", None, None);
+    storage.save_prompt(&mut current).await?;
+
+    let prompts = storage.load_prompts().await?;
+    let loaded = prompts.iter().find(|p| p.hash == "abc123").unwrap();
+    assert_eq!(loaded.id, "abc123");
+
+    assert_eq!(storage.upgrade_files(true).await?, vec![old.clone()]);
+    assert!(!fs::read_to_string(&old)?.contains("format"));
+    assert_eq!(storage.upgrade_files(false).await?, vec![old.clone()]);
+    let value: serde_json::Value = serde_json::from_str(&fs::read_to_string(&old)?)?;
+    assert_eq!(value["format"], JSON_FORMAT_VERSION);
+    assert_eq!(value["id"], "abc123");
+    assert!(storage.upgrade_files(false).await?.is_empty());
+
+    fs::write(&old, r#"{"format": 99, "hash": "abc123", "content": "From the future"}"#)?;
+    let err = storage.load_prompts().await.unwrap_err();
+    assert!(err.to_string().contains("format 99"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn test_cli_db_migrate() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage_path = dir.path().join("prompts");
+    fs::create_dir_all(&storage_path)?;
+    fs::write(storage_path.join("abc123.json"), r#"{"hash": "abc123", "content": "An old prompt"}"#)?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(&config_path, format!("[storage]\npath = {:?}\n", storage_path.to_string_lossy()))?;
+    let db_path = dir.path().join("prompts.db");
+    let db_config_path = dir.path().join("db.toml");
+    fs::write(&db_config_path, format!("[storage]\ntype = \"libsql\"\npath = {:?}\n", db_path.to_string_lossy()))?;
+
+    let cli = |config: &std::path::Path, args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli").unwrap();
+        cmd.arg("--config").arg(config).args(args);
+        cmd
+    };
+
+    cli(&config_path, &["db", "migrate", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with("Would upgrade 1 prompt files to format 2.").and(predicates::str::contains("abc123.json")));
+    cli(&config_path, &["db", "migrate"]).assert().success().stdout(predicates::str::starts_with("Upgraded 1 prompt files"));
+    cli(&config_path, &["db", "migrate"]).assert().success().stdout("Upgraded 0 prompt files to format 2.\n");
+
+    cli(&db_config_path, &["db", "migrate", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with(format!("Would apply {} migrations", SCHEMA_VERSION)));
+    assert!(!db_path.exists());
+    cli(&db_config_path, &["db", "migrate"]).assert().success();
+    cli(&db_config_path, &["db", "migrate", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(format!("Would apply 0 migrations to reach schema version {}.\n", SCHEMA_VERSION));
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use prompts_cli::{Prompt, search_prompts};
+use prompts_cli::{Prompt, Query, rank_prompts, search_prompts};
 
 #[test]
 fn test_fuzzy_search_prompts() {
@@ -8,18 +8,21 @@ fn test_fuzzy_search_prompts() {
             content: "This is the first prompt.".to_string(),
             tags: Some(vec!["tagA".to_string(), "tagB".to_string()]),
             categories: Some(vec!["catX".to_string()]),
+            ..Default::default()
         },
         Prompt {
             hash: "2".to_string(),
             content: "Second prompt here.".to_string(),
             tags: Some(vec!["tagB".to_string(), "tagC".to_string()]),
             categories: Some(vec!["catY".to_string()]),
+            ..Default::default()
         },
         Prompt {
             hash: "3".to_string(),
             content: "A third one for testing.".to_string(),
             tags: Some(vec!["tagA".to_string()]),
             categories: Some(vec!["catX".to_string(), "catZ".to_string()]),
+            ..Default::default()
         },
     ];
 
@@ -47,3 +50,31 @@ fn test_fuzzy_search_prompts() {
     assert_eq!(results.len(), 0);
 }
 
+
+#[test]
+fn test_rank_prompts_orders_by_score() {
+    let prompts = vec![
+        Prompt::new("Write a poem about the sea and the ships that sail it", None, None),
+        Prompt::new("Translate this text", None, None),
+        Prompt::new("Write a poem", None, None),
+    ];
+
+    let results = rank_prompts(&prompts, &Query::Fuzzy("write poem".to_string()));
+    assert_eq!(results.len(), 2);
+    assert!(results[0].score >= results[1].score);
+    // Equal scores go to the shorter prompt.
+    assert_eq!(results[0].prompt.content, "Write a poem");
+    assert_eq!(results[0].indices.len(), "write poem".len());
+
+    let results = rank_prompts(&prompts, &Query::all());
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(|r| r.score == 0.0 && r.indices.is_empty()));
+
+    // Among equal scores, prompts used more often and then updated more recently come first.
+    let mut prompts = prompts;
+    prompts[0].use_count = 2;
+    prompts[1].updated_at = Some(chrono::Utc::now());
+    let results = rank_prompts(&prompts, &Query::all());
+    let contents: Vec<&str> = results.iter().map(|r| r.prompt.content.as_str()).collect();
+    assert_eq!(contents, vec![prompts[0].content.as_str(), "Translate this text", "Write a poem"]);
+}
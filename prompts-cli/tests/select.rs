@@ -0,0 +1,113 @@
+use predicates::prelude::*;
+use prompts_cli::{
+    storage::{JsonStorage, LibSQLStorage, Storage},
+    Prompt, Prompts,
+};
+use std::fs;
+use tempfile::tempdir;
+
+/// Twenty prompts, so that at least two share the first hex digit of their hash.
+fn prompts() -> Vec<Prompt> {
+    (0..20).map(|i| Prompt::new(&format!("Prompt number {}", i), None, None)).collect()
+}
+
+fn shared_prefix(prompts: &[Prompt]) -> String {
+    let first = prompts
+        .iter()
+        .find(|p| prompts.iter().filter(|q| q.hash[..1] == p.hash[..1]).count() > 1)
+        .unwrap();
+    first.hash[..1].to_string()
+}
+
+async fn test_find_by_hash_impl(storage_type: &str) -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage: Box<dyn Storage + Send + Sync> = if storage_type == "json" {
+        Box::new(JsonStorage::new(Some(dir.path().to_path_buf()))?)
+    } else {
+        Box::new(LibSQLStorage::new(Some(dir.path().join("test.db"))).await?)
+    };
+    let prompts_api = Prompts::new(storage);
+    let mut prompts = prompts();
+    for prompt in &mut prompts {
+        prompts_api.add_prompt(prompt).await?;
+    }
+
+    let target = &prompts[3];
+    assert_eq!(prompts_api.find_by_hash(&target.hash[..12]).await?.hash, target.hash);
+    assert_eq!(prompts_api.find_by_hash(&target.hash[..12].to_uppercase()).await?.hash, target.hash);
+    let found = prompts_api.show_prompt(&format!("@{}", &target.hash[..8]), None).await?;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].hash, target.hash);
+
+    // Anything else after `@` is searched for like any other text.
+    let mut mention = Prompt::new("Ask @team for a review", None, None);
+    prompts_api.add_prompt(&mut mention).await?;
+    assert_eq!(prompts_api.show_prompt("@team", None).await?[0].hash, mention.hash);
+
+    let prefix = shared_prefix(&prompts);
+    let err = prompts_api.find_by_hash(&prefix).await.unwrap_err().to_string();
+    assert!(err.starts_with(&format!("Hash prefix `{}` is ambiguous", prefix)), "{}", err);
+    for prompt in prompts.iter().filter(|p| p.hash.starts_with(&prefix)) {
+        assert!(err.contains(&format!("  {} - {}", &prompt.hash[..12], prompt.content)), "{}", err);
+    }
+
+    assert!(prompts_api.find_by_hash("%").await.unwrap_err().to_string().contains("Invalid hash prefix"));
+    let missing = if target.hash.starts_with("ffff") { "0000" } else { "ffff" };
+    let err = prompts_api.find_by_hash(&format!("{}{}", missing, &target.hash[4..12])).await.unwrap_err();
+    assert!(err.to_string().starts_with("No prompt found with hash"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_find_by_hash_json() -> anyhow::Result<()> {
+    test_find_by_hash_impl("json").await
+}
+
+#[tokio::test]
+async fn test_find_by_hash_libsql() -> anyhow::Result<()> {
+    test_find_by_hash_impl("libsql").await
+}
+
+#[tokio::test]
+async fn test_cli_select_by_hash() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage_path = dir.path().join("prompts");
+    fs::create_dir_all(&storage_path)?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(&config_path, format!("[storage]\npath = {:?}\n", storage_path.to_string_lossy()))?;
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(storage_path))?));
+    let mut first = Prompt::new("Summarise {{ text }} briefly", None, None);
+    let mut second = Prompt::new("Summarise {{ text }} in detail", None, None);
+    prompts_api.add_prompt(&mut first).await?;
+    prompts_api.add_prompt(&mut second).await?;
+
+    let cli = |args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli").unwrap();
+        cmd.arg("--config").arg(&config_path).args(args);
+        cmd
+    };
+
+    cli(&["show", &format!("@{}", &second.hash[..12])])
+        .assert()
+        .success()
+        .stdout("Summarise {{ text }} in detail\n");
+    cli(&["generate", "--hash", &first.hash[..12], "-v", "text=this"])
+        .assert()
+        .success()
+        .stdout("Summarise this briefly\n");
+    cli(&["edit", "--hash", &first.hash[..12], "--add-tags", "short"]).assert().success();
+    cli(&["list", "tag:short"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("briefly").and(predicates::str::contains("in detail").not()));
+
+    cli(&["show", "Summarise", "--hash", &first.hash[..12]])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("either a query or --hash"));
+    cli(&["show", "--hash", "zz"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Invalid hash prefix `zz`"));
+    Ok(())
+}
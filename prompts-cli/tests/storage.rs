@@ -1,6 +1,6 @@
 use prompts_cli::{
-    Prompt,
-    storage::{Storage, JsonStorage, LibSQLStorage}
+    Prompt, Query,
+    storage::{Storage, StorageQuery, JsonStorage, LibSQLStorage}
 };
 use tempfile::tempdir;
 use libsql::{Builder, Value};
@@ -118,3 +118,81 @@ async fn test_json_storage() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_libsql_query_prompts() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let db_path = dir.path().join("test.db");
+    let storage = LibSQLStorage::new(Some(db_path.clone())).await?;
+
+    let mut prompt1 = Prompt::new("Review the code and list every bug with its line number", Some(vec!["rust".to_string()]), None);
+    storage.save_prompt(&mut prompt1).await?;
+    let mut prompt2 = Prompt::new("Review code", Some(vec!["go".to_string()]), Some(vec!["review".to_string()]));
+    storage.save_prompt(&mut prompt2).await?;
+    let mut prompt3 = Prompt::new("Write a haiku", Some(vec!["rust".to_string()]), None);
+    storage.save_prompt(&mut prompt3).await?;
+
+    let query = StorageQuery { filter: Query::parse("rev code")?, ..Default::default() };
+    let results = storage.query_prompts(&query).await?.unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].prompt.hash, prompt2.hash);
+    assert!(results[0].score >= results[1].score);
+
+    let query = StorageQuery { filter: Query::parse("code tag:rust")?, ..Default::default() };
+    let results = storage.query_prompts(&query).await?.unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].prompt.hash, prompt1.hash);
+
+    let query = StorageQuery { filter: Query::parse("category:review")?, ..Default::default() };
+    let results = storage.query_prompts(&query).await?.unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].prompt.hash, prompt2.hash);
+
+    let query = StorageQuery { filter: Query::parse("tag:rust")?, limit: Some(1) };
+    let results = storage.query_prompts(&query).await?.unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].prompt.hash, prompt3.hash);
+
+    // The index follows deletes.
+    storage.delete_prompt(&prompt2.hash).await?;
+    let query = StorageQuery { filter: Query::parse("review")?, ..Default::default() };
+    let results = storage.query_prompts(&query).await?.unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].prompt.hash, prompt1.hash);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_libsql_builds_index_for_existing_database() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let db_path = dir.path().join("test.db");
+    {
+        let db = Builder::new_local(db_path.to_str().unwrap()).build().await?;
+        let conn = db.connect()?;
+        conn.execute(
+            "CREATE TABLE prompts (hash TEXT PRIMARY KEY, content TEXT NOT NULL, tags TEXT, categories TEXT, id TEXT)",
+            (),
+        ).await?;
+        conn.execute(
+            "INSERT INTO prompts (hash, content, tags, categories, id) VALUES ('abc', 'An existing prompt', '[]', '[]', 'abc')",
+            (),
+        ).await?;
+    }
+
+    let storage = LibSQLStorage::new(Some(db_path)).await?;
+    let query = StorageQuery { filter: Query::parse("existing")?, ..Default::default() };
+    let results = storage.query_prompts(&query).await?.unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].prompt.hash, "abc");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_json_storage_has_no_native_query() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage = JsonStorage::new(Some(dir.path().to_path_buf()))?;
+    assert!(storage.query_prompts(&StorageQuery::default()).await?.is_none());
+    Ok(())
+}
@@ -0,0 +1,243 @@
+use prompts_cli::template::{
+    env_variables, missing_variables, read_vars_file, template_variables, ComposedTemplate,
+    InclusionKind, TemplateVariable, VariableSources,
+};
+use std::fs;
+use tempfile::tempdir;
+use prompts_cli::Prompt;
+
+fn names(template: &str) -> Vec<(String, bool)> {
+    template_variables(template)
+        .unwrap()
+        .into_iter()
+        .map(|TemplateVariable { name, required }| (name, required))
+        .collect()
+}
+
+fn required(name: &str) -> (String, bool) {
+    (name.to_string(), true)
+}
+
+fn render(template: &str, context: &tera::Context) -> anyhow::Result<String> {
+    ComposedTemplate::new(&Prompt::new(template, None, None), &[])?.render(context)
+}
+
+fn optional(name: &str) -> (String, bool) {
+    (name.to_string(), false)
+}
+
+#[test]
+fn test_template_variables() {
+    assert_eq!(names("Hello, {{ name }}!"), vec![required("name")]);
+    assert_eq!(names("No variables here."), vec![]);
+    assert_eq!(
+        names("{{ user.name | upper }} wrote {{ items[index] }} and {{ items[0] }}"),
+        vec![required("index"), required("items"), required("user")]
+    );
+}
+
+#[test]
+fn test_template_variables_in_loops_and_conditionals() {
+    let template = r#"
+{% for item in items %}
+  {{ loop.index }}. {{ item.title }} by {{ author }}
+{% endfor %}
+{% if verbose %}{{ details }}{% elif mode == "short" %}{{ summary }}{% endif %}
+{% set greeting = "Hi " ~ name %}{{ greeting }}
+{% if extra is defined %}{{ extra }}{% endif %}
+{{ tone | default(value="neutral") }}
+"#;
+    assert_eq!(
+        names(template),
+        vec![
+            required("author"),
+            required("details"),
+            required("extra"),
+            required("items"),
+            required("mode"),
+            required("name"),
+            required("summary"),
+            optional("tone"),
+            optional("verbose"),
+        ]
+    );
+}
+
+#[test]
+fn test_template_variables_ignore_macro_bodies() {
+    let template = r#"{% macro greet(who) %}Hello {{ who }}{% endmacro greet %}{{ self::greet(who=person) }}"#;
+    assert_eq!(names(template), vec![required("person")]);
+}
+
+#[test]
+fn test_template_variables_invalid_template() {
+    assert!(template_variables("{{ unclosed").is_err());
+}
+
+#[test]
+fn test_missing_variables() {
+    let variables = template_variables("{{ a }} {{ b }} {{ c | default(value=1) }}").unwrap();
+    let mut context = tera::Context::new();
+    context.insert("a", "value");
+    assert_eq!(missing_variables(&variables, &context), vec!["b".to_string()]);
+}
+
+#[test]
+fn test_compose_includes_and_macros() -> anyhow::Result<()> {
+    let tone = Prompt::new("Answer in a {{ tone }} tone.", None, None);
+    let macros = Prompt::new(
+        r#"{% macro bullet(text) %}- {{ text }}{% endmacro bullet %}"#,
+        None,
+        None,
+    );
+    let root = Prompt::new(
+        &format!(
+            r#"{{% import "{}" as m %}}Review {{{{ code }}}}. {{% include "{}" %}}
+{{{{ m::bullet(text="be brief") }}}}"#,
+            &macros.hash[..8],
+            tone.hash
+        ),
+        None,
+        None,
+    );
+    let prompts = vec![tone.clone(), macros.clone(), root.clone()];
+
+    let composed = ComposedTemplate::new(&root, &prompts)?;
+    let kinds: Vec<(InclusionKind, &str)> = composed
+        .inclusions
+        .iter()
+        .map(|i| (i.kind, i.hash.as_str()))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![(InclusionKind::Import, macros.hash.as_str()), (InclusionKind::Include, tone.hash.as_str())]
+    );
+    assert_eq!(
+        composed
+            .variables()?
+            .into_iter()
+            .map(|v| v.name)
+            .collect::<Vec<_>>(),
+        vec!["code".to_string(), "tone".to_string()]
+    );
+
+    let mut context = tera::Context::new();
+    context.insert("code", "main.rs");
+    context.insert("tone", "friendly");
+    assert_eq!(
+        composed.render(&context)?,
+        "Review main.rs. Answer in a friendly tone.\n- be brief"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_compose_nested_includes_by_id() -> anyhow::Result<()> {
+    let mut inner = Prompt::new("inner", None, None);
+    inner.id = "inner-id".to_string();
+    let middle = Prompt::new(r#"middle {% include "inner-id" %}"#, None, None);
+    let root = Prompt::new(&format!(r#"root {{% include "{}" %}}"#, middle.hash), None, None);
+    let prompts = vec![inner, middle, root.clone()];
+
+    let composed = ComposedTemplate::new(&root, &prompts)?;
+    let depths: Vec<(&str, usize)> = composed
+        .inclusions
+        .iter()
+        .map(|i| (i.name.as_str(), i.depth))
+        .collect();
+    assert_eq!(depths, vec![(prompts[1].hash.as_str(), 1), ("inner-id", 2)]);
+    assert_eq!(composed.render(&tera::Context::new())?, "root middle inner");
+    Ok(())
+}
+
+#[test]
+fn test_compose_errors() {
+    let mut a = Prompt::new("", None, None);
+    a.id = "a".to_string();
+    a.content = r#"A {% include "b" %}"#.to_string();
+    let mut b = Prompt::new("", None, None);
+    b.id = "b".to_string();
+    b.hash = "b-hash".to_string();
+    b.content = r#"B {% include "a" %}"#.to_string();
+    let prompts = vec![a.clone(), b];
+
+    let err = ComposedTemplate::new(&a, &prompts).err().unwrap();
+    assert!(err.to_string().contains("cycle"), "{}", err);
+
+    let root = Prompt::new(r#"{% include "nowhere" %}"#, None, None);
+    let err = ComposedTemplate::new(&root, &prompts).err().unwrap();
+    assert_eq!(err.to_string(), "Included prompt `nowhere` not found");
+
+    let root = Prompt::new(r#"Kept{% include "nowhere" ignore missing %}"#, None, None);
+    let composed = ComposedTemplate::new(&root, &prompts).unwrap();
+    assert!(composed.inclusions.is_empty());
+    assert_eq!(composed.render(&tera::Context::new()).unwrap(), "Kept");
+}
+
+#[test]
+fn test_read_vars_file_formats() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let template = "{{ project.name }}:{% for item in items %} {{ item }}{% endfor %}";
+    let files = [
+        ("vars.json", r#"{"project": {"name": "cli"}, "items": ["a", "b"]}"#),
+        ("vars.toml", "items = [\"a\", \"b\"]\n[project]\nname = \"cli\"\n"),
+        ("vars.yaml", "project:\n  name: cli\nitems:\n  - a\n  - b\n"),
+    ];
+    for (name, contents) in files {
+        let path = dir.path().join(name);
+        fs::write(&path, contents)?;
+        assert_eq!(render(template, &read_vars_file(&path)?)?, "cli: a b", "{}", name);
+    }
+
+    let path = dir.path().join("vars.txt");
+    fs::write(&path, "name=cli")?;
+    assert!(read_vars_file(&path).unwrap_err().to_string().contains("Unsupported variables file"));
+
+    let path = dir.path().join("list.json");
+    fs::write(&path, "[1, 2]")?;
+    assert!(read_vars_file(&path).unwrap_err().to_string().contains("table of variables"));
+    Ok(())
+}
+
+#[test]
+fn test_env_variables() {
+    let vars = vec![
+        ("PROMPT_USER_NAME".to_string(), "Ada".to_string()),
+        ("PROMPT_".to_string(), "ignored".to_string()),
+        ("HOME".to_string(), "/home/ada".to_string()),
+        ("PROMPT_TONE".to_string(), "dry".to_string()),
+    ];
+    assert_eq!(
+        env_variables("PROMPT_", vars),
+        vec![
+            ("tone".to_string(), "dry".to_string()),
+            ("user_name".to_string(), "Ada".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_variable_sources_precedence() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let first = dir.path().join("first.json");
+    fs::write(&first, r#"{"a": "first", "b": "first", "c": "first"}"#)?;
+    let second = dir.path().join("second.yaml");
+    fs::write(&second, "b: second\nc: second\n")?;
+    let content = dir.path().join("content.txt");
+    fs::write(&content, "from file")?;
+
+    let sources = VariableSources {
+        files: vec![first, second],
+        env_prefix: None,
+        file_contents: vec![("c".to_string(), content), ("d".to_string(), dir.path().join("content.txt"))],
+        values: vec![("d".to_string(), "value".to_string())],
+    };
+    assert_eq!(render("{{ a }} {{ b }} {{ c }} {{ d }}", &sources.context()?)?, "first second from file value");
+
+    let sources = VariableSources {
+        file_contents: vec![("c".to_string(), dir.path().join("missing.txt"))],
+        ..Default::default()
+    };
+    assert!(sources.context().is_err());
+    Ok(())
+}
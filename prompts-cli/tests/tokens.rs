@@ -0,0 +1,131 @@
+use predicates::prelude::*;
+use prompts_cli::{
+    tokens::{count_tokens, Encoding, TokensConfig},
+    JsonStorage, Prompt, Prompts,
+};
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_count_tokens() {
+    assert_eq!(count_tokens("", Encoding::Cl100kBase), 0);
+    assert_eq!(count_tokens("hello world", Encoding::Cl100kBase), 2);
+    assert_eq!(count_tokens("hello world", Encoding::O200kBase), 2);
+    assert!(count_tokens("{{ name }}", Encoding::O200kBase) > 0);
+
+    assert_eq!("cl100k".parse(), Ok(Encoding::Cl100kBase));
+    assert_eq!("o200k_base".parse(), Ok(Encoding::O200kBase));
+    assert!("p50k_base".parse::<Encoding>().is_err());
+    assert_eq!(Encoding::for_model("gpt-4o"), Some(Encoding::O200kBase));
+    assert_eq!(Encoding::for_model("gpt-4"), Some(Encoding::Cl100kBase));
+    assert_eq!(Encoding::for_model("llama3.2"), None);
+}
+
+#[test]
+fn test_token_budget() {
+    let default = TokensConfig::default().budget(None, None);
+    assert_eq!(default.encoding, Encoding::O200kBase);
+    assert_eq!(default.context_limit, None);
+    assert!(!default.count("hello world").exceeds_limit());
+
+    let config = TokensConfig {
+        model: Some("gpt-4".to_string()),
+        encoding: None,
+        context_limit: None,
+    };
+    let budget = config.budget(None, None);
+    assert_eq!(budget.encoding, Encoding::Cl100kBase);
+    assert_eq!(budget.context_limit, Some(8192));
+
+    // A model given for one command replaces the configured one entirely.
+    let budget = config.budget(Some("gpt-4o"), None);
+    assert_eq!(budget.encoding, Encoding::O200kBase);
+    assert_eq!(budget.context_limit, Some(128_000));
+    assert_eq!(config.budget(Some("llama3.2"), None).context_limit, None);
+    assert_eq!(config.budget(None, Some(Encoding::O200kBase)).encoding, Encoding::O200kBase);
+
+    let config = TokensConfig {
+        model: Some("llama3.2".to_string()),
+        encoding: Some(Encoding::Cl100kBase),
+        context_limit: Some(2),
+    };
+    let count = config.budget(None, None).count("one two three");
+    assert_eq!(count.encoding, Encoding::Cl100kBase);
+    assert_eq!(count.tokens, 3);
+    assert!(count.exceeds_limit());
+    assert_eq!(count.to_string(), "3 tokens (cl100k_base), 150.0% of the 2-token context of llama3.2");
+
+    // A configured context limit also applies to a model given for one command.
+    assert_eq!(config.budget(Some("gpt-4o"), None).context_limit, Some(2));
+}
+
+#[tokio::test]
+async fn test_cli_count_tokens() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage_path = dir.path().join("prompts");
+    fs::create_dir_all(&storage_path)?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "[storage]\npath = {:?}\n\n[tokens]\nmodel = \"gpt-4o\"\n",
+            storage_path.to_string_lossy()
+        ),
+    )?;
+    let prompts_api = Prompts::new(Box::new(JsonStorage::new(Some(storage_path.clone()))?));
+    let mut prompt = Prompt::new("hello {{ name }}", None, None);
+    prompts_api.add_prompt(&mut prompt).await?;
+
+    let cli = |args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli").unwrap();
+        cmd.arg("--config").arg(&config_path).args(args);
+        cmd
+    };
+
+    cli(&["tokens", "hello", "-v", "name=world"])
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with("2 tokens (o200k_base), 0.0% of the 128000-token context of gpt-4o"));
+
+    cli(&["tokens", "hello"])
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("Counting the unrendered template, missing variables: name"));
+
+    let output = cli(&["--output", "json", "tokens", "hello", "-v", "name=world", "--encoding", "cl100k", "--model", "llama3.2"])
+        .output()?;
+    let count: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(count["tokens"], 2);
+    assert_eq!(count["encoding"], "cl100k_base");
+    assert_eq!(count["model"], "llama3.2");
+    assert_eq!(count["context_limit"], serde_json::Value::Null);
+
+    cli(&["show", "hello", "--count-tokens"])
+        .assert()
+        .success()
+        .stdout("hello {{ name }}\n")
+        .stderr(predicates::str::contains("tokens (o200k_base)"));
+
+    let list = format!("{} {:>6} - hello {{{{ name }}}}", &prompt.hash[..12], count_tokens("hello {{ name }}", Encoding::O200kBase));
+    cli(&["list", "--count-tokens"]).assert().success().stdout(predicates::str::contains(list));
+
+    fs::write(
+        &config_path,
+        format!(
+            "[storage]\npath = {:?}\n\n[tokens]\ncontext_limit = 1\n",
+            storage_path.to_string_lossy()
+        ),
+    )?;
+    cli(&["generate", "hello", "-v", "name=world", "--count-tokens"])
+        .assert()
+        .success()
+        .stdout("hello world\n")
+        .stderr(predicates::str::contains("Warning: 2 tokens exceeds the 1-token context limit"));
+
+    cli(&["generate", "hello", "-v", "name=world"])
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("tokens").not());
+
+    Ok(())
+}
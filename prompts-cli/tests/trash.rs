@@ -0,0 +1,139 @@
+use chrono::{Duration, Utc};
+use predicates::prelude::*;
+use prompts_cli::{
+    core::parse_age,
+    storage::{JsonStorage, LibSQLStorage, Storage},
+    Prompt, Prompts,
+};
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_parse_age() {
+    let now = Utc::now();
+    let thirty_days = parse_age("30d").unwrap();
+    assert!((now - thirty_days - Duration::days(30)).num_seconds().abs() < 5);
+    assert!((now - parse_age("2w").unwrap() - Duration::weeks(2)).num_seconds().abs() < 5);
+    assert_eq!(parse_age("2024-05-01").unwrap().to_rfc3339(), "2024-05-01T00:00:00+00:00");
+    assert!(parse_age("soon").is_err());
+}
+
+async fn test_trash_impl(storage_type: &str) -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage: Box<dyn Storage + Send + Sync> = if storage_type == "json" {
+        Box::new(JsonStorage::new(Some(dir.path().to_path_buf()))?)
+    } else {
+        Box::new(LibSQLStorage::new(Some(dir.path().join("test.db"))).await?)
+    };
+    let prompts_api = Prompts::new(storage);
+    let mut greeting = Prompt::new("Hello, how are you?", Some(vec!["greeting".to_string()]), None);
+    greeting.name = Some("hello".to_string());
+    let mut farewell = Prompt::new("Goodbye, see you soon!", None, None);
+    prompts_api.add_prompt(&mut greeting).await?;
+    prompts_api.add_prompt(&mut farewell).await?;
+
+    prompts_api.delete_prompt(&greeting.hash).await?;
+    assert_eq!(prompts_api.list_prompts(None).await?.len(), 1);
+    assert_eq!(prompts_api.show_prompt("Hello", None).await?.len(), 0);
+    assert!(prompts_api.find_by_hash(&greeting.hash[..12]).await.is_err());
+    assert_eq!(prompts_api.find_by_name("hello").await?, None);
+
+    let trash = prompts_api.list_trash().await?;
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0].hash, greeting.hash);
+    assert!(trash[0].deleted_at.is_some());
+
+    // The name is free while the prompt is in the trash, but blocks restoring it.
+    let mut other = Prompt::new("Hi", None, None);
+    other.name = Some("hello".to_string());
+    prompts_api.add_prompt(&mut other).await?;
+    let err = prompts_api.restore_prompt(&greeting.hash[..12]).await.unwrap_err();
+    assert!(err.to_string().contains("Name `hello` is already used"), "{}", err);
+    assert_eq!(prompts_api.list_trash().await?.len(), 1);
+    prompts_api.delete_prompt(&other.hash).await?;
+
+    let restored = prompts_api.restore_prompt(&greeting.hash[..12]).await?;
+    assert_eq!(restored.hash, greeting.hash);
+    let found = prompts_api.find_by_name("hello").await?.unwrap();
+    assert_eq!(found.tags, Some(vec!["greeting".to_string()]));
+    assert_eq!(found.deleted_at, None);
+    assert_eq!(prompts_api.show_prompt("Hello", None).await?.len(), 1);
+    assert!(prompts_api.restore_prompt(&greeting.hash[..12]).await.unwrap_err().to_string().starts_with("No prompt in the trash"));
+
+    // Only `other` is left in the trash.
+    prompts_api.delete_prompt(&farewell.hash).await?;
+    assert_eq!(prompts_api.purge_trash(Some(Utc::now() - Duration::days(1))).await?.len(), 0);
+    assert_eq!(prompts_api.purge_trash(None).await?.len(), 2);
+    assert!(prompts_api.list_trash().await?.is_empty());
+
+    // Adding a prompt again takes it out of the trash.
+    prompts_api.delete_prompt(&greeting.hash).await?;
+    prompts_api.add_prompt(&mut Prompt::new("Hello, how are you?", None, None)).await?;
+    assert!(prompts_api.list_trash().await?.is_empty());
+    assert_eq!(prompts_api.list_prompts(None).await?.len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_trash_json() -> anyhow::Result<()> {
+    test_trash_impl("json").await
+}
+
+#[tokio::test]
+async fn test_trash_libsql() -> anyhow::Result<()> {
+    test_trash_impl("libsql").await
+}
+
+#[test]
+fn test_cli_trash() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let storage_path = dir.path().join("prompts");
+    fs::create_dir_all(&storage_path)?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(&config_path, format!("[storage]\npath = {:?}\n", storage_path.to_string_lossy()))?;
+
+    let cli = |args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("prompts-cli").unwrap();
+        cmd.arg("--config").arg(&config_path).args(args);
+        cmd
+    };
+
+    let prompt = Prompt::new("Summarise {{ text }}", None, None);
+    let hash = &prompt.hash[..12];
+    cli(&["add", "Summarise {{ text }}"]).assert().success();
+    cli(&["delete", "Summarise", "--yes"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!("Run `restore {}` to undo", hash)));
+    cli(&["list"]).assert().success().stdout("");
+    cli(&["trash", "list"])
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with(hash).and(predicates::str::contains("Summarise {{ text }}")));
+
+    cli(&["restore", hash]).assert().success();
+    cli(&["show", "Summarise"]).assert().success().stdout("Summarise {{ text }}\n");
+    cli(&["restore", hash])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("No prompt in the trash"));
+
+    // Without a terminal there is no one to ask, so `delete` needs `--yes`.
+    cli(&["delete", "Summarise"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("pass --yes to delete without confirmation"));
+    cli(&["trash", "purge"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("pass --yes to delete without confirmation"));
+    cli(&["delete", "Summarise", "--yes"]).assert().success();
+    cli(&["trash", "purge", "--older-than", "1d", "--yes"]).assert().success().stdout("Purged 0 prompts.\n");
+    cli(&["trash", "purge", "--yes"]).assert().success().stdout("Purged 1 prompts.\n");
+    cli(&["trash", "list"]).assert().success().stdout("");
+    cli(&["trash", "purge", "--older-than", "soon"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("invalid age"));
+    Ok(())
+}